@@ -2,13 +2,16 @@ mod file;
 mod hetzner;
 mod mock;
 
-use act_zero::{Actor, ActorResult, Addr};
+use act_zero::{Actor, ActorResult, Addr, Produces};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
 
+use crate::config::NodeRecoveryAction;
 use crate::node::discovery::NodeDiscoveryState;
+use crate::node::exploration::NodeExplorationObserver;
+use crate::node::QuarantineReason;
 use crate::AppConfig;
 use crate::{cloud_init, config, hetzner_cloud};
 use act_zero::runtimes::tokio::spawn_actor;
@@ -22,19 +25,107 @@ pub struct CloudNodeInfo {
     pub group: String,
     pub created_at: DateTime<Utc>,
     pub ip_addresses: Vec<IpAddr>,
+    /// Addresses within private networks, these never get published via dns
+    #[serde(default)]
+    pub private_ip_addresses: Vec<IpAddr>,
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub server_type: Option<String>,
+    /// Id of the image the node was created from, to detect nodes running an outdated image
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub quarantine: Option<QuarantineStatus>,
+}
+
+/// Group specific settings for creating a node, providers ignore the settings of other providers
+#[derive(Debug, Clone, Default)]
+pub struct NodeSpec {
+    /// Location picked by the scaler, overrides the default location of the provider
+    pub location: Option<String>,
+    pub hetzner: Option<config::HetznerNodeGroup>,
+}
+
+/// Progress of a created node until it's up and running
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProvisioningStatus {
+    Pending,
+    Completed,
+    Failed(String),
+}
+
+/// Quarantine marker as found on the cloud server, an operator releases a quarantined node by
+/// replacing the recorded reason with `released`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub enum QuarantineStatus {
+    Quarantined {
+        reason: QuarantineReason,
+        /// Start of the quarantine, so its duration survives restarts of the autoscaler
+        #[serde(default)]
+        since: Option<DateTime<Utc>>,
+    },
+    Released,
 }
 
 #[async_trait]
 pub trait CloudProvider: Actor {
     async fn get_node_info(&mut self, hostname: String) -> ActorResult<Option<CloudNodeInfo>>;
+    /// Whether the node still exists, even if it no longer maps to a valid node info
+    async fn node_exists(&mut self, node_info: CloudNodeInfo) -> ActorResult<bool>;
     async fn create_node(
         &mut self,
         hostname: String,
         group: String,
         target_state: NodeDiscoveryState,
+        spec: NodeSpec,
     ) -> ActorResult<CloudNodeInfo>;
     async fn delete_node(&mut self, node_info: CloudNodeInfo) -> ActorResult<()>;
+    async fn quarantine_node(
+        &mut self,
+        node_info: CloudNodeInfo,
+        reason: QuarantineReason,
+        since: DateTime<Utc>,
+    ) -> ActorResult<()>;
+    async fn recover_node(
+        &mut self,
+        node_info: CloudNodeInfo,
+        action: NodeRecoveryAction,
+    ) -> ActorResult<()>;
     async fn get_nodes(&mut self) -> ActorResult<Vec<CloudNodeInfo>>;
+
+    /// Providers that create nodes synchronously report them as completed right away
+    async fn get_provisioning_status(
+        &mut self,
+        _node_info: CloudNodeInfo,
+    ) -> ActorResult<ProvisioningStatus> {
+        Produces::ok(ProvisioningStatus::Completed)
+    }
+
+    /// Points the reverse dns entries of the public addresses to the hostname, providers without
+    /// reverse dns management skip this
+    async fn create_ptr_records(&mut self, _node_info: CloudNodeInfo) -> ActorResult<()> {
+        Produces::ok(())
+    }
+
+    /// Corrects drift between an explored node and the spec of its group, like manually changed
+    /// firewall assignments
+    async fn reconcile_node(
+        &mut self,
+        _node_info: CloudNodeInfo,
+        _spec: NodeSpec,
+    ) -> ActorResult<()> {
+        Produces::ok(())
+    }
+
+    /// Starts pushing changed nodes to the observer as soon as they happen, providers without
+    /// change notifications rely on the periodic exploration only
+    async fn watch_nodes(
+        &mut self,
+        _observer: Addr<dyn NodeExplorationObserver>,
+    ) -> ActorResult<()> {
+        Produces::ok(())
+    }
 }
 
 pub fn build_from_config(config: AppConfig) -> anyhow::Result<Addr<dyn CloudProvider>> {
@@ -42,22 +133,29 @@ pub fn build_from_config(config: AppConfig) -> anyhow::Result<Addr<dyn CloudProv
         config::CloudProvider::File {
             exploration_path,
             discovery_path,
+            watch,
         } => upcast!(spawn_actor(FileCloudProvider::new(
             exploration_path,
-            discovery_path
+            discovery_path,
+            watch.clone(),
         ))),
         config::CloudProvider::Hetzner {
             server_type,
             image,
+            image_refresh_interval,
             ssh_keys,
             group_label_name,
             api_address,
             api_token,
+            api_rate_limit,
             location,
+            quarantine_label_name,
+            unavailable_blacklist_duration,
         } => {
             let client = hetzner_cloud::Client::builder()
                 .address(api_address.clone())
                 .api_token(api_token.clone())
+                .rate_limit(*api_rate_limit)
                 .build()?;
 
             let user_data_generator =
@@ -69,8 +167,13 @@ pub fn build_from_config(config: AppConfig) -> anyhow::Result<Addr<dyn CloudProv
                     group_label_name: group_label_name.clone(),
                     server_type: server_type.clone(),
                     image: image.clone(),
+                    image_refresh_interval: *image_refresh_interval,
                     ssh_keys: ssh_keys.clone(),
                     location: location.clone(),
+                    quarantine_label_name: quarantine_label_name
+                        .clone()
+                        .unwrap_or_else(|| format!("{}-quarantine", group_label_name)),
+                    unavailable_blacklist_duration: *unavailable_blacklist_duration,
                 },
                 user_data_generator,
             );
@@ -1,20 +1,27 @@
-use crate::cloud_provider::{CloudNodeInfo, CloudProvider};
+use crate::cloud_provider::{CloudNodeInfo, CloudProvider, NodeSpec, QuarantineStatus};
+use crate::config::{FileWatch, NodeRecoveryAction};
+use crate::file_watcher::{FileChange, FileWatcher};
 use crate::node::discovery::{NodeDiscoveryData, NodeDiscoveryState};
+use crate::node::exploration::NodeExplorationObserver;
+use crate::node::QuarantineReason;
 use crate::utils::path_append;
 use crate::{actor, utils};
-use act_zero::{Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
+use act_zero::{send, Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
 use anyhow::Context;
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
 use std::path::{Path, PathBuf};
 use tracing::error;
-use tracing::info;
+use tracing::{info, warn};
 
 pub struct FileCloudProvider {
     exploration_directory: PathBuf,
     discovery_directory: PathBuf,
+    watch_config: Option<FileWatch>,
+    watcher: Option<FileWatcher>,
+    observer: Option<Addr<dyn NodeExplorationObserver>>,
     addr: WeakAddr<Self>,
 }
 
@@ -22,13 +29,45 @@ impl FileCloudProvider {
     pub fn new(
         exploration_directory: impl AsRef<Path>,
         discovery_directory: impl AsRef<Path>,
+        watch_config: Option<FileWatch>,
     ) -> Self {
         Self {
             exploration_directory: exploration_directory.as_ref().into(),
             discovery_directory: discovery_directory.as_ref().into(),
+            watch_config,
+            watcher: None,
+            observer: None,
             addr: Default::default(),
         }
     }
+
+    #[tracing::instrument(
+        name = "FileCloudProvider::handle_file_change",
+        skip(self),
+        fields(exploration_directory = %self.exploration_directory.display())
+    )]
+    async fn handle_file_change(&mut self, change: FileChange) {
+        let observer = match self.observer.as_ref() {
+            Some(v) => v,
+            None => return,
+        };
+
+        match change {
+            FileChange::Changed(path) => match parse_node_discovery_file(&path) {
+                Ok(node_info) => send!(observer.observe_node_exploration(node_info)),
+                Err(e) => warn!(
+                    error = format!("{:?}", e).as_str(),
+                    "Failed to parse changed node exploration file"
+                ),
+            },
+            FileChange::Removed(_) => {}
+            FileChange::Rescan => {
+                for node_info in scan_for_nodes(&self.exploration_directory).await {
+                    send!(observer.observe_node_exploration(node_info));
+                }
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -74,12 +113,19 @@ impl CloudProvider for FileCloudProvider {
         Produces::ok(node_info.ok())
     }
 
+    async fn node_exists(&mut self, node_info: CloudNodeInfo) -> ActorResult<bool> {
+        let path = path_append(self.exploration_directory.join(&node_info.hostname), ".yml");
+
+        Produces::ok(path.exists())
+    }
+
     #[tracing::instrument(name = "FileCloudProvider::create_node", skip(self))]
     async fn create_node(
         &mut self,
         hostname: String,
         group: String,
         target_state: NodeDiscoveryState,
+        spec: NodeSpec,
     ) -> ActorResult<CloudNodeInfo> {
         let node_info = CloudNodeInfo {
             identifier: format!("{}-identifier", hostname),
@@ -87,12 +133,18 @@ impl CloudProvider for FileCloudProvider {
             group: group.clone(),
             created_at: Utc::now(),
             ip_addresses: vec!["1.2.3.4".parse().unwrap()],
+            private_ip_addresses: vec![],
+            location: spec.location,
+            server_type: None,
+            image: None,
+            quarantine: None,
         };
 
         let discovery_data = NodeDiscoveryData {
             hostname: hostname.clone(),
             group: group.clone(),
             state: target_state,
+            datacenter: None,
         };
 
         let exploration_path = path_append(self.exploration_directory.join(&hostname), ".yml");
@@ -137,10 +189,88 @@ impl CloudProvider for FileCloudProvider {
         Produces::ok(())
     }
 
+    #[tracing::instrument(name = "FileCloudProvider::quarantine_node", skip(self))]
+    async fn quarantine_node(
+        &mut self,
+        node_info: CloudNodeInfo,
+        reason: QuarantineReason,
+        since: DateTime<Utc>,
+    ) -> ActorResult<()> {
+        let node_info = CloudNodeInfo {
+            quarantine: Some(QuarantineStatus::Quarantined {
+                reason,
+                since: Some(since),
+            }),
+            ..node_info
+        };
+
+        let exploration_path =
+            path_append(self.exploration_directory.join(&node_info.hostname), ".yml");
+
+        let result = File::create(&exploration_path)
+            .with_context(|| format!("Failed to create {}", &exploration_path.display()))
+            .map(BufWriter::new)
+            .and_then(|writer| {
+                serde_yaml::to_writer(writer, &node_info).map_err(anyhow::Error::new)
+            });
+
+        match result {
+            Ok(_) => Produces::ok(()),
+            Err(e) => {
+                error!("{:?}", e);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// File based nodes can't be reset or rebuilt, the recovery only waits for the node to be
+    /// discovered again
+    #[tracing::instrument(name = "FileCloudProvider::recover_node", skip(self))]
+    async fn recover_node(
+        &mut self,
+        node_info: CloudNodeInfo,
+        action: NodeRecoveryAction,
+    ) -> ActorResult<()> {
+        info!("Skip recovery action of file based node");
+
+        Produces::ok(())
+    }
+
     #[tracing::instrument(name = "FileCloudProvider::get_nodes", skip(self))]
     async fn get_nodes(&mut self) -> ActorResult<Vec<CloudNodeInfo>> {
         Produces::ok(scan_for_nodes(&self.exploration_directory).await)
     }
+
+    #[tracing::instrument(name = "FileCloudProvider::watch_nodes", skip(self, observer))]
+    async fn watch_nodes(
+        &mut self,
+        observer: Addr<dyn NodeExplorationObserver>,
+    ) -> ActorResult<()> {
+        let watch_config = match (&self.watch_config, &self.watcher) {
+            (Some(watch_config), None) => watch_config,
+            _ => return Produces::ok(()),
+        };
+
+        let addr = self.addr.clone();
+        let watcher = FileWatcher::new(
+            &self.exploration_directory,
+            watch_config.debounce,
+            move |c| send!(addr.handle_file_change(c)),
+        );
+
+        match watcher {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+                self.observer = Some(observer);
+            }
+            Err(e) => warn!(
+                error = format!("{:?}", e).as_str(),
+                "Failed to watch node exploration files, falling back to polling"
+            ),
+        }
+
+        Produces::ok(())
+    }
 }
 
 async fn scan_for_nodes(path: impl AsRef<Path>) -> Vec<CloudNodeInfo> {
@@ -1,31 +1,69 @@
 use crate::cloud_init::user_data::GenerateUserData;
-use crate::cloud_provider::{CloudNodeInfo, CloudProvider};
+use crate::cloud_provider::{
+    CloudNodeInfo, CloudProvider, NodeSpec, ProvisioningStatus, QuarantineStatus,
+};
+use crate::config::{HetznerFirewall, HetznerImage, NodeRecoveryAction};
+use crate::hetzner_cloud::actions::{Action, ActionStatus, Actions};
 use crate::hetzner_cloud::error::Error;
-use crate::hetzner_cloud::servers::{NewServer, Server, Servers};
+use crate::hetzner_cloud::firewalls::{ApplyFirewall, Firewall, FirewallResource, Firewalls};
+use crate::hetzner_cloud::images::{Image, ImageStatus, ImageType, Images};
+use crate::hetzner_cloud::placement_groups::{
+    NewPlacementGroup, PlacementGroup, PlacementGroups, PlacementType,
+    SPREAD_PLACEMENT_GROUP_SIZE_LIMIT,
+};
+use crate::hetzner_cloud::servers::{
+    ChangeDnsPtr, NewServer, NewServerFirewall, NewServerPublicNet, RebuildServer, Server,
+    ServerStatus, Servers, UpdateServer,
+};
 use crate::node::discovery::NodeDiscoveryState;
+use crate::node::QuarantineReason;
 use crate::{actor, hetzner_cloud};
 use act_zero::{Actor, ActorError, ActorResult, Addr, Produces};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
 use http::StatusCode;
 use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::debug;
 use tracing::error;
 use tracing::info;
 use tracing::warn;
 
+/// Resolved firewalls are reused for a while, so reconciling every explored node doesn't query
+/// the firewalls of its group again
+const FIREWALL_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Error codes of a failed server creation that are worth trying the next fallback for
+const FALLBACK_ERROR_CODES: [&str; 2] = ["resource_unavailable", "placement_error"];
+
+/// Server type and location of a new server
+type ServerOption = (String, Option<String>);
+
 pub struct HetznerCloudProvider<UDG: GenerateUserData> {
     client: hetzner_cloud::Client,
     config: Config,
     user_data_generator: UDG,
+    firewalls: HashMap<HetznerFirewall, (Vec<Firewall>, Instant)>,
+    /// Server options that recently ran out of capacity, with the time they became unavailable
+    unavailable_server_options: HashMap<ServerOption, Instant>,
+    /// Actions started by the creation of a server that didn't finish yet, by server id
+    pending_actions: HashMap<u64, Vec<u64>>,
+    /// Id of the image the label selector resolved to, with the time it got resolved
+    resolved_image: Option<(String, Instant)>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Config {
     pub group_label_name: String,
     pub server_type: String,
-    pub image: String,
+    pub image: HetznerImage,
+    pub image_refresh_interval: Duration,
     pub ssh_keys: Vec<String>,
     pub location: Option<String>,
+    pub quarantine_label_name: String,
+    pub unavailable_blacklist_duration: Duration,
 }
 
 impl<UDG: GenerateUserData> HetznerCloudProvider<UDG> {
@@ -34,7 +72,140 @@ impl<UDG: GenerateUserData> HetznerCloudProvider<UDG> {
             client,
             config,
             user_data_generator,
+            firewalls: HashMap::new(),
+            unavailable_server_options: HashMap::new(),
+            pending_actions: HashMap::new(),
+            resolved_image: None,
+        }
+    }
+
+    /// The preferred server type and location first, followed by the fallbacks of the group
+    fn server_options(&self, spec: &NodeSpec) -> Vec<ServerOption> {
+        let location = spec
+            .location
+            .clone()
+            .or_else(|| self.config.location.clone());
+
+        let mut server_options = vec![(self.config.server_type.clone(), location.clone())];
+
+        if let Some(hetzner) = spec.hetzner.as_ref() {
+            server_options.extend(hetzner.fallbacks.iter().map(|fallback| {
+                (
+                    fallback.server_type.clone(),
+                    fallback.location.clone().or_else(|| location.clone()),
+                )
+            }));
+        }
+
+        server_options
+    }
+
+    async fn resolve_firewalls(&mut self, references: &[HetznerFirewall]) -> Result<Vec<Firewall>> {
+        let mut resolved = vec![];
+
+        for reference in references {
+            if let Some(firewalls) = cached_firewalls(&self.firewalls, reference, Instant::now()) {
+                resolved.extend(firewalls.iter().cloned());
+                continue;
+            }
+
+            let (name, label_selector) = match reference {
+                HetznerFirewall::Name(name) => (Some(name.as_str()), None),
+                HetznerFirewall::LabelSelector(selector) => (None, Some(selector.as_str())),
+            };
+
+            let firewalls = self.client.search_firewalls(name, label_selector).await?;
+
+            // a node without its firewalls must never be created
+            if firewalls.is_empty() {
+                return Err(anyhow!("Failed to find firewall {:?}", reference));
+            }
+
+            resolved.extend(firewalls.iter().cloned());
+            self.firewalls
+                .insert(reference.clone(), (firewalls, Instant::now()));
         }
+
+        resolved.sort_by_key(|firewall| firewall.id);
+        resolved.dedup_by_key(|firewall| firewall.id);
+
+        Ok(resolved)
+    }
+
+    /// Resolves a label selector to the newest matching snapshot; the last resolved image stays
+    /// in use if looking for a newer one fails
+    async fn resolve_image(&mut self) -> Result<String> {
+        let label_selector = match &self.config.image {
+            HetznerImage::Name(name) => return Ok(name.clone()),
+            HetznerImage::LabelSelector { label_selector } => label_selector.clone(),
+        };
+
+        match &self.resolved_image {
+            Some((image, resolved_at))
+                if resolved_at.elapsed() < self.config.image_refresh_interval =>
+            {
+                return Ok(image.clone());
+            }
+            _ => {}
+        }
+
+        let image = match self.client.search_snapshots(&label_selector).await {
+            Ok(images) => match newest_snapshot(images) {
+                Some(image) => image,
+                None => return Err(anyhow!("Failed to find image {}", label_selector)),
+            },
+            Err(e) => return last_resolved_image(self.resolved_image.as_ref(), e),
+        };
+
+        let image_id = image.id.to_string();
+
+        if self.resolved_image.as_ref().map(|(id, _)| id) != Some(&image_id) {
+            info!(
+                image_id = image.id,
+                image = image.description.as_str(),
+                "Resolved image"
+            );
+        }
+
+        self.resolved_image = Some((image_id.clone(), Instant::now()));
+
+        Ok(image_id)
+    }
+
+    /// Fills the placement groups of the node group one after another and creates the next
+    /// partition once all of them reached the size limit
+    async fn find_or_create_placement_group(&self, group: &str) -> Result<u64> {
+        let label_selector = format!("{}={}", self.config.group_label_name, group);
+
+        let placement_groups = self
+            .client
+            .get_all_placement_groups(&label_selector)
+            .await?;
+
+        if let Some(placement_group_id) = available_placement_group(&placement_groups) {
+            return Ok(placement_group_id);
+        }
+
+        let name = next_placement_group_name(group, &placement_groups);
+
+        let mut labels = HashMap::new();
+        labels.insert(self.config.group_label_name.clone(), group.to_owned());
+
+        let placement_group = self
+            .client
+            .create_placement_group(&NewPlacementGroup {
+                name: &name,
+                placement_type: PlacementType::Spread,
+                labels: Some(&labels),
+            })
+            .await?;
+
+        info!(
+            placement_group = placement_group.name.as_str(),
+            "Created placement group"
+        );
+
+        Ok(placement_group.id)
     }
 }
 
@@ -67,30 +238,53 @@ where
         let server = self.client.search_server(&hostname).await;
 
         Produces::ok(match server {
-            Ok(Some(server)) => {
-                match create_cloud_node_info(server, &self.config.group_label_name) {
-                    Ok(v) => Some(v),
-                    Err(e) => {
-                        warn!("Failed to create cloud node info: {:?}", e);
-                        None
-                    }
+            Ok(Some(server)) => match create_cloud_node_info(server, &self.config) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    warn!("Failed to create cloud node info: {:?}", e);
+                    None
                 }
-            }
+            },
             Ok(None) => None,
             Err(e) => {
                 error!("Failed to get node info: {:?}", e);
 
-                None
+                return Err(e.into());
             }
         })
     }
 
+    #[tracing::instrument(
+        name = "HetznerCloudProvider::node_exists",
+        skip(self, node_info),
+        fields(hostname = %node_info.hostname)
+    )]
+    async fn node_exists(&mut self, node_info: CloudNodeInfo) -> ActorResult<bool> {
+        let server_id: u64 = node_info
+            .identifier
+            .parse()
+            .map_err(anyhow::Error::new)
+            .map_err(actor::Error::from)?;
+
+        match self.client.get_server(server_id).await {
+            Ok(_) => Produces::ok(true),
+            Err(Error::BadResponse { status, .. }) if status == StatusCode::NOT_FOUND => {
+                Produces::ok(false)
+            }
+            Err(e) => {
+                error!("Failed to fetch server: {:?}", e);
+                Err(e.into())
+            }
+        }
+    }
+
     #[tracing::instrument(name = "HetznerCloudProvider::create_node", skip(self))]
     async fn create_node(
         &mut self,
         hostname: String,
         group: String,
         target_state: NodeDiscoveryState,
+        spec: NodeSpec,
     ) -> ActorResult<CloudNodeInfo> {
         let mut labels = HashMap::new();
         labels.insert(self.config.group_label_name.clone(), group.clone());
@@ -104,10 +298,52 @@ where
                 }
             };
 
-        let server = NewServer {
+        let firewall_references = spec
+            .hetzner
+            .as_ref()
+            .map(|h| h.firewalls.as_slice())
+            .unwrap_or_default();
+
+        let firewalls = match self.resolve_firewalls(firewall_references).await {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to resolve firewalls: {:?}", e);
+                return Err(e.into());
+            }
+        };
+
+        let placement_group = match spec.hetzner.as_ref() {
+            Some(hetzner) if hetzner.spread_placement => {
+                match self.find_or_create_placement_group(&group).await {
+                    Ok(v) => Some(v),
+                    Err(e) => {
+                        error!("Failed to find or create placement group: {:?}", e);
+                        return Err(e.into());
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        let image = match self.resolve_image().await {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to resolve image: {:?}", e);
+                return Err(e.into());
+            }
+        };
+
+        let server_options = self.server_options(&spec);
+
+        let blacklist_duration = self.config.unavailable_blacklist_duration;
+        self.unavailable_server_options
+            .retain(|_, unavailable_at| unavailable_at.elapsed() < blacklist_duration);
+
+        // server type and location are set per server option
+        let new_server = NewServer {
             name: &hostname,
             server_type: &self.config.server_type,
-            image: &self.config.image,
+            image: &image,
             ssh_keys: self
                 .config
                 .ssh_keys
@@ -116,10 +352,50 @@ where
                 .collect(),
             user_data: Some(&user_data),
             labels: Some(&labels),
-            location: self.config.location.as_ref().map(|s| s.as_str()),
+            location: None,
+            networks: spec
+                .hetzner
+                .as_ref()
+                .map(|h| h.networks.clone())
+                .unwrap_or_default(),
+            public_net: spec
+                .hetzner
+                .as_ref()
+                .filter(|h| !h.enable_public_ipv4)
+                .map(|_| NewServerPublicNet {
+                    enable_ipv4: false,
+                    enable_ipv6: true,
+                }),
+            firewalls: firewalls
+                .iter()
+                .map(|firewall| NewServerFirewall {
+                    firewall: firewall.id,
+                })
+                .collect(),
+            placement_group,
         };
 
-        let server = match self.client.create_server(&server).await {
+        let client = &self.client;
+        let created_server = create_with_fallbacks(
+            server_options,
+            &mut self.unavailable_server_options,
+            |(server_type, location)| {
+                let new_server = new_server.clone();
+
+                async move {
+                    client
+                        .create_server(&NewServer {
+                            server_type: &server_type,
+                            location: location.as_deref(),
+                            ..new_server
+                        })
+                        .await
+                }
+            },
+        )
+        .await;
+
+        let created_server = match created_server {
             Ok(v) => v,
             Err(e) => {
                 error!("Failed to create server: {:?}", e);
@@ -127,15 +403,105 @@ where
             }
         };
 
-        Produces::ok(
-            match create_cloud_node_info(server, &self.config.group_label_name) {
+        let server = match created_server {
+            Some(v) => {
+                self.pending_actions.insert(v.server.id, v.action_ids());
+                v.server
+            }
+            None => {
+                error!("Failed to create server, all server types are unavailable");
+                return Err(anyhow!("All server types are unavailable").into());
+            }
+        };
+
+        // the cached firewalls don't know about the new server yet
+        self.firewalls.clear();
+
+        Produces::ok(match create_cloud_node_info(server, &self.config) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to create cloud node info: {:?}", e);
+                return Err(e.into());
+            }
+        })
+    }
+
+    #[tracing::instrument(
+        name = "HetznerCloudProvider::get_provisioning_status",
+        skip(self, node_info),
+        fields(hostname = %node_info.hostname)
+    )]
+    async fn get_provisioning_status(
+        &mut self,
+        node_info: CloudNodeInfo,
+    ) -> ActorResult<ProvisioningStatus> {
+        let server_id: u64 = node_info
+            .identifier
+            .parse()
+            .map_err(anyhow::Error::new)
+            .map_err(actor::Error::from)?;
+
+        let action_ids = self
+            .pending_actions
+            .get(&server_id)
+            .cloned()
+            .unwrap_or_default();
+
+        let mut actions = Vec::with_capacity(action_ids.len());
+        for action_id in action_ids {
+            let action = match self.client.get_action(action_id).await {
                 Ok(v) => v,
                 Err(e) => {
-                    error!("Failed to create cloud node info: {:?}", e);
+                    error!("Failed to fetch server action: {:?}", e);
                     return Err(e.into());
                 }
-            },
-        )
+            };
+
+            if action.status == ActionStatus::Running {
+                info!(
+                    action_id = action.id,
+                    progress = action.progress,
+                    "Waiting for server action {}",
+                    action.command
+                );
+            }
+
+            actions.push(action);
+        }
+
+        match actions_provisioning_status(&actions) {
+            Some(ProvisioningStatus::Pending) => {
+                let running_action_ids = actions
+                    .iter()
+                    .filter(|a| a.status == ActionStatus::Running)
+                    .map(|a| a.id)
+                    .collect();
+                self.pending_actions.insert(server_id, running_action_ids);
+
+                return Produces::ok(ProvisioningStatus::Pending);
+            }
+            Some(status) => {
+                self.pending_actions.remove(&server_id);
+
+                return Produces::ok(status);
+            }
+            None => {}
+        }
+
+        self.pending_actions.remove(&server_id);
+
+        let server = match self.client.get_server(server_id).await {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to fetch server: {:?}", e);
+                return Err(e.into());
+            }
+        };
+
+        Produces::ok(match server.status {
+            ServerStatus::Running => ProvisioningStatus::Completed,
+            _ => ProvisioningStatus::Pending,
+        })
     }
 
     #[tracing::instrument(name = "HetznerCloudProvider::delete_node", skip(self))]
@@ -148,6 +514,8 @@ where
             }
         };
 
+        self.pending_actions.remove(&server_id);
+
         match self.client.delete_server(server_id).await {
             Ok(_) => Produces::ok(()),
             Err(e) => match e {
@@ -162,6 +530,134 @@ where
         }
     }
 
+    #[tracing::instrument(name = "HetznerCloudProvider::quarantine_node", skip(self))]
+    async fn quarantine_node(
+        &mut self,
+        node_info: CloudNodeInfo,
+        reason: QuarantineReason,
+        since: DateTime<Utc>,
+    ) -> ActorResult<()> {
+        let server_id: u64 = match node_info.identifier.parse() {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to parse node identifier: {:?}", e);
+                return Err(e.into());
+            }
+        };
+
+        let server = match self.client.search_server(&node_info.hostname).await {
+            Ok(Some(v)) => v,
+            Ok(None) => {
+                return Err(anyhow!("Failed to find server {}", node_info.hostname).into());
+            }
+            Err(e) => {
+                error!("Failed to fetch server: {:?}", e);
+                return Err(e.into());
+            }
+        };
+
+        let mut labels = server.labels;
+        labels.insert(
+            self.config.quarantine_label_name.clone(),
+            reason.to_string(),
+        );
+        labels.insert(
+            quarantined_since_label_name(&self.config),
+            since.timestamp().to_string(),
+        );
+
+        let update = UpdateServer {
+            name: None,
+            labels: Some(&labels),
+        };
+
+        match self.client.update_server(server_id, &update).await {
+            Ok(_) => Produces::ok(()),
+            Err(e) => {
+                error!("Failed to label server as quarantined: {:?}", e);
+                Err(e.into())
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "HetznerCloudProvider::recover_node", skip(self))]
+    async fn recover_node(
+        &mut self,
+        node_info: CloudNodeInfo,
+        action: NodeRecoveryAction,
+    ) -> ActorResult<()> {
+        let server_id: u64 = match node_info.identifier.parse() {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to parse node identifier: {:?}", e);
+                return Err(e.into());
+            }
+        };
+
+        let result = match action {
+            NodeRecoveryAction::Reset => self.client.reset_server(server_id).await,
+            NodeRecoveryAction::Rebuild => {
+                // rebuild from the same image, the configured one is only used as a fallback
+                let image = match node_info.image.clone() {
+                    Some(v) => v,
+                    None => match self.resolve_image().await {
+                        Ok(v) => v,
+                        Err(e) => {
+                            error!("Failed to resolve image: {:?}", e);
+                            return Err(e.into());
+                        }
+                    },
+                };
+
+                let rebuild = RebuildServer { image: &image };
+
+                self.client.rebuild_server(server_id, &rebuild).await
+            }
+        };
+
+        match result {
+            Ok(action) => {
+                info!(
+                    action_id = action.id,
+                    "Started server action {}", action.command
+                );
+                Produces::ok(())
+            }
+            Err(e) => {
+                error!("Failed to run server action: {:?}", e);
+                Err(e.into())
+            }
+        }
+    }
+
+    #[tracing::instrument(
+        name = "HetznerCloudProvider::create_ptr_records",
+        skip(self, node_info),
+        fields(hostname = %node_info.hostname)
+    )]
+    async fn create_ptr_records(&mut self, node_info: CloudNodeInfo) -> ActorResult<()> {
+        let server_id: u64 = node_info
+            .identifier
+            .parse()
+            .map_err(anyhow::Error::new)
+            .map_err(actor::Error::from)?;
+
+        // the same host addresses the node's dns records point to
+        for ip in node_info.ip_addresses {
+            let change = ChangeDnsPtr {
+                ip,
+                dns_ptr: Some(&node_info.hostname),
+            };
+
+            if let Err(e) = self.client.change_dns_ptr(server_id, &change).await {
+                error!(%ip, "Failed to change dns pointer: {:?}", e);
+                return Err(e.into());
+            }
+        }
+
+        Produces::ok(())
+    }
+
     #[tracing::instrument(name = "HetznerCloudProvider::get_nodes", skip(self))]
     async fn get_nodes(&mut self) -> ActorResult<Vec<CloudNodeInfo>> {
         let selector = &self.config.group_label_name;
@@ -170,45 +666,276 @@ where
             Err(e) => {
                 error!("Failed to fetch nodes: {:?}", e);
 
-                return Produces::ok(vec![]);
+                return Err(e.into());
             }
         };
 
         let nodes = servers
             .into_iter()
-            .filter_map(
-                |s| match create_cloud_node_info(s, &self.config.group_label_name) {
-                    Ok(v) => Some(v),
-                    Err(e) => {
-                        warn!("Failed to create cloud node info: {:?}", e);
-                        None
-                    }
-                },
-            )
+            .filter_map(|s| match create_cloud_node_info(s, &self.config) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    warn!("Failed to create cloud node info: {:?}", e);
+                    None
+                }
+            })
             .collect();
 
         Produces::ok(nodes)
     }
+
+    #[tracing::instrument(
+        name = "HetznerCloudProvider::reconcile_node",
+        skip(self, node_info, spec),
+        fields(hostname = %node_info.hostname)
+    )]
+    async fn reconcile_node(
+        &mut self,
+        node_info: CloudNodeInfo,
+        spec: NodeSpec,
+    ) -> ActorResult<()> {
+        if let HetznerImage::LabelSelector { .. } = self.config.image {
+            match self.resolve_image().await {
+                Ok(image) if node_info.image.as_ref() != Some(&image) => {
+                    debug!(
+                        image_id = node_info.image.as_deref().unwrap_or("unknown"),
+                        latest_image_id = image.as_str(),
+                        "Node runs an outdated image"
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => error!("Failed to resolve image: {:?}", e),
+            }
+        }
+
+        let firewall_references = match spec.hetzner {
+            Some(hetzner) if !hetzner.firewalls.is_empty() => hetzner.firewalls,
+            _ => return Produces::ok(()),
+        };
+
+        let server_id: u64 = node_info
+            .identifier
+            .parse()
+            .map_err(anyhow::Error::new)
+            .map_err(actor::Error::from)?;
+
+        let firewalls = self
+            .resolve_firewalls(&firewall_references)
+            .await
+            .map_err(actor::Error::from)?;
+
+        let missing_firewalls = firewalls
+            .into_iter()
+            .filter(|firewall| !firewall.is_applied_to_server(server_id))
+            .collect::<Vec<Firewall>>();
+
+        if missing_firewalls.is_empty() {
+            return Produces::ok(());
+        }
+
+        self.firewalls.clear();
+
+        for firewall in missing_firewalls {
+            warn!(
+                firewall = firewall.name.as_str(),
+                "Re-applying missing firewall"
+            );
+
+            let apply = ApplyFirewall {
+                apply_to: vec![FirewallResource::server(server_id)],
+            };
+
+            if let Err(e) = self.client.apply_firewall(firewall.id, &apply).await {
+                error!(
+                    firewall = firewall.name.as_str(),
+                    error = format!("{:?}", e).as_str(),
+                    "Failed to apply firewall"
+                );
+            }
+        }
+
+        Produces::ok(())
+    }
+}
+
+/// The most recently created of the available snapshots
+fn newest_snapshot(images: Vec<Image>) -> Option<Image> {
+    images
+        .into_iter()
+        .filter(|image| {
+            image.image_type == ImageType::Snapshot && image.status == ImageStatus::Available
+        })
+        .max_by_key(|image| image.created)
+}
+
+/// Keeps booting the last resolved image while looking for a newer one fails
+fn last_resolved_image(resolved_image: Option<&(String, Instant)>, error: Error) -> Result<String> {
+    match resolved_image {
+        Some((image, _)) => {
+            warn!(
+                error = format!("{:?}", error).as_str(),
+                "Failed to refresh image, keeping the last resolved one"
+            );
+
+            Ok(image.clone())
+        }
+        None => Err(error.into()),
+    }
+}
+
+/// The oldest spread placement group that didn't reach the size limit yet
+fn available_placement_group(placement_groups: &[PlacementGroup]) -> Option<u64> {
+    placement_groups
+        .iter()
+        .filter(|placement_group| {
+            placement_group.placement_type == PlacementType::Spread
+                && placement_group.servers.len() < SPREAD_PLACEMENT_GROUP_SIZE_LIMIT
+        })
+        .map(|placement_group| placement_group.id)
+        .min()
+}
+
+/// Name of the first partition of the node group that doesn't exist yet
+fn next_placement_group_name(group: &str, placement_groups: &[PlacementGroup]) -> String {
+    (0..)
+        .map(|partition| format!("{}-spread-{}", group, partition))
+        .find(|name| placement_groups.iter().all(|pg| &pg.name != name))
+        .unwrap()
+}
+
+/// Firewalls a reference resolved to, unless they were resolved too long ago
+fn cached_firewalls<'a>(
+    cache: &'a HashMap<HetznerFirewall, (Vec<Firewall>, Instant)>,
+    reference: &HetznerFirewall,
+    now: Instant,
+) -> Option<&'a [Firewall]> {
+    cache
+        .get(reference)
+        .filter(|(_, resolved_at)| now.duration_since(*resolved_at) < FIREWALL_CACHE_TTL)
+        .map(|(firewalls, _)| firewalls.as_slice())
+}
+
+/// Provisioning status as far as the actions started by the server creation tell; `None` once all
+/// of them succeeded and only the server status is left to check
+fn actions_provisioning_status(actions: &[Action]) -> Option<ProvisioningStatus> {
+    if let Some(action) = actions.iter().find(|a| a.status == ActionStatus::Error) {
+        let message = action
+            .error
+            .as_ref()
+            .map(|e| format!("{}: {}", e.code, e.message))
+            .unwrap_or_default();
+
+        return Some(ProvisioningStatus::Failed(format!(
+            "Server action {} failed: {}",
+            action.command, message
+        )));
+    }
+
+    if actions.iter().any(|a| a.status == ActionStatus::Running) {
+        return Some(ProvisioningStatus::Pending);
+    }
+
+    None
+}
+
+/// Tries the server options in order, skipping the ones that recently ran out of capacity;
+/// options that fail with a capacity error get blacklisted and the next one is tried
+async fn create_with_fallbacks<T, F, Fut>(
+    server_options: Vec<ServerOption>,
+    unavailable_server_options: &mut HashMap<ServerOption, Instant>,
+    mut create: F,
+) -> hetzner_cloud::Result<Option<T>>
+where
+    F: FnMut(ServerOption) -> Fut,
+    Fut: Future<Output = hetzner_cloud::Result<T>>,
+{
+    for server_option in server_options {
+        if unavailable_server_options.contains_key(&server_option) {
+            continue;
+        }
+
+        match create(server_option.clone()).await {
+            Ok(v) => return Ok(Some(v)),
+            Err(e) => match e.code() {
+                Some(code) if FALLBACK_ERROR_CODES.contains(&code.as_str()) => {
+                    let (server_type, location) = &server_option;
+                    warn!(
+                        %server_type,
+                        location = ?location,
+                        code = code.as_str(),
+                        "Server type is unavailable, trying the next fallback"
+                    );
+
+                    unavailable_server_options.insert(server_option, Instant::now());
+                }
+                _ => return Err(e),
+            },
+        }
+    }
+
+    Ok(None)
 }
 
-fn create_cloud_node_info(server: Server, group_label_name: &str) -> Result<CloudNodeInfo> {
-    let group = match server.labels.get(group_label_name) {
+fn create_cloud_node_info(server: Server, config: &Config) -> Result<CloudNodeInfo> {
+    let group = match server.labels.get(&config.group_label_name) {
         Some(v) => v.clone(),
-        None => return Err(anyhow!("Missing node group label `{}`", group_label_name)),
+        None => {
+            return Err(anyhow!(
+                "Missing node group label `{}`",
+                config.group_label_name
+            ))
+        }
     };
 
+    let quarantine = server
+        .labels
+        .get(&config.quarantine_label_name)
+        .map(|v| {
+            parse_quarantine_status(v, server.labels.get(&quarantined_since_label_name(config)))
+        })
+        .transpose()?;
+
     let ip_addresses = server.get_ip_addresses();
+    let private_ip_addresses = server.get_private_ip_addresses();
+    let location = Some(server.datacenter.location.name);
+    let server_type = Some(server.server_type.name);
+    let image = server.image.map(|image| image.id.to_string());
     let cni = CloudNodeInfo {
         identifier: server.id.to_string(),
         hostname: server.name,
         created_at: server.created,
         group,
         ip_addresses,
+        private_ip_addresses,
+        location,
+        server_type,
+        image,
+        quarantine,
     };
 
     Ok(cni)
 }
 
+/// Label with the unix timestamp the quarantine started at, next to the reason label
+fn quarantined_since_label_name(config: &Config) -> String {
+    format!("{}-since", config.quarantine_label_name)
+}
+
+fn parse_quarantine_status(
+    label_value: &str,
+    since_label_value: Option<&String>,
+) -> Result<QuarantineStatus> {
+    match label_value {
+        "released" => Ok(QuarantineStatus::Released),
+        v => Ok(QuarantineStatus::Quarantined {
+            reason: v.parse().map_err(|e: String| anyhow!(e))?,
+            since: since_label_value
+                .and_then(|since| since.parse().ok())
+                .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()),
+        }),
+    }
+}
+
 fn gen_user_data<UDG: GenerateUserData>(
     hostname: &str,
     group: &str,
@@ -222,3 +949,255 @@ fn gen_user_data<UDG: GenerateUserData>(
 
     Ok(String::from_utf8(user_data)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hetzner_cloud::actions::ActionError;
+    use futures::future;
+    use http::HeaderMap;
+
+    fn api_error(code: &str) -> Error {
+        Error::BadResponse {
+            status: StatusCode::PRECONDITION_FAILED,
+            headers: HeaderMap::new(),
+            body: format!(r#"{{"error": {{"code": "{}", "message": ""}}}}"#, code),
+        }
+    }
+
+    fn action(id: u64, status: ActionStatus) -> Action {
+        Action {
+            id,
+            command: "create_server".to_owned(),
+            status,
+            progress: 0,
+            error: None,
+            started: None,
+            finished: None,
+        }
+    }
+
+    fn server_option(server_type: &str, location: &str) -> ServerOption {
+        (server_type.to_owned(), Some(location.to_owned()))
+    }
+
+    #[tokio::test]
+    async fn test_create_with_fallbacks_blacklists_unavailable_options() {
+        let options = vec![
+            server_option("cx21", "fsn1"),
+            server_option("cx31", "fsn1"),
+            server_option("cx21", "nbg1"),
+        ];
+        let mut unavailable = HashMap::new();
+        let mut attempts = vec![];
+
+        let created = create_with_fallbacks(options, &mut unavailable, |option| {
+            attempts.push(option.clone());
+
+            future::ready(match option.0.as_str() {
+                "cx21" if option.1.as_deref() == Some("fsn1") => {
+                    Err(api_error("resource_unavailable"))
+                }
+                "cx31" => Err(api_error("placement_error")),
+                _ => Ok(option),
+            })
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(Some(server_option("cx21", "nbg1")), created);
+        assert_eq!(3, attempts.len());
+        assert!(unavailable.contains_key(&server_option("cx21", "fsn1")));
+        assert!(unavailable.contains_key(&server_option("cx31", "fsn1")));
+        assert!(!unavailable.contains_key(&server_option("cx21", "nbg1")));
+    }
+
+    #[tokio::test]
+    async fn test_create_with_fallbacks_skips_blacklisted_options() {
+        let options = vec![server_option("cx21", "fsn1"), server_option("cx31", "fsn1")];
+        let mut unavailable = HashMap::new();
+        unavailable.insert(server_option("cx21", "fsn1"), Instant::now());
+
+        let created = create_with_fallbacks(options.clone(), &mut unavailable, |option| {
+            assert_ne!(server_option("cx21", "fsn1"), option);
+            future::ready(Ok(option))
+        })
+        .await
+        .unwrap();
+        assert_eq!(Some(server_option("cx31", "fsn1")), created);
+
+        unavailable.insert(server_option("cx31", "fsn1"), Instant::now());
+        let created = create_with_fallbacks(options, &mut unavailable, |option| {
+            future::ready(Ok(option))
+        })
+        .await
+        .unwrap();
+        assert_eq!(None, created);
+    }
+
+    #[tokio::test]
+    async fn test_create_with_fallbacks_stops_on_other_errors() {
+        let options = vec![server_option("cx21", "fsn1"), server_option("cx31", "fsn1")];
+        let mut unavailable = HashMap::new();
+        let mut attempts = 0;
+
+        let result = create_with_fallbacks(options, &mut unavailable, |_| {
+            attempts += 1;
+            future::ready(Err::<ServerOption, _>(api_error("invalid_input")))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(1, attempts);
+        assert!(unavailable.is_empty());
+    }
+
+    #[test]
+    fn test_actions_provisioning_status() {
+        assert_eq!(None, actions_provisioning_status(&[]));
+        assert_eq!(
+            None,
+            actions_provisioning_status(&[action(1, ActionStatus::Success)])
+        );
+        assert_eq!(
+            Some(ProvisioningStatus::Pending),
+            actions_provisioning_status(&[
+                action(1, ActionStatus::Success),
+                action(2, ActionStatus::Running)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_failed_action_fails_the_provisioning() {
+        let mut failed = action(2, ActionStatus::Error);
+        failed.error = Some(ActionError {
+            code: "action_failed".to_owned(),
+            message: "boot failed".to_owned(),
+        });
+
+        assert_eq!(
+            Some(ProvisioningStatus::Failed(
+                "Server action create_server failed: action_failed: boot failed".to_owned()
+            )),
+            actions_provisioning_status(&[action(1, ActionStatus::Running), failed])
+        );
+    }
+
+    #[test]
+    fn test_cached_firewalls_expire() {
+        let reference = HetznerFirewall::LabelSelector("role=edge".to_owned());
+        let firewall = Firewall {
+            id: 7,
+            name: "edge".to_owned(),
+            labels: HashMap::new(),
+            applied_to: vec![],
+        };
+        let resolved_at = Instant::now();
+
+        let mut cache = HashMap::new();
+        cache.insert(reference.clone(), (vec![firewall], resolved_at));
+
+        let cached = cached_firewalls(&cache, &reference, resolved_at).unwrap();
+        assert_eq!(vec![7], cached.iter().map(|f| f.id).collect::<Vec<u64>>());
+
+        assert!(cached_firewalls(&cache, &reference, resolved_at + FIREWALL_CACHE_TTL).is_none());
+        assert!(cached_firewalls(
+            &cache,
+            &HetznerFirewall::Name("edge".to_owned()),
+            resolved_at
+        )
+        .is_none());
+    }
+
+    fn placement_group(id: u64, name: &str, servers: usize) -> PlacementGroup {
+        PlacementGroup {
+            id,
+            name: name.to_owned(),
+            labels: HashMap::new(),
+            placement_type: PlacementType::Spread,
+            servers: (0..servers as u64).collect(),
+        }
+    }
+
+    #[test]
+    fn test_available_placement_group_is_the_oldest_with_room() {
+        let placement_groups = vec![
+            placement_group(12, "edge-spread-2", 3),
+            placement_group(10, "edge-spread-0", SPREAD_PLACEMENT_GROUP_SIZE_LIMIT),
+            placement_group(11, "edge-spread-1", 9),
+        ];
+
+        assert_eq!(Some(11), available_placement_group(&placement_groups));
+        assert_eq!(None, available_placement_group(&placement_groups[1..2]));
+        assert_eq!(None, available_placement_group(&[]));
+    }
+
+    #[test]
+    fn test_next_placement_group_name_fills_gaps() {
+        assert_eq!("edge-spread-0", next_placement_group_name("edge", &[]));
+
+        let placement_groups = vec![
+            placement_group(10, "edge-spread-0", SPREAD_PLACEMENT_GROUP_SIZE_LIMIT),
+            placement_group(12, "edge-spread-2", SPREAD_PLACEMENT_GROUP_SIZE_LIMIT),
+        ];
+        assert_eq!(
+            "edge-spread-1",
+            next_placement_group_name("edge", &placement_groups)
+        );
+
+        let placement_groups = vec![
+            placement_group(10, "edge-spread-0", SPREAD_PLACEMENT_GROUP_SIZE_LIMIT),
+            placement_group(11, "edge-spread-1", SPREAD_PLACEMENT_GROUP_SIZE_LIMIT),
+        ];
+        assert_eq!(
+            "edge-spread-2",
+            next_placement_group_name("edge", &placement_groups)
+        );
+    }
+
+    fn snapshot(id: u64, created_minutes_ago: i64, status: ImageStatus) -> Image {
+        Image {
+            id,
+            image_type: ImageType::Snapshot,
+            status,
+            name: None,
+            description: format!("edge-{}", id),
+            created: Utc::now() - chrono::Duration::minutes(created_minutes_ago),
+            labels: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_newest_snapshot_is_selected() {
+        let images = vec![
+            snapshot(1, 60, ImageStatus::Available),
+            snapshot(3, 10, ImageStatus::Available),
+            snapshot(2, 30, ImageStatus::Available),
+        ];
+
+        assert_eq!(Some(3), newest_snapshot(images).map(|image| image.id));
+    }
+
+    #[test]
+    fn test_unavailable_snapshots_are_skipped() {
+        let images = vec![
+            snapshot(1, 60, ImageStatus::Available),
+            snapshot(2, 1, ImageStatus::Creating),
+        ];
+
+        assert_eq!(Some(1), newest_snapshot(images).map(|image| image.id));
+        assert!(newest_snapshot(vec![]).is_none());
+    }
+
+    #[test]
+    fn test_last_resolved_image_is_kept_on_error() {
+        let resolved_image = ("42".to_owned(), Instant::now());
+
+        assert_eq!(
+            "42",
+            last_resolved_image(Some(&resolved_image), api_error("server_error")).unwrap()
+        );
+        assert!(last_resolved_image(None, api_error("server_error")).is_err());
+    }
+}
@@ -1,8 +1,11 @@
 use crate::actor;
-use crate::cloud_provider::{CloudNodeInfo, CloudProvider};
+use crate::cloud_provider::{CloudNodeInfo, CloudProvider, NodeSpec};
+use crate::config::NodeRecoveryAction;
 use crate::node::discovery::NodeDiscoveryState;
+use crate::node::QuarantineReason;
 use act_zero::{Actor, ActorError, ActorResult, Addr, Produces};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use tracing::info;
 
 struct MockCloudProvider;
@@ -29,11 +32,16 @@ impl CloudProvider for MockCloudProvider {
         Produces::ok(None)
     }
 
+    async fn node_exists(&mut self, _node_info: CloudNodeInfo) -> ActorResult<bool> {
+        Produces::ok(false)
+    }
+
     async fn create_node(
         &mut self,
         _hostname: String,
         _group: String,
         _target_state: NodeDiscoveryState,
+        _spec: NodeSpec,
     ) -> ActorResult<CloudNodeInfo> {
         unimplemented!()
     }
@@ -42,6 +50,23 @@ impl CloudProvider for MockCloudProvider {
         Produces::ok(())
     }
 
+    async fn quarantine_node(
+        &mut self,
+        _node_info: CloudNodeInfo,
+        _reason: QuarantineReason,
+        _since: DateTime<Utc>,
+    ) -> ActorResult<()> {
+        Produces::ok(())
+    }
+
+    async fn recover_node(
+        &mut self,
+        _node_info: CloudNodeInfo,
+        _action: NodeRecoveryAction,
+    ) -> ActorResult<()> {
+        Produces::ok(())
+    }
+
     async fn get_nodes(&mut self) -> ActorResult<Vec<CloudNodeInfo>> {
         Produces::ok(vec![])
     }
@@ -1,6 +1,6 @@
 use crate::AppConfig;
 use anyhow::Context;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::BufReader;
 use std::sync::Arc;
@@ -13,7 +13,7 @@ pub struct Config {
     pub node_discovery: NodeDiscovery,
     pub node_exploration: NodeExploration,
     pub node_discovery_provider: NodeDiscoveryProvider,
-    pub node_group_discovery_providers: Vec<NodeGroupDiscoveryProvider>,
+    pub node_group_discovery_providers: Vec<NodeGroupDiscoverySource>,
     pub cloud_provider: CloudProvider,
     pub dns_provider: DnsProvider,
     pub cloud_init: CloudInit,
@@ -21,6 +21,7 @@ pub struct Config {
     #[serde(with = "humantime_serde")]
     pub node_group_discovery_timeout: Duration,
     pub node_controller: NodeController,
+    pub node_reconciliation: Option<NodeReconciliation>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -30,10 +31,14 @@ pub enum NodeStats {
         #[serde(with = "humantime_serde")]
         interval: Duration,
         path: String,
+        watch: Option<FileWatch>,
     },
     NSS {
         tls: NodeStatsNSSTLS,
         port: u16,
+        /// Connects to the first private ip address of the node instead of its hostname
+        #[serde(default)]
+        use_private_address: bool,
     },
 }
 
@@ -51,11 +56,131 @@ pub enum NodeDiscoveryProvider {
     Mock,
     File {
         path: String,
+        watch: Option<FileWatch>,
     },
     Consul {
         service_name: String,
-        address: String,
+        #[serde(flatten)]
+        connection: ConsulConnection,
+        watch: Option<ConsulWatch>,
+        /// Lets the autoscaler register provisioned nodes in the catalog instead of the nodes
+        /// registering themselves
+        registration: Option<ConsulRegistration>,
+    },
+    /// Nodes are the targets of a SRV record, with group and state kept in TXT records of the
+    /// targets; state updates and the registration of provisioned nodes are written through the
+    /// dns provider, which has to support srv records
+    Dns {
+        srv_name: String,
+        /// Port of the srv records that provisioned nodes get registered with
+        srv_port: u16,
+        /// Time until written states are visible to resolvers, until then the written state
+        /// takes precedence over the resolved one
+        #[serde(default = "default_dns_propagation_delay", with = "humantime_serde")]
+        propagation_delay: Duration,
     },
+    /// Writable node list persisted to a json state file, seeded with the given nodes as long
+    /// as the state file doesn't exist
+    Static {
+        state_path: String,
+        #[serde(default)]
+        nodes: Vec<StaticNode>,
+    },
+}
+
+/// Seed node of the static node discovery
+#[derive(Clone, Deserialize, Debug)]
+pub struct StaticNode {
+    pub hostname: String,
+    pub group: String,
+    /// Discovery state like `ready`, `active` or `draining-scaling`
+    pub state: String,
+}
+
+fn default_dns_propagation_delay() -> Duration {
+    Duration::from_secs(120)
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct ConsulConnection {
+    pub address: String,
+    pub token: Option<ConsulToken>,
+    pub tls: Option<ConsulTls>,
+    /// Datacenters to query, the datacenter of the agent is used if empty
+    #[serde(default)]
+    pub datacenters: Vec<String>,
+}
+
+/// Consul ACL token, given inline or loaded from a file or an environment variable on startup
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsulToken {
+    Value(String),
+    File(String),
+    Env(String),
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct ConsulTls {
+    pub ca_cert_path: String,
+}
+
+/// Catalog registration of provisioned nodes as external nodes; the health checks are only
+/// executed by an external checker like consul-esm, until then the nodes are not discovered
+#[derive(Clone, Deserialize, Debug)]
+pub struct ConsulRegistration {
+    pub port: u16,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub checks: Vec<ConsulCheck>,
+    /// Registers the first private ip address of the node as service address, so the checks
+    /// run against the private network
+    #[serde(default)]
+    pub use_private_address: bool,
+}
+
+/// Http check if `http` is set, tcp check on the service address and port otherwise
+#[derive(Clone, Deserialize, Debug)]
+pub struct ConsulCheck {
+    pub name: String,
+    pub http: Option<String>,
+    #[serde(default = "default_consul_check_interval", with = "humantime_serde")]
+    pub interval: Duration,
+    #[serde(default = "default_consul_check_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+fn default_consul_check_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_consul_check_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// Consul blocking query settings; with a watch the regular discovery interval only serves as
+/// periodic full resync
+#[derive(Clone, Deserialize, Debug)]
+pub struct ConsulWatch {
+    #[serde(default = "default_consul_wait_time", with = "humantime_serde")]
+    pub wait_time: Duration,
+}
+
+fn default_consul_wait_time() -> Duration {
+    Duration::from_secs(300)
+}
+
+/// Filesystem watch of file based providers; without a watch or if it can't be set up the
+/// providers fall back to their regular polling interval
+#[derive(Clone, Deserialize, Debug)]
+pub struct FileWatch {
+    #[serde(default = "default_file_watch_debounce", with = "humantime_serde")]
+    pub debounce: Duration,
+}
+
+fn default_file_watch_debounce() -> Duration {
+    Duration::from_millis(200)
 }
 
 #[derive(Deserialize, Debug)]
@@ -76,24 +201,114 @@ pub struct NodeExploration {
     pub interval: Duration,
 }
 
+#[derive(Clone, Deserialize, Debug)]
+pub struct NodeReconciliation {
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    #[serde(with = "humantime_serde")]
+    pub grace_period: Duration,
+    /// Orphans are only reported unless deletion is enabled explicitly
+    #[serde(default = "default_reconciliation_dry_run")]
+    pub dry_run: bool,
+}
+
+fn default_reconciliation_dry_run() -> bool {
+    true
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum CloudProvider {
     File {
         exploration_path: String,
         discovery_path: String,
+        watch: Option<FileWatch>,
     },
     Hetzner {
         server_type: String,
-        image: String,
+        image: HetznerImage,
+        /// How long an image resolved by label selector is used before looking for a newer one
+        #[serde(default = "default_image_refresh_interval", with = "humantime_serde")]
+        image_refresh_interval: Duration,
         ssh_keys: Vec<String>,
         group_label_name: String,
         api_address: String,
         api_token: String,
+        /// Requests per hour the cloud api allows the project
+        #[serde(default = "default_hetzner_api_rate_limit")]
+        api_rate_limit: u64,
         location: Option<String>,
+        quarantine_label_name: Option<String>,
+        /// How long a server type and location that ran out of capacity is skipped
+        #[serde(
+            default = "default_unavailable_blacklist_duration",
+            with = "humantime_serde"
+        )]
+        unavailable_blacklist_duration: Duration,
     },
 }
 
+fn default_hetzner_api_rate_limit() -> u64 {
+    3600
+}
+
+fn default_unavailable_blacklist_duration() -> Duration {
+    Duration::from_secs(600)
+}
+
+fn default_image_refresh_interval() -> Duration {
+    Duration::from_secs(300)
+}
+
+/// Either a fixed image name or id, or a label selector that resolves to the newest matching
+/// snapshot
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum HetznerImage {
+    Name(String),
+    LabelSelector { label_selector: String },
+}
+
+/// Hetzner specific settings of a node group that apply to newly created servers
+#[derive(Clone, Deserialize, Debug)]
+pub struct HetznerNodeGroup {
+    /// Ids of the private networks new servers get attached to
+    #[serde(default)]
+    pub networks: Vec<u64>,
+    /// Servers without a public ipv4 address are only reachable via ipv6 or private networks
+    #[serde(default = "default_enable_public_ipv4")]
+    pub enable_public_ipv4: bool,
+    /// Firewalls that get applied on creation and re-applied if they went missing later on
+    #[serde(default)]
+    pub firewalls: Vec<HetznerFirewall>,
+    /// Spreads the servers across physical hosts with spread placement groups; groups beyond the
+    /// placement group size limit are partitioned into several placement groups
+    #[serde(default)]
+    pub spread_placement: bool,
+    /// Server types and locations that are tried in order if the preferred one is unavailable
+    #[serde(default)]
+    pub fallbacks: Vec<HetznerServerFallback>,
+}
+
+/// Alternative server type; without a location the server stays in the preferred location
+#[derive(Clone, Deserialize, Debug)]
+pub struct HetznerServerFallback {
+    pub server_type: String,
+    pub location: Option<String>,
+}
+
+/// Reference to existing Hetzner firewalls; a label selector may match several of them
+#[derive(Clone, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum HetznerFirewall {
+    Name(String),
+    LabelSelector(String),
+}
+
+fn default_enable_public_ipv4() -> bool {
+    true
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum DnsProvider {
@@ -103,6 +318,9 @@ pub enum DnsProvider {
         record_ttl: u64,
         api_token: String,
         address: String,
+        /// Initial requests per hour until the api responses tell the actual limit
+        #[serde(default = "default_hetzner_api_rate_limit")]
+        api_rate_limit: u64,
     },
     Cloudflare {
         zone_id: String,
@@ -111,11 +329,44 @@ pub enum DnsProvider {
     },
 }
 
+#[derive(Deserialize, Debug)]
+pub struct NodeGroupDiscoverySource {
+    /// Node group definitions of sources with a higher priority overlay the ones of sources with a
+    /// lower priority field by field; equal priorities are applied in configuration order
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(flatten)]
+    pub provider: NodeGroupDiscoveryProvider,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum NodeGroupDiscoveryProvider {
-    File { path: String },
-    Consul { key_prefix: String, address: String },
+    File {
+        path: String,
+        watch: Option<FileWatch>,
+    },
+    /// Every configured datacenter becomes its own discovery source with the same priority
+    Consul {
+        key_prefix: String,
+        #[serde(flatten)]
+        connection: ConsulConnection,
+        watch: Option<ConsulWatch>,
+    },
+    /// Fetches a json or yaml list of node groups
+    Http {
+        url: String,
+        bearer_token: Option<String>,
+        tls: Option<HttpTls>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HttpTls {
+    pub ca_cert_path: Option<String>,
+    /// Client certificate and key are only needed for sources that authenticate their clients
+    pub client_cert_path: Option<String>,
+    pub client_key_path: Option<String>,
 }
 
 #[derive(Clone, Deserialize, Debug)]
@@ -150,6 +401,85 @@ pub struct NodeController {
     pub discovery_timeout: Duration,
     #[serde(with = "humantime_serde")]
     pub exploration_timeout: Duration,
+    /// How long to wait for the cloud provider to confirm a node deletion before retrying it
+    #[serde(default = "default_deletion_timeout", with = "humantime_serde")]
+    pub deletion_timeout: Duration,
+    pub quarantine: Option<NodeQuarantine>,
+    pub lifecycle_hooks: Option<NodeLifecycleHooks>,
+}
+
+fn default_deletion_timeout() -> Duration {
+    Duration::from_secs(300)
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct NodeQuarantine {
+    #[serde(with = "humantime_serde")]
+    pub duration: Duration,
+}
+
+/// Recovery ladder that is climbed step by step before an unhealthy node gets replaced
+#[derive(Clone, Deserialize, Debug)]
+pub struct NodeRecovery {
+    pub steps: Vec<NodeRecoveryStep>,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct NodeRecoveryStep {
+    pub action: NodeRecoveryAction,
+    /// How long to wait for the node to be discovered again after running the action
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+#[derive(Copy, Clone, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeRecoveryAction {
+    Reset,
+    Rebuild,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct NodeLifecycleHooks {
+    /// Upper limit for how long blocking hooks may delay a transition
+    #[serde(with = "humantime_serde")]
+    pub max_delay: Duration,
+    pub hooks: Vec<NodeLifecycleHook>,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct NodeLifecycleHook {
+    pub event: NodeLifecycleEvent,
+    #[serde(default)]
+    pub blocking: bool,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    pub action: NodeLifecycleHookAction,
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeLifecycleEvent {
+    PreActivate,
+    PostReady,
+    PreDrain,
+    PreDelete,
+    PostDeprovision,
+    DeletionFailed,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NodeLifecycleHookAction {
+    Http {
+        url: String,
+        bearer_token: Option<String>,
+    },
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
 }
 
 pub fn load_config() -> anyhow::Result<AppConfig> {
@@ -0,0 +1,279 @@
+use crate::config::{ConsulConnection, ConsulToken};
+use anyhow::{anyhow, Context};
+use http::StatusCode;
+use serde_json::{json, Value};
+use std::{env, fs};
+
+/// Builds a consul client for the given connection, queries go to the given datacenter or the
+/// datacenter of the agent if none is given
+pub fn build_client(
+    connection: &ConsulConnection,
+    datacenter: Option<&str>,
+) -> anyhow::Result<consul_api_client::Client> {
+    let mut builder = consul_api_client::Config::builder();
+    builder.address(connection.address.clone());
+
+    if let Some(token) = connection.token.as_ref() {
+        builder.token(load_token(token)?);
+    }
+
+    if let Some(datacenter) = datacenter {
+        builder.datacenter(datacenter.to_owned());
+    }
+
+    if connection.tls.is_some() {
+        builder.http_client(build_http_client(connection)?);
+    }
+
+    Ok(consul_api_client::Client::new(builder.build()?)?)
+}
+
+/// Builds a transaction client for the given connection, with the same datacenter semantics as
+/// [`build_client`]
+pub fn build_txn_client(
+    connection: &ConsulConnection,
+    datacenter: Option<&str>,
+) -> anyhow::Result<TxnClient> {
+    Ok(TxnClient {
+        address: connection.address.trim_end_matches('/').to_owned(),
+        token: connection.token.as_ref().map(load_token).transpose()?,
+        datacenter: datacenter.map(ToOwned::to_owned),
+        http_client: build_http_client(connection)?,
+    })
+}
+
+fn build_http_client(connection: &ConsulConnection) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::ClientBuilder::new();
+
+    if let Some(tls) = connection.tls.as_ref() {
+        let ca_cert = fs::read(&tls.ca_cert_path)
+            .with_context(|| format!("Failed to read consul ca cert {}", tls.ca_cert_path))?;
+
+        builder = builder
+            .use_rustls_tls()
+            .add_root_certificate(reqwest::Certificate::from_pem(&ca_cert)?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Consul indexes of blocking queries have to be greater than zero and get reset if they go
+/// backwards
+pub fn next_consul_index(current: Option<u64>, last_index: Option<u64>) -> Option<u64> {
+    match (current, last_index) {
+        (_, None) | (_, Some(0)) => None,
+        (Some(current), Some(last_index)) if last_index < current => None,
+        (_, last_index) => last_index,
+    }
+}
+
+/// Catalog transactions, which the consul api client doesn't cover; they allow check-and-set
+/// updates of service registrations
+#[derive(Clone, Debug)]
+pub struct TxnClient {
+    address: String,
+    token: Option<String>,
+    datacenter: Option<String>,
+    http_client: reqwest::Client,
+}
+
+impl TxnClient {
+    /// Reads the catalog service of a node including its modify index
+    pub async fn get_service(&self, node: &str, service_id: &str) -> anyhow::Result<Option<Value>> {
+        let operations = json!([{
+            "Service": {
+                "Verb": "get",
+                "Node": node,
+                "Service": { "ID": service_id },
+            }
+        }]);
+
+        // the transaction gets rolled back if the service doesn't exist
+        Ok(self
+            .run(&operations)
+            .await?
+            .and_then(|mut response| response.pointer_mut("/Results/0/Service").map(Value::take)))
+    }
+
+    /// Writes the catalog service of a node, unless it was modified since the `ModifyIndex` of the
+    /// given service; returns whether the service was written
+    pub async fn cas_service(&self, node: &str, service: &Value) -> anyhow::Result<bool> {
+        let operations = json!([{
+            "Service": {
+                "Verb": "cas",
+                "Node": node,
+                "Service": service,
+            }
+        }]);
+
+        Ok(self.run(&operations).await?.is_some())
+    }
+
+    /// Returns `None` if the transaction was rolled back
+    async fn run(&self, operations: &Value) -> anyhow::Result<Option<Value>> {
+        let mut request_builder = self
+            .http_client
+            .put(&format!("{}/v1/txn", self.address))
+            .json(operations);
+
+        if let Some(datacenter) = self.datacenter.as_ref() {
+            request_builder = request_builder.query(&[("dc", datacenter)]);
+        }
+
+        if let Some(token) = self.token.as_ref() {
+            request_builder = request_builder.header("X-Consul-Token", token);
+        }
+
+        let response = request_builder.send().await?;
+
+        match response.status() {
+            StatusCode::CONFLICT => Ok(None),
+            status if status.is_success() => Ok(Some(response.json().await?)),
+            status => Err(anyhow!(
+                "Consul transaction failed with {}: {}",
+                status,
+                response.text().await.unwrap_or_default()
+            )),
+        }
+    }
+}
+
+fn load_token(token: &ConsulToken) -> anyhow::Result<String> {
+    Ok(match token {
+        ConsulToken::Value(v) => v.clone(),
+        ConsulToken::File(path) => fs::read_to_string(path)
+            .with_context(|| format!("Failed to read consul token file {}", path))?
+            .trim()
+            .to_owned(),
+        ConsulToken::Env(name) => env::var(name)
+            .with_context(|| format!("Failed to read consul token from env var {}", name))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_next_consul_index() {
+        assert_eq!(Some(5), next_consul_index(None, Some(5)));
+        assert_eq!(Some(7), next_consul_index(Some(5), Some(7)));
+        assert_eq!(Some(5), next_consul_index(Some(5), Some(5)));
+    }
+
+    #[test]
+    fn test_next_consul_index_resets() {
+        assert_eq!(None, next_consul_index(Some(5), Some(3)));
+        assert_eq!(None, next_consul_index(Some(5), Some(0)));
+        assert_eq!(None, next_consul_index(Some(5), None));
+    }
+
+    #[test]
+    fn test_load_token() {
+        let path = env::temp_dir().join(format!("consul-token-{}", std::process::id()));
+        fs::write(&path, "file-token\n").unwrap();
+        env::set_var("TEST_LOAD_TOKEN_CONSUL_TOKEN", "env-token");
+
+        let token_file = ConsulToken::File(path.to_string_lossy().into_owned());
+        let token_env = ConsulToken::Env("TEST_LOAD_TOKEN_CONSUL_TOKEN".to_owned());
+        let missing_env = ConsulToken::Env("TEST_LOAD_TOKEN_MISSING".to_owned());
+
+        assert_eq!(
+            "value-token",
+            load_token(&ConsulToken::Value("value-token".to_owned())).unwrap()
+        );
+        assert_eq!("file-token", load_token(&token_file).unwrap());
+        assert_eq!("env-token", load_token(&token_env).unwrap());
+        assert!(load_token(&missing_env).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    /// Answers a single request with the given response and returns the request head in lowercase
+    async fn serve_once(response: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut request = vec![];
+            let mut buf = [0; 1024];
+            let head_len = loop {
+                if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break request.len();
+                }
+                request.extend_from_slice(&buf[..n]);
+            };
+            let head = String::from_utf8_lossy(&request[..head_len]).to_lowercase();
+
+            // the body has to be read, otherwise closing the connection resets it
+            let content_length = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length: "))
+                .map(|length| length.trim().parse::<usize>().unwrap())
+                .unwrap_or(0);
+            while request.len() < head_len + content_length {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+
+            stream.write_all(response.as_bytes()).await.unwrap();
+            head
+        });
+
+        (address, handle)
+    }
+
+    #[tokio::test]
+    async fn test_txn_client_sends_token_and_datacenter() {
+        let (address, server) = serve_once(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\
+             Connection: close\r\n\r\n{}",
+        )
+        .await;
+        let client = TxnClient {
+            address,
+            token: Some("secret".to_owned()),
+            datacenter: Some("dc2".to_owned()),
+            http_client: reqwest::Client::new(),
+        };
+
+        assert!(client
+            .cas_service("edge-1", &json!({"ID": "edge"}))
+            .await
+            .unwrap());
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("put /v1/txn?dc=dc2 "));
+        assert!(request.contains("x-consul-token: secret"));
+    }
+
+    #[tokio::test]
+    async fn test_txn_client_conflict_is_a_rollback() {
+        let (address, _server) =
+            serve_once("HTTP/1.1 409 Conflict\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .await;
+        let client = TxnClient {
+            address,
+            token: None,
+            datacenter: None,
+            http_client: reqwest::Client::new(),
+        };
+
+        assert!(!client
+            .cas_service("edge-1", &json!({"ID": "edge"}))
+            .await
+            .unwrap());
+    }
+}
@@ -15,6 +15,13 @@ use cf::framework::auth::Credentials;
 use cf::framework::Environment;
 use cf::framework::HttpApiClientConfig;
 
+#[derive(Debug, Clone)]
+pub struct DnsRecordInfo {
+    pub hostname: String,
+    pub record_type: String,
+    pub value: String,
+}
+
 #[async_trait]
 pub trait DnsProvider: Actor {
     async fn create_records(
@@ -24,6 +31,18 @@ pub trait DnsProvider: Actor {
     ) -> ActorResult<()>;
 
     async fn delete_records(&mut self, hostname: String) -> ActorResult<()>;
+
+    /// Reloads and returns all address records of the zone
+    async fn get_records(&mut self) -> ActorResult<Vec<DnsRecordInfo>>;
+
+    /// Replaces all txt records of the hostname with the given values
+    async fn set_txt_records(&mut self, hostname: String, values: Vec<String>) -> ActorResult<()>;
+
+    /// Adds the value to the srv records of the name, unless one of them holds it already
+    async fn add_srv_record(&mut self, name: String, value: String) -> ActorResult<()>;
+
+    /// Removes the srv records of the name that hold the value
+    async fn remove_srv_record(&mut self, name: String, value: String) -> ActorResult<()>;
 }
 
 pub fn build_from_config(config: AppConfig) -> anyhow::Result<Addr<dyn DnsProvider>> {
@@ -34,10 +53,12 @@ pub fn build_from_config(config: AppConfig) -> anyhow::Result<Addr<dyn DnsProvid
             api_token,
             zone_apex,
             record_ttl,
+            api_rate_limit,
         } => {
             let hetzner_dns_client = crate::hetzner_dns::Client::builder()
                 .address(address.clone())
                 .api_token(api_token.clone())
+                .rate_limit(*api_rate_limit)
                 .build()?;
 
             upcast!(spawn_actor(hetzner::HetznerDnsProvider::new(
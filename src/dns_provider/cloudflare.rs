@@ -1,5 +1,5 @@
 use act_zero::{Actor, ActorError, ActorResult, Addr, Produces};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use cloudflare::framework::async_api::{ApiClient, Client};
 use tracing::info;
@@ -7,7 +7,7 @@ use tracing::warn;
 
 use crate::actor;
 use crate::dns_provider::record_store::RecordStore;
-use crate::dns_provider::{record_store, record_type, DnsProvider};
+use crate::dns_provider::{record_store, record_type, DnsProvider, DnsRecordInfo};
 use cloudflare::endpoints::dns::{
     CreateDnsRecord, CreateDnsRecordParams, DeleteDnsRecord, DnsContent, DnsRecord, ListDnsRecords,
     ListDnsRecordsParams,
@@ -122,6 +122,82 @@ impl DnsProvider for CloudflareDnsProvider {
 
         Produces::ok(())
     }
+
+    #[tracing::instrument(name = "CloudflareDnsProvider::get_records", skip(self))]
+    async fn get_records(&mut self) -> ActorResult<Vec<DnsRecordInfo>> {
+        self.records.clear();
+        load_records(&self.client, &self.config, &mut self.records).await?;
+
+        let records = self
+            .records
+            .all()
+            .filter_map(|r| {
+                match r.content {
+                    DnsContent::A { content } => Some(("A", content.to_string())),
+                    DnsContent::AAAA { content } => Some(("AAAA", content.to_string())),
+                    _ => None,
+                }
+                .map(|(record_type, value)| DnsRecordInfo {
+                    hostname: r.name.clone(),
+                    record_type: record_type.into(),
+                    value,
+                })
+            })
+            .collect();
+
+        Produces::ok(records)
+    }
+
+    #[tracing::instrument(name = "CloudflareDnsProvider::set_txt_records", skip(self))]
+    async fn set_txt_records(&mut self, hostname: String, values: Vec<String>) -> ActorResult<()> {
+        self.ensure_records_are_loaded().await?;
+
+        delete_records(
+            &self.client,
+            &self.config,
+            &mut self.records,
+            &hostname,
+            "TXT",
+        )
+        .await?;
+
+        for value in values {
+            info!("Create record {} TXT {}", hostname, value);
+
+            let response = self
+                .client
+                .request(&CreateDnsRecord {
+                    zone_identifier: &self.config.zone_id,
+                    params: CreateDnsRecordParams {
+                        ttl: Some(self.config.record_ttl),
+                        priority: None,
+                        proxied: None,
+                        name: &hostname,
+                        content: DnsContent::TXT { content: value },
+                    },
+                })
+                .await?;
+
+            self.records.add(response.result);
+        }
+
+        Produces::ok(())
+    }
+
+    // cloudflare expects srv records as structured data, which the api client doesn't support
+    async fn add_srv_record(&mut self, _name: String, _value: String) -> ActorResult<()> {
+        Err(actor::Error::from(anyhow!(
+            "Srv records are not supported by the cloudflare dns provider"
+        ))
+        .into())
+    }
+
+    async fn remove_srv_record(&mut self, _name: String, _value: String) -> ActorResult<()> {
+        Err(actor::Error::from(anyhow!(
+            "Srv records are not supported by the cloudflare dns provider"
+        ))
+        .into())
+    }
 }
 
 async fn delete_records(
@@ -1,6 +1,6 @@
 use crate::actor;
 use crate::dns_provider::record_store::RecordStore;
-use crate::dns_provider::{record_store, record_type, DnsProvider};
+use crate::dns_provider::{record_store, record_type, DnsProvider, DnsRecordInfo};
 use crate::hetzner_dns::records::{NewRecord, Record, Records};
 use crate::hetzner_dns::zones::{Zone, Zones};
 use crate::hetzner_dns::Client;
@@ -101,6 +101,108 @@ impl DnsProvider for HetznerDnsProvider {
 
         Produces::ok(())
     }
+
+    #[tracing::instrument(name = "HetznerDnsProvider::get_records", skip(self))]
+    async fn get_records(&mut self) -> ActorResult<Vec<DnsRecordInfo>> {
+        self.fetch_zone_if_missing().await?;
+        let zone = self.zone.as_ref().unwrap();
+
+        self.records.clear();
+        load_records(&self.client, zone, &mut self.records).await?;
+
+        let records = self
+            .records
+            .all()
+            .filter(|r| r.record_type == "A" || r.record_type == "AAAA")
+            .map(|r| DnsRecordInfo {
+                hostname: match r.name.as_str() {
+                    "@" => self.config.zone_apex.clone(),
+                    name => format!("{}.{}", name, self.config.zone_apex),
+                },
+                record_type: r.record_type.clone(),
+                value: r.value.clone(),
+            })
+            .collect();
+
+        Produces::ok(records)
+    }
+
+    #[tracing::instrument(name = "HetznerDnsProvider::set_txt_records", skip(self))]
+    async fn set_txt_records(&mut self, hostname: String, values: Vec<String>) -> ActorResult<()> {
+        let record_name = self.gen_record_name(&hostname)?;
+        self.fetch_zone_if_missing().await?;
+        let zone = self.zone.as_ref().unwrap();
+        if self.records.is_empty() {
+            load_records(&self.client, zone, &mut self.records).await?;
+        }
+
+        for record in self.records.get(record_name, "TXT") {
+            info!(record = format!("{:?}", *record).as_str(), "Delete record");
+            self.client.delete_record(&record.id).await?;
+            self.records.remove(record.as_ref());
+        }
+
+        for value in values.iter() {
+            let record = NewRecord {
+                record_type: "TXT",
+                zone_id: &zone.id,
+                name: record_name,
+                value,
+                ttl: Some(self.config.record_ttl),
+            };
+
+            info!(record = format!("{:?}", record).as_str(), "Create record");
+            let record = self.client.create_record(&record).await?;
+            self.records.add(record);
+        }
+
+        Produces::ok(())
+    }
+
+    #[tracing::instrument(name = "HetznerDnsProvider::add_srv_record", skip(self))]
+    async fn add_srv_record(&mut self, name: String, value: String) -> ActorResult<()> {
+        let record_name = self.gen_record_name(&name)?;
+        self.reload_records().await?;
+        let zone = self.zone.as_ref().unwrap();
+
+        let exists = self
+            .records
+            .get(record_name, "SRV")
+            .iter()
+            .any(|record| record.value == value);
+
+        if !exists {
+            let record = NewRecord {
+                record_type: "SRV",
+                zone_id: &zone.id,
+                name: record_name,
+                value: &value,
+                ttl: Some(self.config.record_ttl),
+            };
+
+            info!(record = format!("{:?}", record).as_str(), "Create record");
+            let record = self.client.create_record(&record).await?;
+            self.records.add(record);
+        }
+
+        Produces::ok(())
+    }
+
+    #[tracing::instrument(name = "HetznerDnsProvider::remove_srv_record", skip(self))]
+    async fn remove_srv_record(&mut self, name: String, value: String) -> ActorResult<()> {
+        let record_name = self.gen_record_name(&name)?;
+        self.reload_records().await?;
+
+        for record in self.records.get(record_name, "SRV") {
+            if record.value == value {
+                info!(record = format!("{:?}", *record).as_str(), "Delete record");
+                self.client.delete_record(&record.id).await?;
+                self.records.remove(record.as_ref());
+            }
+        }
+
+        Produces::ok(())
+    }
 }
 
 impl HetznerDnsProvider {
@@ -127,6 +229,15 @@ impl HetznerDnsProvider {
         }
     }
 
+    /// Srv record sets are shared by all nodes, so they are always edited on fresh records
+    async fn reload_records(&mut self) -> Result<()> {
+        self.fetch_zone_if_missing().await?;
+        let zone = self.zone.as_ref().unwrap();
+
+        self.records.clear();
+        load_records(&self.client, zone, &mut self.records).await
+    }
+
     fn gen_record_name<'a>(&self, full: &'a str) -> Result<&'a str> {
         match full.strip_suffix(format!(".{}", &self.config.zone_apex).as_str()) {
             Some(name) => Ok(name),
@@ -1,4 +1,4 @@
-use super::DnsProvider;
+use super::{DnsProvider, DnsRecordInfo};
 use crate::actor;
 use act_zero::{Actor, ActorError, ActorResult, Addr, Produces};
 use async_trait::async_trait;
@@ -36,4 +36,24 @@ impl DnsProvider for MockDnsProvider {
     async fn delete_records(&mut self, _hostname: String) -> ActorResult<()> {
         Produces::ok(())
     }
+
+    async fn get_records(&mut self) -> ActorResult<Vec<DnsRecordInfo>> {
+        Produces::ok(vec![])
+    }
+
+    async fn set_txt_records(
+        &mut self,
+        _hostname: String,
+        _values: Vec<String>,
+    ) -> ActorResult<()> {
+        Produces::ok(())
+    }
+
+    async fn add_srv_record(&mut self, _name: String, _value: String) -> ActorResult<()> {
+        Produces::ok(())
+    }
+
+    async fn remove_srv_record(&mut self, _name: String, _value: String) -> ActorResult<()> {
+        Produces::ok(())
+    }
 }
@@ -67,6 +67,15 @@ where
         self.records.is_empty()
     }
 
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.lookup.clear();
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &Arc<T>> {
+        self.records.values()
+    }
+
     fn remove_from_lookup(&mut self, record: &T) {
         if let Some(by_type) = self.lookup.get_mut(record.get_name()) {
             if let Some(list) = by_type.get_mut(record.get_type()) {
@@ -0,0 +1,71 @@
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone)]
+pub enum FileChange {
+    Changed(PathBuf),
+    Removed(PathBuf),
+    /// Events got lost, everything below the watched path has to be re-read
+    Rescan,
+}
+
+/// Watches a file or directory (non recursive) and reports debounced changes to the given
+/// callback from a background thread; the watch stops once the watcher gets dropped
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+    pub fn new<F>(path: impl AsRef<Path>, debounce: Duration, on_change: F) -> anyhow::Result<Self>
+    where
+        F: Fn(FileChange) + Send + 'static,
+    {
+        let path = path.as_ref().to_path_buf();
+        let (tx, rx) = mpsc::channel();
+
+        let mut watcher: RecommendedWatcher = notify::watcher(tx, debounce)?;
+        watcher.watch(&path, RecursiveMode::NonRecursive)?;
+
+        thread::spawn(move || {
+            // the channel closes as soon as the watcher is dropped
+            for event in rx {
+                for change in map_event(event) {
+                    on_change(change);
+                }
+            }
+        });
+
+        info!(path = %path.display(), "Started file watcher");
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+fn map_event(event: DebouncedEvent) -> Vec<FileChange> {
+    match event {
+        DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => {
+            vec![FileChange::Changed(path)]
+        }
+        DebouncedEvent::Remove(path) => vec![FileChange::Removed(path)],
+        DebouncedEvent::Rename(from, to) => {
+            vec![FileChange::Removed(from), FileChange::Changed(to)]
+        }
+        DebouncedEvent::Rescan => vec![FileChange::Rescan],
+        DebouncedEvent::Error(e, path) => {
+            warn!(
+                error = format!("{:?}", e).as_str(),
+                path = format!("{:?}", path).as_str(),
+                "File watcher error"
+            );
+
+            vec![FileChange::Rescan]
+        }
+        DebouncedEvent::NoticeWrite(_)
+        | DebouncedEvent::NoticeRemove(_)
+        | DebouncedEvent::Chmod(_) => vec![],
+    }
+}
@@ -1,9 +1,15 @@
+pub mod actions;
 pub mod error;
+pub mod firewalls;
+pub mod images;
+pub mod placement_groups;
 mod request;
 pub mod servers;
 
+use crate::rate_limit::RateLimiter;
 use reqwest::ClientBuilder;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 pub type Result<T> = std::result::Result<T, error::Error>;
 
@@ -17,6 +23,7 @@ pub struct Client {
 pub struct Config {
     address: String,
     api_token: String,
+    rate_limiter: RateLimiter,
 }
 
 impl Client {
@@ -29,6 +36,7 @@ impl Client {
 pub struct Builder {
     address: Option<String>,
     api_token: Option<String>,
+    rate_limit: Option<u64>,
 }
 
 impl Builder {
@@ -42,6 +50,12 @@ impl Builder {
         self
     }
 
+    /// Requests per hour the api allows
+    pub fn rate_limit(mut self, rate_limit: u64) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
     pub fn build(self) -> Result<Client> {
         use error::Error::*;
 
@@ -49,6 +63,10 @@ impl Builder {
             config: Config {
                 address: self.address.ok_or(MissingConfig("address"))?,
                 api_token: self.api_token.ok_or(MissingConfig("api_token"))?,
+                rate_limiter: RateLimiter::new(
+                    self.rate_limit.ok_or(MissingConfig("rate_limit"))?,
+                    Duration::from_secs(3600),
+                ),
             },
             http_client: ClientBuilder::new().build()?,
         })
@@ -0,0 +1,55 @@
+use super::Result;
+use crate::hetzner_cloud::request::get;
+use crate::hetzner_cloud::Client;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Action {
+    pub id: u64,
+    pub command: String,
+    pub status: ActionStatus,
+    #[serde(default)]
+    pub progress: u8,
+    #[serde(default)]
+    pub error: Option<ActionError>,
+    pub started: Option<DateTime<Utc>>,
+    pub finished: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionStatus {
+    Running,
+    Success,
+    Error,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActionError {
+    pub code: String,
+    pub message: String,
+}
+
+#[async_trait]
+pub trait Actions {
+    async fn get_action(&self, action_id: u64) -> Result<Action>;
+}
+
+#[async_trait]
+impl Actions for Client {
+    async fn get_action(&self, action_id: u64) -> Result<Action> {
+        let path = format!("/v1/actions/{}", action_id);
+
+        get(
+            &self.http_client,
+            &self.config,
+            &path,
+            "/action",
+            HashMap::new(),
+        )
+        .await
+    }
+}
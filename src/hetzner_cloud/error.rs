@@ -1,5 +1,6 @@
 use http::{HeaderMap, StatusCode};
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -26,4 +27,20 @@ pub enum Error {
     },
     #[error("Missing response value {0}")]
     MissingResponseValue(String),
+    #[error("Rate limited, retry after {retry_after:?}")]
+    RateLimited { retry_after: Duration },
+}
+
+impl Error {
+    /// Machine readable error code of the api, like `resource_unavailable`
+    pub fn code(&self) -> Option<String> {
+        match self {
+            Error::BadResponse { body, .. } => serde_json::from_str::<serde_json::Value>(body)
+                .ok()?
+                .pointer("/error/code")?
+                .as_str()
+                .map(String::from),
+            _ => None,
+        }
+    }
 }
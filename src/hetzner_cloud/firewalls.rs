@@ -0,0 +1,158 @@
+use super::Result;
+use crate::hetzner_cloud::actions::Action;
+use crate::hetzner_cloud::request::{get_list, post};
+use crate::hetzner_cloud::{Client, PaginationParams};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Firewall {
+    pub id: u64,
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub applied_to: Vec<FirewallResource>,
+}
+
+impl Firewall {
+    /// Also covers servers the firewall got applied to via label selector
+    pub fn is_applied_to_server(&self, server_id: u64) -> bool {
+        self.applied_to
+            .iter()
+            .any(|resource| resource.contains_server(server_id))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FirewallResource {
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<ResourceId>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub applied_to_resources: Vec<FirewallResource>,
+}
+
+impl FirewallResource {
+    pub fn server(server_id: u64) -> Self {
+        Self {
+            resource_type: String::from("server"),
+            server: Some(ResourceId { id: server_id }),
+            applied_to_resources: vec![],
+        }
+    }
+
+    fn contains_server(&self, server_id: u64) -> bool {
+        self.server.as_ref().map(|s| s.id) == Some(server_id)
+            || self
+                .applied_to_resources
+                .iter()
+                .any(|resource| resource.contains_server(server_id))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResourceId {
+    pub id: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ApplyFirewall {
+    pub apply_to: Vec<FirewallResource>,
+}
+
+#[async_trait]
+pub trait Firewalls {
+    async fn search_firewalls(
+        &self,
+        name: Option<&str>,
+        label_selector: Option<&str>,
+    ) -> Result<Vec<Firewall>>;
+    async fn apply_firewall(&self, firewall_id: u64, apply: &ApplyFirewall) -> Result<Vec<Action>>;
+}
+
+#[async_trait]
+impl Firewalls for Client {
+    async fn search_firewalls(
+        &self,
+        name: Option<&str>,
+        label_selector: Option<&str>,
+    ) -> Result<Vec<Firewall>> {
+        let mut params = HashMap::new();
+        if let Some(name) = name {
+            params.insert(String::from("name"), String::from(name));
+        }
+        if let Some(label_selector) = label_selector {
+            params.insert(String::from("label_selector"), String::from(label_selector));
+        }
+
+        let pagination_params = PaginationParams {
+            page: 1,
+            per_page: 50,
+        };
+
+        let (firewalls, _) = get_list(
+            &self.http_client,
+            &self.config,
+            "/v1/firewalls",
+            "/firewalls",
+            params,
+            Some(&pagination_params),
+        )
+        .await?;
+
+        Ok(firewalls)
+    }
+
+    async fn apply_firewall(&self, firewall_id: u64, apply: &ApplyFirewall) -> Result<Vec<Action>> {
+        let path = format!("/v1/firewalls/{}/actions/apply_to_resources", firewall_id);
+
+        post(
+            &self.http_client,
+            &self.config,
+            &path,
+            apply,
+            Some("/actions"),
+            HashMap::new(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_firewall_applied_to_server() {
+        let firewall: Firewall = serde_json::from_str(
+            r#"{
+                "id": 7,
+                "name": "edge",
+                "labels": {},
+                "applied_to": [
+                    {"type": "server", "server": {"id": 42}},
+                    {
+                        "type": "label_selector",
+                        "label_selector": {"selector": "role=edge"},
+                        "applied_to_resources": [{"type": "server", "server": {"id": 43}}]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(firewall.is_applied_to_server(42));
+        assert!(firewall.is_applied_to_server(43));
+        assert!(!firewall.is_applied_to_server(44));
+    }
+
+    #[test]
+    fn test_firewall_applied_to_nothing() {
+        let firewall: Firewall =
+            serde_json::from_str(r#"{"id": 7, "name": "edge", "labels": {}}"#).unwrap();
+
+        assert!(!firewall.is_applied_to_server(42));
+    }
+}
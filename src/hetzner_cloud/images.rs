@@ -0,0 +1,72 @@
+use super::Result;
+use crate::hetzner_cloud::request::get_list;
+use crate::hetzner_cloud::{Client, PaginationParams};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Image {
+    pub id: u64,
+    #[serde(rename = "type")]
+    pub image_type: ImageType,
+    pub status: ImageStatus,
+    pub name: Option<String>,
+    pub description: String,
+    pub created: DateTime<Utc>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageType {
+    System,
+    App,
+    Snapshot,
+    Backup,
+    Temporary,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageStatus {
+    Available,
+    Creating,
+    Unavailable,
+}
+
+#[async_trait]
+pub trait Images {
+    /// Available snapshots matching the label selector, newest first
+    async fn search_snapshots(&self, label_selector: &str) -> Result<Vec<Image>>;
+}
+
+#[async_trait]
+impl Images for Client {
+    async fn search_snapshots(&self, label_selector: &str) -> Result<Vec<Image>> {
+        let mut params = HashMap::new();
+        params.insert(String::from("type"), String::from("snapshot"));
+        params.insert(String::from("status"), String::from("available"));
+        params.insert(String::from("sort"), String::from("created:desc"));
+        params.insert(String::from("label_selector"), String::from(label_selector));
+
+        let pagination_params = PaginationParams {
+            page: 1,
+            per_page: 50,
+        };
+
+        let (images, _) = get_list(
+            &self.http_client,
+            &self.config,
+            "/v1/images",
+            "/images",
+            params,
+            Some(&pagination_params),
+        )
+        .await?;
+
+        Ok(images)
+    }
+}
@@ -0,0 +1,94 @@
+use super::Result;
+use crate::hetzner_cloud::request::{get_list, post};
+use crate::hetzner_cloud::{Client, PaginationParams};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Maximum number of servers within a spread placement group
+pub const SPREAD_PLACEMENT_GROUP_SIZE_LIMIT: usize = 10;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlacementGroup {
+    pub id: u64,
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    #[serde(rename = "type")]
+    pub placement_type: PlacementType,
+    #[serde(default)]
+    pub servers: Vec<u64>,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlacementType {
+    Spread,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NewPlacementGroup<'a> {
+    pub name: &'a str,
+    #[serde(rename = "type")]
+    pub placement_type: PlacementType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<&'a HashMap<String, String>>,
+}
+
+#[async_trait]
+pub trait PlacementGroups {
+    async fn get_all_placement_groups(&self, label_selector: &str) -> Result<Vec<PlacementGroup>>;
+    async fn create_placement_group(
+        &self,
+        placement_group: &NewPlacementGroup<'_>,
+    ) -> Result<PlacementGroup>;
+}
+
+#[async_trait]
+impl PlacementGroups for Client {
+    async fn get_all_placement_groups(&self, label_selector: &str) -> Result<Vec<PlacementGroup>> {
+        let mut params = HashMap::new();
+        params.insert(String::from("label_selector"), String::from(label_selector));
+
+        let mut all_placement_groups = vec![];
+        let mut pagination_params = PaginationParams {
+            page: 1,
+            per_page: 50,
+        };
+
+        loop {
+            let (mut placement_groups, _): (Vec<PlacementGroup>, _) = get_list(
+                &self.http_client,
+                &self.config,
+                "/v1/placement_groups",
+                "/placement_groups",
+                params.clone(),
+                Some(&pagination_params),
+            )
+            .await?;
+
+            if placement_groups.is_empty() {
+                break;
+            }
+
+            all_placement_groups.append(&mut placement_groups);
+            pagination_params.page += 1;
+        }
+
+        Ok(all_placement_groups)
+    }
+
+    async fn create_placement_group(
+        &self,
+        placement_group: &NewPlacementGroup<'_>,
+    ) -> Result<PlacementGroup> {
+        post(
+            &self.http_client,
+            &self.config,
+            "/v1/placement_groups",
+            placement_group,
+            Some("/placement_group"),
+            HashMap::new(),
+        )
+        .await
+    }
+}
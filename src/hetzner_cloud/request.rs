@@ -1,6 +1,7 @@
 use crate::hetzner_cloud::{error::Error, Config, PaginationMeta, PaginationParams, Result};
+use crate::rate_limit::{self, Priority};
 use http::header::ACCEPT;
-use reqwest::{RequestBuilder, Url};
+use reqwest::{RequestBuilder, Response, Url};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::Value;
@@ -24,7 +25,7 @@ pub(super) async fn get_list<R: DeserializeOwned>(
         .with_auth(config)
         .header(ACCEPT, "application/json");
 
-    let response = request_builder.send().await?;
+    let response = send(config, request_builder, Priority::Low).await?;
     let mut json: Value = response.json().await?;
 
     let pagination: Option<PaginationMeta> = match parse_at_pointer(&mut json, "/meta/pagination") {
@@ -41,6 +42,38 @@ pub(super) async fn get_list<R: DeserializeOwned>(
     Ok((data, pagination))
 }
 
+pub(super) async fn get<R: DeserializeOwned>(
+    http_client: &reqwest::Client,
+    config: &Config,
+    path: &str,
+    result_json_path: &str,
+    params: HashMap<String, String>,
+) -> Result<R> {
+    let url = gen_url(config, path, &params)?;
+    let request_builder = http_client
+        .get(url)
+        .with_auth(config)
+        .header(ACCEPT, "application/json");
+
+    let response = send(config, request_builder, Priority::Low).await?;
+
+    if !response.status().is_success() {
+        return Err(Error::BadResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.text().await?,
+        });
+    }
+
+    let mut json: Value = response.json().await?;
+    let data: R = match parse_at_pointer(&mut json, result_json_path) {
+        Some(v) => v,
+        None => return Err(Error::MissingResponseValue(result_json_path.to_owned())),
+    }?;
+
+    Ok(data)
+}
+
 pub(super) async fn post<T: Serialize, R: DeserializeOwned>(
     http_client: &reqwest::Client,
     config: &Config,
@@ -56,7 +89,43 @@ pub(super) async fn post<T: Serialize, R: DeserializeOwned>(
         .header(ACCEPT, "application/json")
         .json(content);
 
-    let response = request_builder.send().await?;
+    let response = send(config, request_builder, Priority::High).await?;
+
+    if !response.status().is_success() {
+        return Err(Error::BadResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.text().await?,
+        });
+    }
+
+    let mut json: Value = response.json().await?;
+    // an empty pointer refers to the whole document
+    let result_json_path = result_json_path.unwrap_or("");
+    let data: R = match parse_at_pointer(&mut json, result_json_path) {
+        Some(v) => v,
+        None => return Err(Error::MissingResponseValue(result_json_path.to_owned())),
+    }?;
+
+    Ok(data)
+}
+
+pub(super) async fn put<T: Serialize, R: DeserializeOwned>(
+    http_client: &reqwest::Client,
+    config: &Config,
+    url_path: &str,
+    content: &T,
+    result_json_path: Option<&str>,
+    params: HashMap<String, String>,
+) -> Result<R> {
+    let url = gen_url(config, url_path, &params)?;
+    let request_builder = http_client
+        .put(url)
+        .with_auth(config)
+        .header(ACCEPT, "application/json")
+        .json(content);
+
+    let response = send(config, request_builder, Priority::High).await?;
 
     if !response.status().is_success() {
         return Err(Error::BadResponse {
@@ -67,7 +136,8 @@ pub(super) async fn post<T: Serialize, R: DeserializeOwned>(
     }
 
     let mut json: Value = response.json().await?;
-    let result_json_path = result_json_path.unwrap_or("/");
+    // an empty pointer refers to the whole document
+    let result_json_path = result_json_path.unwrap_or("");
     let data: R = match parse_at_pointer(&mut json, result_json_path) {
         Some(v) => v,
         None => return Err(Error::MissingResponseValue(result_json_path.to_owned())),
@@ -88,7 +158,7 @@ pub(super) async fn delete(
         .with_auth(config)
         .header(ACCEPT, "application/json");
 
-    let response = request_builder.send().await?;
+    let response = send(config, request_builder, Priority::High).await?;
 
     if !response.status().is_success() {
         return Err(Error::BadResponse {
@@ -101,6 +171,20 @@ pub(super) async fn delete(
     Ok(())
 }
 
+async fn send(
+    config: &Config,
+    request_builder: RequestBuilder,
+    priority: Priority,
+) -> Result<Response> {
+    rate_limit::send(
+        &config.rate_limiter,
+        request_builder,
+        priority,
+        |retry_after| Error::RateLimited { retry_after },
+    )
+    .await
+}
+
 trait Paginate {
     fn paginate(&mut self, pagination: &PaginationParams);
 }
@@ -1,5 +1,6 @@
 use super::Result;
-use crate::hetzner_cloud::request::{delete, get_list, post};
+use crate::hetzner_cloud::actions::Action;
+use crate::hetzner_cloud::request::{delete, get, get_list, post, put};
 use crate::hetzner_cloud::{Client, PaginationMeta, PaginationParams};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
@@ -14,22 +15,104 @@ pub struct Server {
     pub name: String,
     pub created: DateTime<Utc>,
     pub public_net: ServerPublicNet,
+    #[serde(default)]
+    pub private_net: Vec<ServerPrivateNet>,
+    pub datacenter: Datacenter,
+    pub server_type: ServerType,
+    pub status: ServerStatus,
+    /// Missing if the image got deleted after the server was created from it
+    #[serde(default)]
+    pub image: Option<ServerImage>,
     pub labels: HashMap<String, String>,
 }
 
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerStatus {
+    Running,
+    Initializing,
+    Starting,
+    Stopping,
+    Off,
+    Deleting,
+    Migrating,
+    Rebuilding,
+    Unknown,
+}
+
+/// Server as returned on creation, together with the actions that boot it
+#[derive(Clone, Debug, Deserialize)]
+pub struct CreatedServer {
+    pub server: Server,
+    pub action: Action,
+    #[serde(default)]
+    pub next_actions: Vec<Action>,
+}
+
+impl CreatedServer {
+    pub fn action_ids(&self) -> Vec<u64> {
+        std::iter::once(&self.action)
+            .chain(self.next_actions.iter())
+            .map(|action| action.id)
+            .collect()
+    }
+}
+
 impl Server {
     pub fn get_ip_addresses(&self) -> Vec<IpAddr> {
-        vec![
-            IpAddr::V4(self.public_net.ipv4.ip),
-            IpAddr::V6(self.public_net.ipv6.ip),
-        ]
+        let mut ip_addresses = vec![];
+
+        if let Some(ipv4) = self.public_net.ipv4.as_ref() {
+            ip_addresses.push(IpAddr::V4(ipv4.ip));
+        }
+
+        if let Some(ipv6) = self.public_net.ipv6.as_ref() {
+            ip_addresses.push(IpAddr::V6(ipv6.ip));
+        }
+
+        ip_addresses
     }
+
+    pub fn get_private_ip_addresses(&self) -> Vec<IpAddr> {
+        self.private_net
+            .iter()
+            .map(|net| IpAddr::V4(net.ip))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerType {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerImage {
+    pub id: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Datacenter {
+    pub name: String,
+    pub location: Location,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Location {
+    pub name: String,
 }
 
+/// Public addresses are missing if they were disabled on creation
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ServerPublicNet {
-    pub ipv4: Ipv4Info,
-    pub ipv6: Ipv6Info,
+    pub ipv4: Option<Ipv4Info>,
+    pub ipv6: Option<Ipv6Info>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerPrivateNet {
+    pub network: u64,
+    pub ip: Ipv4Addr,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -63,13 +146,57 @@ pub struct NewServer<'a> {
     pub labels: Option<&'a HashMap<String, String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub location: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub networks: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_net: Option<NewServerPublicNet>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub firewalls: Vec<NewServerFirewall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placement_group: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NewServerFirewall {
+    pub firewall: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NewServerPublicNet {
+    pub enable_ipv4: bool,
+    pub enable_ipv6: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct UpdateServer<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<&'a HashMap<String, String>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RebuildServer<'a> {
+    pub image: &'a str,
+}
+
+/// Without a dns pointer the reverse dns entry is reset to the default of the address
+#[derive(Clone, Debug, Serialize)]
+pub struct ChangeDnsPtr<'a> {
+    pub ip: IpAddr,
+    pub dns_ptr: Option<&'a str>,
 }
 
 #[async_trait]
 pub trait Servers {
     async fn get_all_servers(&self, label_selector: Option<&str>) -> Result<Vec<Server>>;
-    async fn create_server(&self, server: &NewServer<'_>) -> Result<Server>;
+    async fn create_server(&self, server: &NewServer<'_>) -> Result<CreatedServer>;
+    async fn get_server(&self, server_id: u64) -> Result<Server>;
+    async fn update_server(&self, server_id: u64, server: &UpdateServer<'_>) -> Result<Server>;
     async fn delete_server(&self, server_id: u64) -> Result<()>;
+    async fn reset_server(&self, server_id: u64) -> Result<Action>;
+    async fn rebuild_server(&self, server_id: u64, rebuild: &RebuildServer<'_>) -> Result<Action>;
+    async fn change_dns_ptr(&self, server_id: u64, change: &ChangeDnsPtr<'_>) -> Result<Action>;
     async fn search_server(&self, hostname: &str) -> Result<Option<Server>>;
 }
 
@@ -137,12 +264,39 @@ impl Servers for Client {
         Ok(servers.pop())
     }
 
-    async fn create_server(&self, server: &NewServer<'_>) -> Result<Server> {
+    async fn create_server(&self, server: &NewServer<'_>) -> Result<CreatedServer> {
         post(
             &self.http_client,
             &self.config,
             "/v1/servers",
             server,
+            None,
+            HashMap::new(),
+        )
+        .await
+    }
+
+    async fn get_server(&self, server_id: u64) -> Result<Server> {
+        let path = format!("/v1/servers/{}", server_id);
+
+        get(
+            &self.http_client,
+            &self.config,
+            &path,
+            "/server",
+            HashMap::new(),
+        )
+        .await
+    }
+
+    async fn update_server(&self, server_id: u64, server: &UpdateServer<'_>) -> Result<Server> {
+        let path = format!("/v1/servers/{}", server_id);
+
+        put(
+            &self.http_client,
+            &self.config,
+            &path,
+            server,
             Some("/server"),
             HashMap::new(),
         )
@@ -154,6 +308,48 @@ impl Servers for Client {
 
         delete(&self.http_client, &self.config, &path, HashMap::new()).await
     }
+
+    async fn reset_server(&self, server_id: u64) -> Result<Action> {
+        let path = format!("/v1/servers/{}/actions/reset", server_id);
+
+        post(
+            &self.http_client,
+            &self.config,
+            &path,
+            &HashMap::<String, String>::new(),
+            Some("/action"),
+            HashMap::new(),
+        )
+        .await
+    }
+
+    async fn rebuild_server(&self, server_id: u64, rebuild: &RebuildServer<'_>) -> Result<Action> {
+        let path = format!("/v1/servers/{}/actions/rebuild", server_id);
+
+        post(
+            &self.http_client,
+            &self.config,
+            &path,
+            rebuild,
+            Some("/action"),
+            HashMap::new(),
+        )
+        .await
+    }
+
+    async fn change_dns_ptr(&self, server_id: u64, change: &ChangeDnsPtr<'_>) -> Result<Action> {
+        let path = format!("/v1/servers/{}/actions/change_dns_ptr", server_id);
+
+        post(
+            &self.http_client,
+            &self.config,
+            &path,
+            change,
+            Some("/action"),
+            HashMap::new(),
+        )
+        .await
+    }
 }
 
 fn allocate_result_vec<T>(pagination_meta: Option<PaginationMeta>) -> Vec<T> {
@@ -163,21 +359,39 @@ fn allocate_result_vec<T>(pagination_meta: Option<PaginationMeta>) -> Vec<T> {
         .unwrap_or_default()
 }
 
-/// Parses an ipv6 cidr address like 2a01:4f8:c17:5038::/64 and adds an explicit ::1 if the
-/// address ends in ::
+/// Hetzner assigns a /64 network to each server, the server itself uses the first address in it
+const IPV6_NETWORK_PREFIX: u32 = 64;
+
+/// Parses an ipv6 cidr address like 2a01:4f8:c17:5038::/64 and uses the first host address ::1 if
+/// the address is the network itself, so dns records and pointers are set on the server's address
 fn parse_ipv6_cidr<'de, D>(deserializer: D) -> std::result::Result<Ipv6Addr, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s: String = Deserialize::deserialize(deserializer)?;
-    let v = s.split('/').next().unwrap_or("");
+    let mut parts = s.split('/');
+
+    let address: Ipv6Addr = parts
+        .next()
+        .unwrap_or("")
+        .parse()
+        .map_err(D::Error::custom)?;
+    let prefix = match parts.next() {
+        Some(p) => p.parse::<u32>().map_err(D::Error::custom)?,
+        None => IPV6_NETWORK_PREFIX,
+    };
+
+    Ok(ipv6_host_address(address, prefix))
+}
 
-    if !v.ends_with("::") {
-        v.parse().map_err(D::Error::custom)
-    } else {
-        let s = format!("{}::1", v.split("::").next().unwrap_or_default());
+fn ipv6_host_address(address: Ipv6Addr, prefix: u32) -> Ipv6Addr {
+    let host_mask = u128::MAX.checked_shr(prefix).unwrap_or(0);
+    let address = u128::from(address);
 
-        s.parse().map_err(D::Error::custom)
+    if host_mask != 0 && address & host_mask == 0 {
+        Ipv6Addr::from(address | 1)
+    } else {
+        Ipv6Addr::from(address)
     }
 }
 
@@ -222,4 +436,45 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_ipv6_cidr_parsing_expanded() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use serde::de::value::Error as ValueError;
+        let deserializer = "2a01:4f8:c17:0:0:0:0:0/64".to_owned().into_deserializer();
+        let ip: std::result::Result<Ipv6Addr, ValueError> = parse_ipv6_cidr(deserializer);
+
+        let expected: Ipv6Addr = "2a01:4f8:c17::1".parse()?;
+        assert_eq!(expected, ip.unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipv6_host_address() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let network: Ipv6Addr = "2a01:4f8:c17:5038::".parse()?;
+        let host: Ipv6Addr = "2a01:4f8:c17:5038::1".parse()?;
+
+        assert_eq!(host, ipv6_host_address(network, 64));
+        assert_eq!(host, ipv6_host_address(host, 64));
+        assert_eq!(network, ipv6_host_address(network, 128));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_public_net_uses_ipv6_host_address(
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let public_net: ServerPublicNet = serde_json::from_str(
+            r#"{
+                "ipv4": {"ip": "1.2.3.4", "dns_ptr": "static.4.3.2.1.clients.your-server.de"},
+                "ipv6": {"ip": "2a01:4f8:c17:5038::/64", "dns_ptr": []}
+            }"#,
+        )?;
+
+        let ipv6 = public_net.ipv6.unwrap();
+        let expected: Ipv6Addr = "2a01:4f8:c17:5038::1".parse()?;
+        assert_eq!(expected, ipv6.ip);
+
+        Ok(())
+    }
 }
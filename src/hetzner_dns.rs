@@ -3,8 +3,10 @@ pub mod records;
 mod request;
 pub mod zones;
 
+use crate::rate_limit::RateLimiter;
 use reqwest::ClientBuilder;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct Client {
@@ -16,6 +18,7 @@ pub struct Client {
 struct Config {
     address: String,
     api_token: String,
+    rate_limiter: RateLimiter,
 }
 
 impl Client {
@@ -28,6 +31,7 @@ impl Client {
 pub struct Builder {
     address: Option<String>,
     api_token: Option<String>,
+    rate_limit: Option<u64>,
 }
 
 impl Builder {
@@ -41,6 +45,12 @@ impl Builder {
         self
     }
 
+    /// Requests per hour the api allows
+    pub fn rate_limit(mut self, rate_limit: u64) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
     pub fn build(self) -> Result<Client> {
         use error::Error::*;
 
@@ -48,6 +58,11 @@ impl Builder {
             config: Config {
                 address: self.address.ok_or(MissingConfig("address"))?,
                 api_token: self.api_token.ok_or(MissingConfig("api_token"))?,
+                // initial budget until the response headers tell the actual limit
+                rate_limiter: RateLimiter::new(
+                    self.rate_limit.ok_or(MissingConfig("rate_limit"))?,
+                    Duration::from_secs(3600),
+                ),
             },
             http_client: ClientBuilder::new().build()?,
         })
@@ -1,5 +1,6 @@
 use http::HeaderMap;
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -22,4 +23,6 @@ pub enum Error {
     },
     #[error("Missing response value {0}")]
     MissingResponseValue(String),
+    #[error("Rate limited, retry after {retry_after:?}")]
+    RateLimited { retry_after: Duration },
 }
@@ -1,6 +1,7 @@
 use crate::hetzner_dns::{error::Error, Config, PaginationMeta, PaginationParams, Result};
+use crate::rate_limit::{self, Priority};
 use http::header::ACCEPT;
-use reqwest::{RequestBuilder, Url};
+use reqwest::{RequestBuilder, Response, Url};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::Value;
@@ -24,7 +25,7 @@ pub(super) async fn get_list<R: DeserializeOwned>(
         .with_auth(config)
         .header(ACCEPT, "application/json");
 
-    let response = request_builder.send().await?;
+    let response = send(config, request_builder, Priority::Low).await?;
     let mut json: Value = response.json().await?;
 
     let pagination: Option<PaginationMeta> = match parse_at_pointer(&mut json, "/meta/pagination") {
@@ -56,7 +57,7 @@ pub(super) async fn post<T: Serialize, R: DeserializeOwned>(
         .header(ACCEPT, "application/json")
         .json(content);
 
-    let response = request_builder.send().await?;
+    let response = send(config, request_builder, Priority::High).await?;
 
     if !response.status().is_success() {
         return Err(Error::BadResponse {
@@ -87,7 +88,7 @@ pub(super) async fn delete(
         .with_auth(config)
         .header(ACCEPT, "application/json");
 
-    let response = request_builder.send().await?;
+    let response = send(config, request_builder, Priority::High).await?;
 
     if !response.status().is_success() {
         return Err(Error::BadResponse {
@@ -99,6 +100,20 @@ pub(super) async fn delete(
     Ok(())
 }
 
+async fn send(
+    config: &Config,
+    request_builder: RequestBuilder,
+    priority: Priority,
+) -> Result<Response> {
+    rate_limit::send(
+        &config.rate_limiter,
+        request_builder,
+        priority,
+        |retry_after| Error::RateLimited { retry_after },
+    )
+    .await
+}
+
 trait Paginate {
     fn paginate(&mut self, pagination: &PaginationParams);
 }
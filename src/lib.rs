@@ -5,11 +5,14 @@ pub mod actor;
 pub mod cloud_init;
 pub mod cloud_provider;
 pub mod config;
+pub mod consul;
 pub mod dns_provider;
+pub mod file_watcher;
 pub mod hetzner_cloud;
 pub mod hetzner_dns;
 pub mod node;
 pub mod node_groups;
+pub mod rate_limit;
 pub mod utils;
 
 type AppConfig = Arc<Config>;
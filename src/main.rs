@@ -3,14 +3,16 @@ use act_zero::upcast;
 use edge_auto_scaler::config::load_config;
 use edge_auto_scaler::node::discovery::NodeDiscovery;
 use edge_auto_scaler::node::exploration::NodeExploration;
+use edge_auto_scaler::node::reconciliation::NodeReconciliation;
 use edge_auto_scaler::node::stats::{build_stream_factory_from_config, NodeStatsStreamFactory};
-use edge_auto_scaler::node::NodeStats;
+use edge_auto_scaler::node::{LifecycleHooks, NodeStats};
 use edge_auto_scaler::node_groups::discovery::NodeGroupDiscovery;
 use edge_auto_scaler::node_groups::NodeGroupsController;
 use edge_auto_scaler::{cloud_provider, dns_provider, node, node_groups};
 use futures::task::Context;
 use opentelemetry::api::Provider;
 use opentelemetry::sdk;
+use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::macros::support::{Pin, Poll};
@@ -68,7 +70,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cloud_provider = cloud_provider::build_from_config(Arc::clone(&config))?;
     let dns_provider = dns_provider::build_from_config(Arc::clone(&config))?;
     let node_discovery_provider =
-        node::discovery::provider::build_from_config(Arc::clone(&config))?;
+        node::discovery::provider::build_from_config(Arc::clone(&config), dns_provider.clone())?;
+
+    let lifecycle_hooks = Arc::new(LifecycleHooks::new(
+        config.node_controller.lifecycle_hooks.clone(),
+    )?);
 
     let node_group_discovery_providers =
         node_groups::discovery::provider::build_from_config(Arc::clone(&config))?;
@@ -79,6 +85,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         dns_provider.clone(),
         stream_factory.clone(),
         Arc::new(node_group_scaler_config.node_hostname_suffix.clone()),
+        lifecycle_hooks,
         Arc::clone(&config),
     ));
 
@@ -100,6 +107,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         config.node_group_discovery.interval,
     ));
 
+    let _node_reconciliation = config.node_reconciliation.as_ref().map(|nr_config| {
+        spawn_actor(NodeReconciliation::new(
+            node_groups_controller.clone(),
+            node_discovery_provider.clone(),
+            cloud_provider.clone(),
+            dns_provider.clone(),
+            node_group_scaler_config.node_hostname_suffix.clone(),
+            nr_config.clone(),
+        ))
+    });
+
     let mut interval = tokio::time::interval(Duration::from_secs(30));
     loop {
         tokio::select! {
@@ -112,7 +130,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 struct StreamFactory;
 
 impl NodeStatsStreamFactory for StreamFactory {
-    fn create_stream(&self, hostname: String) -> Pin<Box<dyn Stream<Item = NodeStats> + Send>> {
+    fn create_stream(
+        &self,
+        hostname: String,
+        _private_address: Option<IpAddr>,
+    ) -> Pin<Box<dyn Stream<Item = NodeStats> + Send>> {
         info!("Creating NodeStatsStream for {}", hostname);
         Box::pin(FixedNodeStatsStream {
             interval: tokio::time::interval(Duration::from_millis(100)),
@@ -2,17 +2,21 @@ use act_zero::Actor;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
 
 mod controller;
 pub mod discovery;
 pub mod exploration;
 mod hostname;
+pub mod lifecycle_hooks;
+pub mod reconciliation;
 pub mod stats;
 
 use crate::node::discovery::NodeDiscoveryState;
 pub use controller::NodeController;
 pub use controller::Providers as NodeControllerProviders;
 pub use hostname::HostnameGenerator;
+pub use lifecycle_hooks::LifecycleHooks;
 
 #[derive(Debug, Clone)]
 pub struct Node {
@@ -64,12 +68,50 @@ impl fmt::Display for NodeDrainingCause {
     }
 }
 
+#[derive(Debug, Copy, Clone, Deserialize, PartialEq, Serialize)]
+pub enum QuarantineReason {
+    DiscoveryTimeout,
+    ExplorationTimeout,
+    UnexpectedState,
+    ProvisioningFailed,
+}
+
+impl fmt::Display for QuarantineReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                QuarantineReason::DiscoveryTimeout => "discovery-timeout",
+                QuarantineReason::ExplorationTimeout => "exploration-timeout",
+                QuarantineReason::UnexpectedState => "unexpected-state",
+                QuarantineReason::ProvisioningFailed => "provisioning-failed",
+            }
+        )
+    }
+}
+
+impl FromStr for QuarantineReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "discovery-timeout" => QuarantineReason::DiscoveryTimeout,
+            "exploration-timeout" => QuarantineReason::ExplorationTimeout,
+            "unexpected-state" => QuarantineReason::UnexpectedState,
+            "provisioning-failed" => QuarantineReason::ProvisioningFailed,
+            _ => return Err(format!("Unknown quarantine reason: {}", s)),
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum NodeState {
     Unready,
     Ready,
     Active,
     Draining(NodeDrainingCause),
+    Quarantined(QuarantineReason),
     Deprovisioned,
 }
 
@@ -85,6 +127,10 @@ impl NodeState {
     pub fn is_ready(&self) -> bool {
         matches!(self, NodeState::Ready)
     }
+
+    pub fn is_quarantined(&self) -> bool {
+        matches!(self, NodeState::Quarantined(_))
+    }
 }
 
 impl From<NodeDiscoveryState> for NodeState {
@@ -101,6 +147,8 @@ impl From<NodeDiscoveryState> for NodeState {
 pub struct NodeStateInfo {
     pub hostname: String,
     pub state: NodeState,
+    pub location: Option<String>,
+    pub server_type: Option<String>,
 }
 
 #[async_trait]
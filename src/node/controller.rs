@@ -2,16 +2,18 @@ mod config;
 mod state_machine;
 mod stats_streamer;
 
-use crate::cloud_provider::{CloudNodeInfo, CloudProvider};
+use crate::cloud_provider::{CloudNodeInfo, CloudProvider, NodeSpec};
+use crate::config::NodeRecovery;
 use crate::dns_provider::DnsProvider;
 use crate::node::controller::state_machine::{NodeMachine, NodeMachineEvent};
 use crate::node::discovery::{NodeDiscoveryData, NodeDiscoveryProvider, NodeDiscoveryState};
-use crate::node::{Node, NodeDrainingCause, NodeStateObserver, NodeStatsObserver};
+use crate::node::{LifecycleHooks, Node, NodeDrainingCause, NodeStateObserver, NodeStatsObserver};
 use act_zero::runtimes::tokio::Timer;
 use act_zero::timer::Tick;
 use act_zero::{send, Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
 use async_trait::async_trait;
 use std::fmt;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::info;
 
@@ -90,12 +92,15 @@ pub struct Providers {
 }
 
 impl NodeController {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         node: Node,
         node_stats_observer: WeakAddr<dyn NodeStatsObserver>,
         node_state_observer: WeakAddr<dyn NodeStateObserver>,
         providers: Providers,
         node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
+        lifecycle_hooks: Arc<LifecycleHooks>,
+        recovery: Option<NodeRecovery>,
         config: AppConfig,
     ) -> Self {
         let nc_config = &config.node_controller;
@@ -104,6 +109,9 @@ impl NodeController {
             provisioning_timeout: nc_config.provisioning_timeout,
             discovery_timeout: nc_config.discovery_timeout,
             exploration_timeout: nc_config.exploration_timeout,
+            quarantine_duration: nc_config.quarantine.as_ref().map(|q| q.duration),
+            deletion_timeout: nc_config.deletion_timeout,
+            recovery_steps: recovery.map(|r| r.steps).unwrap_or_default(),
         };
 
         Self {
@@ -118,6 +126,7 @@ impl NodeController {
                 providers.dns_provider,
                 node_stats_observer,
                 node_stats_stream_factory,
+                lifecycle_hooks,
                 nm_config,
             )),
         }
@@ -128,8 +137,8 @@ impl NodeController {
         skip(self),
         fields(hostname = %self.node.hostname, group = %self.node.group)
     )]
-    pub async fn provision_node(&mut self, target_state: NodeDiscoveryState) {
-        self.process_node_machine(Some(NodeMachineEvent::ProvisionNode { target_state }))
+    pub async fn provision_node(&mut self, target_state: NodeDiscoveryState, spec: NodeSpec) {
+        self.process_node_machine(Some(NodeMachineEvent::ProvisionNode { target_state, spec }))
             .await;
     }
 
@@ -1,3 +1,4 @@
+use crate::config::NodeRecoveryStep;
 use std::time::Duration;
 
 #[derive(Debug)]
@@ -6,4 +7,7 @@ pub struct Config {
     pub draining_time: Duration,
     pub discovery_timeout: Duration,
     pub exploration_timeout: Duration,
+    pub quarantine_duration: Option<Duration>,
+    pub deletion_timeout: Duration,
+    pub recovery_steps: Vec<NodeRecoveryStep>,
 }
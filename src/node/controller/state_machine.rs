@@ -6,23 +6,32 @@ mod draining;
 mod exploring;
 mod initializing;
 mod provisioning;
+mod quarantined;
 mod ready;
+mod recovering;
 
 use super::Config;
 use super::StatsStreamer;
-use crate::cloud_provider::{CloudNodeInfo, CloudProvider};
+use crate::cloud_provider::{CloudNodeInfo, CloudProvider, NodeSpec, QuarantineStatus};
+use crate::config::{NodeLifecycleEvent, NodeRecoveryStep};
 use crate::dns_provider::DnsProvider;
 use crate::node::discovery::{NodeDiscoveryData, NodeDiscoveryProvider, NodeDiscoveryState};
+use crate::node::lifecycle_hooks::{HookOutcome, HookPayload, LifecycleHooks};
 use crate::node::stats::NodeStatsStreamFactory;
 use crate::node::{
     Node, NodeDrainingCause, NodeState, NodeStateInfo, NodeStateObserver, NodeStatsObserver,
+    QuarantineReason,
 };
 use act_zero::runtimes::tokio::spawn_actor;
 use act_zero::{send, Addr, WeakAddr};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
 use std::string::ToString;
-use std::time::Instant;
-use tracing::info;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
 
 #[derive(strum_macros::Display, Debug)]
 pub enum NodeMachine {
@@ -33,17 +42,28 @@ pub enum NodeMachine {
     Ready(Data<Ready>),
     Active(Data<Active>),
     Draining(Data<Draining>),
+    Recovering(Data<Recovering>),
+    Quarantined(Data<Quarantined>),
     Deprovisioning(Data<Deprovisioning>),
     Deprovisioned(Data<Deprovisioned>),
 }
 
 #[derive(Debug)]
 pub enum NodeMachineEvent {
-    ProvisionNode { target_state: NodeDiscoveryState },
-    DiscoveredNode { discovery_data: NodeDiscoveryData },
-    ExploredNode { node_info: CloudNodeInfo },
+    ProvisionNode {
+        target_state: NodeDiscoveryState,
+        spec: NodeSpec,
+    },
+    DiscoveredNode {
+        discovery_data: NodeDiscoveryData,
+    },
+    ExploredNode {
+        node_info: CloudNodeInfo,
+    },
     ActivateNode,
-    DeprovisionNode { cause: NodeDrainingCause },
+    DeprovisionNode {
+        cause: NodeDrainingCause,
+    },
 }
 
 #[async_trait]
@@ -67,6 +87,7 @@ pub struct Shared {
     dns_provider: Addr<dyn DnsProvider>,
     node_stats_observer: WeakAddr<dyn NodeStatsObserver>,
     node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
+    lifecycle_hooks: Arc<LifecycleHooks>,
     config: Config,
 }
 
@@ -77,17 +98,28 @@ pub struct Initializing {}
 pub struct Provisioning {
     node_info: Option<CloudNodeInfo>,
     entered_state_at: Instant,
+    /// The cloud provider reported the node as up and running
+    completed_node: bool,
+    last_completion_check: Option<Instant>,
     created_dns_records: bool,
+    created_ptr_records: bool,
+    registered_node: bool,
     target_state: NodeDiscoveryState,
+    spec: NodeSpec,
 }
 
 impl Provisioning {
-    fn new(target_state: NodeDiscoveryState) -> Self {
+    fn new(target_state: NodeDiscoveryState, spec: NodeSpec) -> Self {
         Self {
             node_info: None,
             entered_state_at: Instant::now(),
+            completed_node: false,
+            last_completion_check: None,
             created_dns_records: false,
+            created_ptr_records: false,
+            registered_node: false,
             target_state,
+            spec,
         }
     }
 }
@@ -131,6 +163,9 @@ pub struct Ready {
     last_discovered_at: Option<Instant>,
     stats_streamer: Option<Addr<StatsStreamer>>,
     marked_as_ready: bool,
+    ran_post_ready_hooks: bool,
+    activation_requested_at: Option<Instant>,
+    pre_activate_hooks: Option<HookRun>,
 }
 
 impl Ready {
@@ -141,6 +176,9 @@ impl Ready {
             last_discovered_at: None,
             stats_streamer,
             marked_as_ready: false,
+            ran_post_ready_hooks: false,
+            activation_requested_at: None,
+            pre_activate_hooks: None,
         }
     }
 }
@@ -152,6 +190,8 @@ pub struct Active {
     entered_state_at: Instant,
     last_discovered_at: Option<Instant>,
     stats_streamer: Option<Addr<StatsStreamer>>,
+    drain_requested: Option<(NodeDrainingCause, Instant)>,
+    pre_drain_hooks: Option<HookRun>,
 }
 
 impl Active {
@@ -162,6 +202,8 @@ impl Active {
             entered_state_at: Instant::now(),
             last_discovered_at: None,
             stats_streamer,
+            drain_requested: None,
+            pre_drain_hooks: None,
         }
     }
 
@@ -172,6 +214,8 @@ impl Active {
             entered_state_at: Instant::now(),
             last_discovered_at: None,
             stats_streamer,
+            drain_requested: None,
+            pre_drain_hooks: None,
         }
     }
 }
@@ -183,6 +227,8 @@ pub struct Draining {
     marked_as_draining: bool,
     entered_state_at: Instant,
     stats_streamer: Option<Addr<StatsStreamer>>,
+    activation_requested_at: Option<Instant>,
+    pre_activate_hooks: Option<HookRun>,
 }
 
 impl Draining {
@@ -197,6 +243,8 @@ impl Draining {
             marked_as_draining: false,
             entered_state_at: Instant::now(),
             stats_streamer,
+            activation_requested_at: None,
+            pre_activate_hooks: None,
         }
     }
 
@@ -211,6 +259,72 @@ impl Draining {
             marked_as_draining: true,
             entered_state_at: Instant::now(),
             stats_streamer,
+            activation_requested_at: None,
+            pre_activate_hooks: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Recovering {
+    node_info: CloudNodeInfo,
+    step: usize,
+    entered_step_at: Instant,
+    ran_action: bool,
+    last_action_attempt: Option<Instant>,
+    /// Traffic is kept away from the node while it's reset or rebuilt
+    deleted_dns_records: bool,
+    /// Discovery state the node came back with, once its dns records need to be restored
+    recovered_state: Option<NodeDiscoveryState>,
+}
+
+impl Recovering {
+    fn new(node_info: CloudNodeInfo) -> Self {
+        Self {
+            node_info,
+            step: 0,
+            entered_step_at: Instant::now(),
+            ran_action: false,
+            last_action_attempt: None,
+            deleted_dns_records: false,
+            recovered_state: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Quarantined {
+    node_info: Option<CloudNodeInfo>,
+    reason: QuarantineReason,
+    /// Wall clock time instead of an instant, as it's recorded on the node to outlive restarts
+    quarantined_at: DateTime<Utc>,
+    labelled_node: bool,
+    deleted_dns_records: bool,
+}
+
+impl Quarantined {
+    fn new(node_info: Option<CloudNodeInfo>, reason: QuarantineReason) -> Self {
+        Self {
+            node_info,
+            reason,
+            quarantined_at: Utc::now(),
+            labelled_node: false,
+            deleted_dns_records: false,
+        }
+    }
+
+    /// Continues the quarantine recorded on the node; nodes labelled without a start time get
+    /// labelled again, so the quarantine can run out after a restart
+    fn new_labelled(node_info: CloudNodeInfo, reason: QuarantineReason) -> Self {
+        let since = match node_info.quarantine {
+            Some(QuarantineStatus::Quarantined { since, .. }) => since,
+            _ => None,
+        };
+
+        Self {
+            quarantined_at: since.unwrap_or_else(Utc::now),
+            labelled_node: since.is_some(),
+            ..Self::new(Some(node_info), reason)
         }
     }
 }
@@ -218,16 +332,30 @@ impl Draining {
 #[derive(Debug)]
 pub struct Deprovisioning {
     node_info: Option<CloudNodeInfo>,
+    entered_state_at: Instant,
+    passed_pre_delete_hooks: bool,
+    pre_delete_hooks: Option<HookRun>,
+    deletion_started_at: Option<Instant>,
     deleted_node: bool,
+    verified_deletion: bool,
+    last_verification_attempt: Option<Instant>,
     deleted_dns_records: bool,
+    deregistered_node: bool,
 }
 
 impl Deprovisioning {
     fn new(node_info: Option<CloudNodeInfo>) -> Self {
         Self {
             node_info,
+            entered_state_at: Instant::now(),
+            passed_pre_delete_hooks: false,
+            pre_delete_hooks: None,
+            deletion_started_at: None,
             deleted_node: false,
+            verified_deletion: false,
+            last_verification_attempt: None,
             deleted_dns_records: false,
+            deregistered_node: false,
         }
     }
 }
@@ -236,6 +364,7 @@ impl Deprovisioning {
 pub struct Deprovisioned;
 
 impl NodeMachine {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         node: Node,
         node_discovery_provider: Addr<dyn NodeDiscoveryProvider>,
@@ -243,6 +372,7 @@ impl NodeMachine {
         dns_provider: Addr<dyn DnsProvider>,
         node_stats_observer: WeakAddr<dyn NodeStatsObserver>,
         node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
+        lifecycle_hooks: Arc<LifecycleHooks>,
         config: Config,
     ) -> Self {
         Self::Initializing(Data {
@@ -254,6 +384,7 @@ impl NodeMachine {
                 dns_provider,
                 node_stats_observer,
                 node_stats_stream_factory,
+                lifecycle_hooks,
                 config,
             },
         })
@@ -276,6 +407,8 @@ impl NodeMachine {
             Self::Ready(m) => handler.progress(m, event).await,
             Self::Active(m) => handler.progress(m, event).await,
             Self::Draining(m) => handler.progress(m, event).await,
+            Self::Recovering(m) => handler.progress(m, event).await,
+            Self::Quarantined(m) => handler.progress(m, event).await,
             Self::Deprovisioning(m) => handler.progress(m, event).await,
             Self::Deprovisioned(m) => handler.progress(m, event).await,
         };
@@ -288,7 +421,29 @@ impl NodeMachine {
         result
     }
 
+    /// Cloud node info as far as it is known in the current state
+    fn node_info(&self) -> Option<&CloudNodeInfo> {
+        match self {
+            NodeMachine::Initializing(_)
+            | NodeMachine::Exploring(_)
+            | NodeMachine::Deprovisioned(_) => None,
+            NodeMachine::Provisioning(Data { state, .. }) => state.node_info.as_ref(),
+            NodeMachine::Discovering(Data { state, .. }) => Some(&state.node_info),
+            NodeMachine::Ready(Data { state, .. }) => Some(&state.node_info),
+            NodeMachine::Active(Data { state, .. }) => Some(&state.node_info),
+            NodeMachine::Draining(Data { state, .. }) => Some(&state.node_info),
+            NodeMachine::Recovering(Data { state, .. }) => Some(&state.node_info),
+            NodeMachine::Quarantined(Data { state, .. }) => state.node_info.as_ref(),
+            NodeMachine::Deprovisioning(Data { state, .. }) => state.node_info.as_ref(),
+        }
+    }
+
     fn publish_node_state(&self, node_state_observer: &WeakAddr<dyn NodeStateObserver>) {
+        // the location and server type the node ended up with, which differ from the requested
+        // ones if the cloud provider fell back to another server option
+        let location = self.node_info().and_then(|ni| ni.location.clone());
+        let server_type = self.node_info().and_then(|ni| ni.server_type.clone());
+
         let node_state_info = match self {
             NodeMachine::Initializing(Data {
                 shared: Shared { node, .. },
@@ -306,12 +461,18 @@ impl NodeMachine {
                 shared: Shared { node, .. },
                 ..
             })
+            | NodeMachine::Recovering(Data {
+                shared: Shared { node, .. },
+                ..
+            })
             | NodeMachine::Deprovisioning(Data {
                 shared: Shared { node, .. },
                 ..
             }) => NodeStateInfo {
                 state: NodeState::Unready,
                 hostname: node.hostname.clone(),
+                location,
+                server_type,
             },
             NodeMachine::Ready(Data {
                 shared: Shared { node, .. },
@@ -319,6 +480,8 @@ impl NodeMachine {
             }) => NodeStateInfo {
                 state: NodeState::Ready,
                 hostname: node.hostname.clone(),
+                location,
+                server_type,
             },
             NodeMachine::Active(Data {
                 shared: Shared { node, .. },
@@ -326,6 +489,8 @@ impl NodeMachine {
             }) => NodeStateInfo {
                 state: NodeState::Active,
                 hostname: node.hostname.clone(),
+                location,
+                server_type,
             },
             NodeMachine::Draining(Data {
                 shared: Shared { node, .. },
@@ -334,6 +499,17 @@ impl NodeMachine {
             }) => NodeStateInfo {
                 state: NodeState::Draining(*cause),
                 hostname: node.hostname.clone(),
+                location,
+                server_type,
+            },
+            NodeMachine::Quarantined(Data {
+                shared: Shared { node, .. },
+                state: Quarantined { reason, .. },
+            }) => NodeStateInfo {
+                state: NodeState::Quarantined(*reason),
+                hostname: node.hostname.clone(),
+                location,
+                server_type,
             },
             NodeMachine::Deprovisioned(Data {
                 shared: Shared { node, .. },
@@ -341,6 +517,8 @@ impl NodeMachine {
             }) => NodeStateInfo {
                 state: NodeState::Deprovisioned,
                 hostname: node.hostname.clone(),
+                location,
+                server_type,
             },
         };
 
@@ -370,12 +548,458 @@ impl NodeMachineProgressHandler {
     }
 }
 
-fn start_stats_streamer(shared: &Shared) -> Addr<StatsStreamer> {
+fn start_stats_streamer(shared: &Shared, node_info: &CloudNodeInfo) -> Addr<StatsStreamer> {
     info!("Start stats streamer actor");
 
     spawn_actor(StatsStreamer::new(
         shared.node.hostname.clone(),
+        node_info.private_ip_addresses.first().copied(),
         shared.node_stats_observer.clone(),
         shared.node_stats_stream_factory.clone(),
     ))
 }
+
+/// Keeps a failed node around for inspection if quarantining is enabled, otherwise the node
+/// gets de-provisioned right away
+fn quarantine_or_deprovision(
+    shared: Shared,
+    node_info: Option<CloudNodeInfo>,
+    reason: QuarantineReason,
+) -> NodeMachine {
+    if shared.config.quarantine_duration.is_some() {
+        info!(%reason, "Quarantine node");
+
+        NodeMachine::Quarantined(Data {
+            shared,
+            state: Quarantined::new(node_info, reason),
+        })
+    } else {
+        NodeMachine::Deprovisioning(Data {
+            shared,
+            state: Deprovisioning::new(node_info),
+        })
+    }
+}
+
+/// Tries the configured recovery ladder of the node group before the undiscoverable node gets
+/// quarantined or replaced
+fn recover_or_replace(shared: Shared, node_info: CloudNodeInfo) -> NodeMachine {
+    if shared.config.recovery_steps.is_empty() {
+        quarantine_or_deprovision(shared, Some(node_info), QuarantineReason::DiscoveryTimeout)
+    } else {
+        info!("Try to recover node");
+
+        NodeMachine::Recovering(Data {
+            shared,
+            state: Recovering::new(node_info),
+        })
+    }
+}
+
+/// Returns the recorded quarantine reason if the explored node is still marked as quarantined
+fn quarantine_reason(node_info: &CloudNodeInfo) -> Option<QuarantineReason> {
+    match node_info.quarantine {
+        Some(QuarantineStatus::Quarantined { reason, .. }) => Some(reason),
+        _ => None,
+    }
+}
+
+enum HookGate {
+    Open,
+    Closed,
+    Pending,
+}
+
+/// Lifecycle hooks of a requested transition; they run once in the background and the
+/// transition polls their outcome on later ticks
+#[derive(Debug)]
+struct HookRun {
+    event: NodeLifecycleEvent,
+    handle: JoinHandle<HookOutcome>,
+    outcome: Option<HookOutcome>,
+}
+
+impl HookRun {
+    fn start(shared: &Shared, payload: HookPayload) -> Self {
+        Self {
+            event: payload.event,
+            handle: LifecycleHooks::spawn(&shared.lifecycle_hooks, payload),
+            outcome: None,
+        }
+    }
+
+    /// The outcome once all blocking hooks finished
+    fn outcome(&mut self) -> Option<HookOutcome> {
+        if self.outcome.is_none() {
+            self.outcome = match (&mut self.handle).now_or_never() {
+                Some(Ok(outcome)) => Some(outcome),
+                Some(Err(e)) => {
+                    warn!(
+                        event = %self.event,
+                        error = format!("{:?}", e).as_str(),
+                        "Lifecycle hooks failed"
+                    );
+
+                    Some(HookOutcome::Delay)
+                }
+                None => None,
+            };
+        }
+
+        self.outcome
+    }
+}
+
+fn hook_payload(
+    shared: &Shared,
+    event: NodeLifecycleEvent,
+    node_info: Option<&CloudNodeInfo>,
+    cause: Option<String>,
+) -> HookPayload {
+    HookPayload {
+        event,
+        hostname: shared.node.hostname.clone(),
+        group: shared.node.group.clone(),
+        ip_addresses: node_info
+            .map(|ni| ni.ip_addresses.clone())
+            .unwrap_or_default(),
+        cause,
+    }
+}
+
+/// Checks the lifecycle hooks guarding a transition, they are started with the first check of a
+/// transition request; running or delayed hooks keep the gate pending until the configured
+/// maximum delay since the transition request was exceeded
+fn pass_hook_gate(
+    shared: &Shared,
+    hook_run: &mut Option<HookRun>,
+    payload: HookPayload,
+    requested_at: Instant,
+    vetoable: bool,
+) -> HookGate {
+    let hook_run = hook_run.get_or_insert_with(|| HookRun::start(shared, payload));
+    let event = hook_run.event;
+
+    match hook_run.outcome() {
+        Some(HookOutcome::Proceed) => HookGate::Open,
+        Some(HookOutcome::Veto) if vetoable => {
+            info!(%event, "Lifecycle hook vetoed transition");
+
+            HookGate::Closed
+        }
+        _ if Instant::now().duration_since(requested_at) >= shared.lifecycle_hooks.max_delay() => {
+            warn!(%event, "Lifecycle hooks exceeded the maximum delay; continue transition");
+
+            HookGate::Open
+        }
+        _ => HookGate::Pending,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NodeRecoveryAction;
+    use crate::node::stats::FileNodeStatsStreamFactory;
+
+    fn shared(quarantine_duration: Option<Duration>) -> Shared {
+        Shared {
+            node: Node {
+                hostname: "edge-1.example.com".to_owned(),
+                group: "edge".to_owned(),
+            },
+            node_discovery_provider: Addr::detached(),
+            cloud_provider: Addr::detached(),
+            dns_provider: Addr::detached(),
+            node_stats_observer: WeakAddr::default(),
+            node_stats_stream_factory: Box::new(FileNodeStatsStreamFactory::new(
+                "stats".to_owned(),
+                Duration::from_secs(1),
+                None,
+            )),
+            lifecycle_hooks: Arc::new(LifecycleHooks::new(None).unwrap()),
+            config: Config {
+                provisioning_timeout: Duration::from_secs(600),
+                draining_time: Duration::from_secs(600),
+                discovery_timeout: Duration::from_secs(600),
+                exploration_timeout: Duration::from_secs(600),
+                quarantine_duration,
+                deletion_timeout: Duration::from_secs(600),
+                recovery_steps: vec![],
+            },
+        }
+    }
+
+    fn node_info(quarantine: Option<QuarantineStatus>) -> CloudNodeInfo {
+        CloudNodeInfo {
+            identifier: "1".to_owned(),
+            hostname: "edge-1.example.com".to_owned(),
+            group: "edge".to_owned(),
+            created_at: Utc::now(),
+            ip_addresses: vec![],
+            private_ip_addresses: vec![],
+            location: None,
+            server_type: None,
+            image: None,
+            quarantine,
+        }
+    }
+
+    fn quarantined(quarantined_at: DateTime<Utc>) -> Data<Quarantined> {
+        Data {
+            shared: shared(Some(Duration::from_secs(3600))),
+            state: Quarantined {
+                quarantined_at,
+                labelled_node: true,
+                deleted_dns_records: true,
+                ..Quarantined::new(Some(node_info(None)), QuarantineReason::DiscoveryTimeout)
+            },
+        }
+    }
+
+    fn awaiting_deletion(deletion_timeout: Duration) -> Data<Deprovisioning> {
+        let mut shared = shared(None);
+        shared.config.deletion_timeout = deletion_timeout;
+
+        Data {
+            shared,
+            state: Deprovisioning {
+                passed_pre_delete_hooks: true,
+                deletion_started_at: Some(Instant::now()),
+                deleted_node: true,
+                ..Deprovisioning::new(Some(node_info(None)))
+            },
+        }
+    }
+
+    fn recovering(step_timeouts: &[Duration]) -> Data<Recovering> {
+        let mut shared = shared(None);
+        shared.config.recovery_steps = step_timeouts
+            .iter()
+            .map(|&timeout| NodeRecoveryStep {
+                action: NodeRecoveryAction::Reset,
+                timeout,
+            })
+            .collect();
+
+        Data {
+            shared,
+            state: Recovering {
+                deleted_dns_records: true,
+                ..Recovering::new(node_info(None))
+            },
+        }
+    }
+
+    async fn explore(quarantine: QuarantineStatus) -> NodeMachine {
+        let data = Data {
+            shared: shared(Some(Duration::from_secs(3600))),
+            state: Initializing {},
+        };
+
+        data.handle(Some(NodeMachineEvent::ExploredNode {
+            node_info: node_info(Some(quarantine)),
+        }))
+        .await
+    }
+
+    #[test]
+    fn test_quarantine_without_duration_deprovisions_right_away() {
+        let node_machine = quarantine_or_deprovision(
+            shared(None),
+            Some(node_info(None)),
+            QuarantineReason::DiscoveryTimeout,
+        );
+
+        assert!(matches!(node_machine, NodeMachine::Deprovisioning(_)));
+    }
+
+    #[tokio::test]
+    async fn test_explored_node_continues_its_recorded_quarantine() {
+        let since = Utc::now() - chrono::Duration::minutes(30);
+
+        let node_machine = explore(QuarantineStatus::Quarantined {
+            reason: QuarantineReason::ExplorationTimeout,
+            since: Some(since),
+        })
+        .await;
+
+        match node_machine {
+            NodeMachine::Quarantined(Data { state, .. }) => {
+                assert_eq!(QuarantineReason::ExplorationTimeout, state.reason);
+                assert_eq!(since, state.quarantined_at);
+                assert!(state.labelled_node);
+            }
+            node_machine => panic!("Unexpected state {}", node_machine),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_explored_node_without_quarantine_start_gets_labelled_again() {
+        let node_machine = explore(QuarantineStatus::Quarantined {
+            reason: QuarantineReason::ExplorationTimeout,
+            since: None,
+        })
+        .await;
+
+        match node_machine {
+            NodeMachine::Quarantined(Data { state, .. }) => assert!(!state.labelled_node),
+            node_machine => panic!("Unexpected state {}", node_machine),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_is_kept_until_its_duration_passed() {
+        let node_machine = quarantined(Utc::now() - chrono::Duration::minutes(30))
+            .handle(None)
+            .await;
+
+        assert!(matches!(node_machine, NodeMachine::Quarantined(_)));
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_times_out() {
+        let node_machine = quarantined(Utc::now() - chrono::Duration::minutes(90))
+            .handle(None)
+            .await;
+
+        assert!(matches!(node_machine, NodeMachine::Deprovisioning(_)));
+    }
+
+    #[tokio::test]
+    async fn test_released_node_leaves_quarantine() {
+        let node_machine = quarantined(Utc::now())
+            .handle(Some(NodeMachineEvent::ExploredNode {
+                node_info: node_info(Some(QuarantineStatus::Released)),
+            }))
+            .await;
+
+        assert!(matches!(node_machine, NodeMachine::Deprovisioning(_)));
+    }
+
+    #[tokio::test]
+    async fn test_deletion_is_retried_after_its_timeout() {
+        let node_machine = awaiting_deletion(Duration::from_secs(0)).handle(None).await;
+
+        match node_machine {
+            NodeMachine::Deprovisioning(Data { state, .. }) => {
+                assert!(!state.deleted_node);
+                assert!(!state.verified_deletion);
+                assert!(state.deletion_started_at.is_none());
+                assert!(state.last_verification_attempt.is_none());
+            }
+            node_machine => panic!("Unexpected state {}", node_machine),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deletion_verification_is_throttled() {
+        let mut data = awaiting_deletion(Duration::from_secs(600));
+        let attempted_at = Instant::now();
+        data.state.last_verification_attempt = Some(attempted_at);
+
+        match data.handle(None).await {
+            NodeMachine::Deprovisioning(Data { state, .. }) => {
+                assert!(state.deleted_node);
+                assert!(!state.verified_deletion);
+                assert_eq!(Some(attempted_at), state.last_verification_attempt);
+            }
+            node_machine => panic!("Unexpected state {}", node_machine),
+        }
+    }
+
+    #[test]
+    fn test_node_without_recovery_steps_gets_replaced() {
+        let node_machine = recover_or_replace(shared(None), node_info(None));
+
+        assert!(matches!(node_machine, NodeMachine::Deprovisioning(_)));
+    }
+
+    #[test]
+    fn test_node_with_recovery_steps_gets_recovered() {
+        let Data { shared, .. } = recovering(&[Duration::from_secs(600)]);
+
+        let node_machine = recover_or_replace(shared, node_info(None));
+
+        assert!(matches!(node_machine, NodeMachine::Recovering(_)));
+    }
+
+    #[tokio::test]
+    async fn test_recovery_step_times_out() {
+        let mut data = recovering(&[Duration::from_secs(0), Duration::from_secs(600)]);
+        data.state.ran_action = true;
+
+        match data.handle(None).await {
+            NodeMachine::Recovering(Data { state, .. }) => {
+                assert_eq!(1, state.step);
+                assert!(!state.ran_action);
+            }
+            node_machine => panic!("Unexpected state {}", node_machine),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_recovery_replaces_node() {
+        let mut data = recovering(&[Duration::from_secs(600)]);
+        data.state.step = 1;
+
+        let node_machine = data.handle(None).await;
+
+        assert!(matches!(node_machine, NodeMachine::Deprovisioning(_)));
+    }
+
+    #[tokio::test]
+    async fn test_failed_recovery_action_is_throttled() {
+        let mut data = recovering(&[Duration::from_secs(600)]);
+        let attempted_at = Instant::now();
+        data.state.last_action_attempt = Some(attempted_at);
+
+        match data.handle(None).await {
+            NodeMachine::Recovering(Data { state, .. }) => {
+                assert!(!state.ran_action);
+                assert_eq!(Some(attempted_at), state.last_action_attempt);
+            }
+            node_machine => panic!("Unexpected state {}", node_machine),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rediscovered_node_is_recovered() {
+        let mut data = recovering(&[Duration::from_secs(600)]);
+        data.state.deleted_dns_records = false;
+
+        let node_machine = data
+            .handle(Some(NodeMachineEvent::DiscoveredNode {
+                discovery_data: NodeDiscoveryData {
+                    hostname: "edge-1.example.com".to_owned(),
+                    group: "edge".to_owned(),
+                    state: NodeDiscoveryState::Active,
+                    datacenter: None,
+                },
+            }))
+            .await;
+
+        assert!(matches!(node_machine, NodeMachine::Active(_)));
+    }
+
+    #[tokio::test]
+    async fn test_rediscovered_node_restores_its_dns_records_first() {
+        let node_machine = recovering(&[Duration::from_secs(600)])
+            .handle(Some(NodeMachineEvent::DiscoveredNode {
+                discovery_data: NodeDiscoveryData {
+                    hostname: "edge-1.example.com".to_owned(),
+                    group: "edge".to_owned(),
+                    state: NodeDiscoveryState::Ready,
+                    datacenter: None,
+                },
+            }))
+            .await;
+
+        match node_machine {
+            NodeMachine::Recovering(Data { state, .. }) => {
+                assert_eq!(Some(NodeDiscoveryState::Ready), state.recovered_state)
+            }
+            node_machine => panic!("Unexpected state {}", node_machine),
+        }
+    }
+}
@@ -2,7 +2,7 @@ use super::*;
 
 use crate::node::discovery::NodeDiscoveryState;
 use act_zero::call;
-use tracing::error;
+use tracing::{error, warn};
 
 impl MachineState for Active {}
 
@@ -13,10 +13,14 @@ impl Handler for Data<Active> {
             Some(NodeMachineEvent::DeprovisionNode { cause }) => {
                 info!("Deprovision node, cause {:?}", cause);
 
-                NodeMachine::Draining(Data {
-                    shared: self.shared,
-                    state: Draining::new(self.state.node_info, cause, self.state.stats_streamer),
-                })
+                Data {
+                    state: Active {
+                        drain_requested: Some((cause, Instant::now())),
+                        ..self.state
+                    },
+                    ..self
+                }
+                .drain()
             }
             Some(NodeMachineEvent::DiscoveredNode {
                 discovery_data: NodeDiscoveryData { state, .. },
@@ -32,6 +36,20 @@ impl Handler for Data<Active> {
                         },
                     })
                 }
+                _ if self.state.marked_as_active => {
+                    warn!(
+                        state = format!("{:?}", state).as_str(),
+                        "State update of active node got lost; marking as active again"
+                    );
+
+                    NodeMachine::Active(Data {
+                        shared: self.shared,
+                        state: Active {
+                            marked_as_active: false,
+                            ..self.state
+                        },
+                    })
+                }
                 _ => {
                     info!(
                         state = format!("{:?}", state).as_str(),
@@ -44,11 +62,9 @@ impl Handler for Data<Active> {
             _ if self.reached_discovery_timeout() => {
                 info!("Reached node discovery timeout");
 
-                NodeMachine::Deprovisioning(Data {
-                    shared: self.shared,
-                    state: Deprovisioning::new(Some(self.state.node_info)),
-                })
+                recover_or_replace(self.shared, self.state.node_info)
             }
+            _ if self.state.drain_requested.is_some() => self.drain(),
             _ if !self.state.marked_as_active => self.mark_as_active().await,
             _ if self.state.stats_streamer.is_none() => self.start_stats_streamer(),
             _ => NodeMachine::Active(self),
@@ -60,10 +76,10 @@ impl Data<Active> {
     async fn mark_as_active(mut self) -> NodeMachine {
         info!("Mark node as active");
 
-        let update_state_result = call!(self.shared.node_discovery_provider.update_state(
-            self.shared.node.hostname.clone(),
-            NodeDiscoveryState::Active
-        ))
+        let update_state_result = call!(self
+            .shared
+            .node_discovery_provider
+            .update_state(self.shared.node.clone(), NodeDiscoveryState::Active))
         .await;
 
         if let Err(e) = update_state_result {
@@ -75,6 +91,42 @@ impl Data<Active> {
         NodeMachine::Active(self)
     }
 
+    fn drain(mut self) -> NodeMachine {
+        let (cause, requested_at) = match self.state.drain_requested {
+            Some(v) => v,
+            None => return NodeMachine::Active(self),
+        };
+
+        let payload = hook_payload(
+            &self.shared,
+            NodeLifecycleEvent::PreDrain,
+            Some(&self.state.node_info),
+            Some(cause.to_string()),
+        );
+
+        let gate = pass_hook_gate(
+            &self.shared,
+            &mut self.state.pre_drain_hooks,
+            payload,
+            requested_at,
+            true,
+        );
+
+        match gate {
+            HookGate::Open => NodeMachine::Draining(Data {
+                shared: self.shared,
+                state: Draining::new(self.state.node_info, cause, self.state.stats_streamer),
+            }),
+            HookGate::Closed => {
+                self.state.drain_requested = None;
+                self.state.pre_drain_hooks = None;
+
+                NodeMachine::Active(self)
+            }
+            HookGate::Pending => NodeMachine::Active(self),
+        }
+    }
+
     fn reached_discovery_timeout(&self) -> bool {
         let cmp_instant = match self.state.last_discovered_at {
             Some(v) => v,
@@ -85,7 +137,7 @@ impl Data<Active> {
     }
 
     fn start_stats_streamer(mut self) -> NodeMachine {
-        self.state.stats_streamer = Some(start_stats_streamer(&self.shared));
+        self.state.stats_streamer = Some(start_stats_streamer(&self.shared, &self.state.node_info));
 
         NodeMachine::Active(self)
     }
@@ -2,7 +2,10 @@ use super::*;
 
 use act_zero::call;
 use async_trait::async_trait;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+/// Minimum delay between two checks whether the cloud provider removed the node
+const DELETION_VERIFICATION_INTERVAL: Duration = Duration::from_secs(5);
 
 impl MachineState for Deprovisioning {}
 
@@ -10,13 +13,37 @@ impl MachineState for Deprovisioning {}
 impl Handler for Data<Deprovisioning> {
     async fn handle(self, _event: Option<NodeMachineEvent>) -> NodeMachine {
         if let (Some(_), false) = (self.state.node_info.as_ref(), self.state.deleted_node) {
-            return self.delete_node().await;
+            return if self.state.passed_pre_delete_hooks {
+                self.delete_node().await
+            } else {
+                self.run_pre_delete_hooks()
+            };
+        }
+
+        if let (Some(_), false) = (self.state.node_info.as_ref(), self.state.verified_deletion) {
+            return if self.reached_deletion_timeout() {
+                self.escalate_failed_deletion()
+            } else {
+                self.verify_deletion().await
+            };
         }
 
         if !self.state.deleted_dns_records {
             return self.delete_dns_records().await;
         }
 
+        if !self.state.deregistered_node {
+            return self.deregister_node().await;
+        }
+
+        let payload = hook_payload(
+            &self.shared,
+            NodeLifecycleEvent::PostDeprovision,
+            self.state.node_info.as_ref(),
+            None,
+        );
+        LifecycleHooks::spawn(&self.shared.lifecycle_hooks, payload);
+
         NodeMachine::Deprovisioned(Data {
             shared: self.shared,
             state: Deprovisioned,
@@ -25,6 +52,27 @@ impl Handler for Data<Deprovisioning> {
 }
 
 impl Data<Deprovisioning> {
+    fn run_pre_delete_hooks(mut self) -> NodeMachine {
+        let payload = hook_payload(
+            &self.shared,
+            NodeLifecycleEvent::PreDelete,
+            self.state.node_info.as_ref(),
+            None,
+        );
+
+        // a node that is already de-provisioning can only be delayed, not kept
+        let gate = pass_hook_gate(
+            &self.shared,
+            &mut self.state.pre_delete_hooks,
+            payload,
+            self.state.entered_state_at,
+            false,
+        );
+        self.state.passed_pre_delete_hooks = matches!(gate, HookGate::Open);
+
+        NodeMachine::Deprovisioning(self)
+    }
+
     async fn delete_node(self) -> NodeMachine {
         info!("Delete node {:?}", self.state.node_info);
 
@@ -45,6 +93,94 @@ impl Data<Deprovisioning> {
         NodeMachine::Deprovisioning(Data {
             state: Deprovisioning {
                 deleted_node,
+                deletion_started_at: self
+                    .state
+                    .deletion_started_at
+                    .or_else(|| Some(Instant::now())),
+                ..self.state
+            },
+            ..self
+        })
+    }
+
+    fn reached_deletion_timeout(&self) -> bool {
+        self.state
+            .deletion_started_at
+            .map(|started_at| {
+                Instant::now().duration_since(started_at) >= self.shared.config.deletion_timeout
+            })
+            .unwrap_or(false)
+    }
+
+    async fn verify_deletion(self) -> NodeMachine {
+        let verified_recently = self
+            .state
+            .last_verification_attempt
+            .map(|attempted_at| {
+                Instant::now().duration_since(attempted_at) < DELETION_VERIFICATION_INTERVAL
+            })
+            .unwrap_or(false);
+
+        if verified_recently {
+            return NodeMachine::Deprovisioning(self);
+        }
+
+        let node_info = self.state.node_info.clone().unwrap();
+
+        let result = call!(self.shared.cloud_provider.node_exists(node_info)).await;
+
+        let verified_deletion = match result {
+            Ok(false) => {
+                info!("Verified node deletion");
+
+                true
+            }
+            Ok(true) => {
+                info!(
+                    "Node still exists, waiting for its deletion {:?}",
+                    self.state.node_info
+                );
+
+                false
+            }
+            Err(e) => {
+                warn!("Failed verifying node deletion {:?}", e);
+
+                false
+            }
+        };
+
+        NodeMachine::Deprovisioning(Data {
+            state: Deprovisioning {
+                verified_deletion,
+                last_verification_attempt: Some(Instant::now()),
+                ..self.state
+            },
+            ..self
+        })
+    }
+
+    /// The cloud provider didn't remove the node in time; alert via the deletion-failed hooks
+    /// and start over with the deletion instead of forgetting the node.
+    fn escalate_failed_deletion(self) -> NodeMachine {
+        error!(
+            timeout_ms = self.shared.config.deletion_timeout.as_millis() as u64,
+            "Node wasn't deleted in time; retrying deletion {:?}", self.state.node_info
+        );
+
+        let payload = hook_payload(
+            &self.shared,
+            NodeLifecycleEvent::DeletionFailed,
+            self.state.node_info.as_ref(),
+            None,
+        );
+        LifecycleHooks::spawn(&self.shared.lifecycle_hooks, payload);
+
+        NodeMachine::Deprovisioning(Data {
+            state: Deprovisioning {
+                deletion_started_at: None,
+                deleted_node: false,
+                last_verification_attempt: None,
                 ..self.state
             },
             ..self
@@ -77,4 +213,31 @@ impl Data<Deprovisioning> {
             ..self
         })
     }
+
+    async fn deregister_node(self) -> NodeMachine {
+        info!("Deregister node");
+
+        let result_fut = call!(self
+            .shared
+            .node_discovery_provider
+            .deregister_node(self.shared.node.hostname.clone()));
+        let result = result_fut.await;
+
+        let deregistered_node = match result {
+            Ok(_) => true,
+            Err(e) => {
+                error!("Failed deregistering node {:?}", e);
+
+                false
+            }
+        };
+
+        NodeMachine::Deprovisioning(Data {
+            state: Deprovisioning {
+                deregistered_node,
+                ..self.state
+            },
+            ..self
+        })
+    }
 }
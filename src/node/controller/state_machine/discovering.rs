@@ -37,10 +37,11 @@ impl Handler for Data<Discovering> {
             _ if self.reached_discovery_timeout() => {
                 info!("Node reached discovery timeout {:?}", self.state.node_info);
 
-                NodeMachine::Deprovisioning(Data {
-                    shared: self.shared,
-                    state: Deprovisioning::new(Some(self.state.node_info)),
-                })
+                quarantine_or_deprovision(
+                    self.shared,
+                    Some(self.state.node_info),
+                    QuarantineReason::DiscoveryTimeout,
+                )
             }
             _ => NodeMachine::Discovering(self),
         }
@@ -1,7 +1,7 @@
 use super::*;
 use crate::node::discovery::NodeDiscoveryState;
 use act_zero::call;
-use tracing::error;
+use tracing::{error, warn};
 
 impl MachineState for Draining {}
 
@@ -28,11 +28,38 @@ impl Handler for Data<Draining> {
             (Some(NodeMachineEvent::ActivateNode), NodeDrainingCause::Scaling) => {
                 info!("Re-activate draining node");
 
-                NodeMachine::Active(Data {
-                    shared: self.shared,
-                    state: Active::new(self.state.node_info, self.state.stats_streamer),
+                Data {
+                    state: Draining {
+                        activation_requested_at: self
+                            .state
+                            .activation_requested_at
+                            .or_else(|| Some(Instant::now())),
+                        ..self.state
+                    },
+                    ..self
+                }
+                .reactivate()
+            }
+            (
+                Some(NodeMachineEvent::DiscoveredNode {
+                    discovery_data: NodeDiscoveryData { state, .. },
+                }),
+                cause,
+            ) if self.state.marked_as_draining && state != NodeDiscoveryState::Draining(*cause) => {
+                warn!(
+                    state = format!("{:?}", state).as_str(),
+                    "State update of draining node got lost; marking as draining again"
+                );
+
+                NodeMachine::Draining(Data {
+                    state: Draining {
+                        marked_as_draining: false,
+                        ..self.state
+                    },
+                    ..self
                 })
             }
+            (None, _) if self.state.activation_requested_at.is_some() => self.reactivate(),
             _ if self.state.stats_streamer.is_none() => self.start_stats_streamer(),
             _ if !self.state.marked_as_draining => self.mark_as_draining().await,
             _ => NodeMachine::Draining(self),
@@ -46,11 +73,47 @@ impl Data<Draining> {
             >= self.shared.config.draining_time
     }
 
+    fn reactivate(mut self) -> NodeMachine {
+        let requested_at = self
+            .state
+            .activation_requested_at
+            .unwrap_or_else(Instant::now);
+
+        let payload = hook_payload(
+            &self.shared,
+            NodeLifecycleEvent::PreActivate,
+            Some(&self.state.node_info),
+            None,
+        );
+
+        let gate = pass_hook_gate(
+            &self.shared,
+            &mut self.state.pre_activate_hooks,
+            payload,
+            requested_at,
+            true,
+        );
+
+        match gate {
+            HookGate::Open => NodeMachine::Active(Data {
+                shared: self.shared,
+                state: Active::new(self.state.node_info, self.state.stats_streamer),
+            }),
+            HookGate::Closed => {
+                self.state.activation_requested_at = None;
+                self.state.pre_activate_hooks = None;
+
+                NodeMachine::Draining(self)
+            }
+            HookGate::Pending => NodeMachine::Draining(self),
+        }
+    }
+
     async fn mark_as_draining(mut self) -> NodeMachine {
         info!("Mark node as draining");
 
         let update_state_result = call!(self.shared.node_discovery_provider.update_state(
-            self.shared.node.hostname.clone(),
+            self.shared.node.clone(),
             NodeDiscoveryState::Draining(self.state.cause)
         ))
         .await;
@@ -65,7 +128,7 @@ impl Data<Draining> {
     }
 
     fn start_stats_streamer(mut self) -> NodeMachine {
-        self.state.stats_streamer = Some(start_stats_streamer(&self.shared));
+        self.state.stats_streamer = Some(start_stats_streamer(&self.shared, &self.state.node_info));
 
         NodeMachine::Draining(self)
     }
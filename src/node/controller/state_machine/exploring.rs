@@ -26,10 +26,7 @@ impl Handler for Data<Exploring> {
             _ if self.reached_exploration_timeout() => {
                 info!("Node reached exploration timeout");
 
-                NodeMachine::Deprovisioning(Data {
-                    shared: self.shared,
-                    state: Deprovisioning::new(None),
-                })
+                quarantine_or_deprovision(self.shared, None, QuarantineReason::ExplorationTimeout)
             }
             None => self.explore_node_info().await,
             _ => NodeMachine::Exploring(self),
@@ -64,6 +61,15 @@ impl Data<Exploring> {
     }
 
     fn explored_node(self, node_info: CloudNodeInfo) -> NodeMachine {
+        if let Some(reason) = quarantine_reason(&node_info) {
+            info!(%reason, "Explored node is quarantined");
+
+            return NodeMachine::Quarantined(Data {
+                shared: self.shared,
+                state: Quarantined::new_labelled(node_info, reason),
+            });
+        }
+
         match self.state.discovery_data.state {
             NodeDiscoveryState::Ready => NodeMachine::Ready(Data {
                 shared: self.shared,
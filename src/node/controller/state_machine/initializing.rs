@@ -6,10 +6,10 @@ impl MachineState for Initializing {}
 impl Handler for Data<Initializing> {
     async fn handle(self, event: Option<NodeMachineEvent>) -> NodeMachine {
         match event {
-            Some(NodeMachineEvent::ProvisionNode { target_state }) => {
+            Some(NodeMachineEvent::ProvisionNode { target_state, spec }) => {
                 NodeMachine::Provisioning(Data {
                     shared: self.shared,
-                    state: Provisioning::new(target_state),
+                    state: Provisioning::new(target_state, spec),
                 })
             }
             Some(NodeMachineEvent::DiscoveredNode { discovery_data }) => {
@@ -18,10 +18,18 @@ impl Handler for Data<Initializing> {
                     state: Exploring::new(discovery_data),
                 })
             }
-            Some(NodeMachineEvent::ExploredNode { node_info }) => NodeMachine::Discovering(Data {
-                shared: self.shared,
-                state: Discovering::new(node_info),
-            }),
+            Some(NodeMachineEvent::ExploredNode { node_info }) => {
+                match quarantine_reason(&node_info) {
+                    Some(reason) => NodeMachine::Quarantined(Data {
+                        shared: self.shared,
+                        state: Quarantined::new_labelled(node_info, reason),
+                    }),
+                    None => NodeMachine::Discovering(Data {
+                        shared: self.shared,
+                        state: Discovering::new(node_info),
+                    }),
+                }
+            }
             _ => NodeMachine::Initializing(self),
         }
     }
@@ -1,9 +1,13 @@
 use super::*;
+use crate::cloud_provider::ProvisioningStatus;
 use crate::node::discovery::NodeDiscoveryState;
 use act_zero::call;
 use async_trait::async_trait;
 use tracing::{error, info};
 
+/// Minimum delay between two checks whether the created node finished booting
+const COMPLETION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
 impl MachineState for Provisioning {}
 
 #[async_trait]
@@ -39,10 +43,18 @@ impl Handler for Data<Provisioning> {
                         shared: self.shared,
                         state: Active::new(node_info, None),
                     }),
-                    _ => NodeMachine::Deprovisioning(Data {
-                        shared: self.shared,
-                        state: Deprovisioning::new(Some(node_info)),
-                    }),
+                    _ => {
+                        info!(
+                            state = format!("{:?}", discovery_data.state).as_str(),
+                            "Discovered provisioned node in unexpected state"
+                        );
+
+                        quarantine_or_deprovision(
+                            self.shared,
+                            Some(node_info),
+                            QuarantineReason::UnexpectedState,
+                        )
+                    }
                 }
             }
             _ => NodeMachine::Provisioning(self),
@@ -61,7 +73,10 @@ impl Data<Provisioning> {
 
         match self.state.node_info.as_ref() {
             None => self.create_node().await,
+            Some(_) if !self.state.completed_node => self.check_completion().await,
             Some(_) if !self.state.created_dns_records => self.create_dns_records().await,
+            Some(_) if !self.state.created_ptr_records => self.create_ptr_records().await,
+            Some(_) if !self.state.registered_node => self.register_node().await,
             _ => NodeMachine::Provisioning(self),
         }
     }
@@ -72,7 +87,8 @@ impl Data<Provisioning> {
         let create_node_result = call!(self.shared.cloud_provider.create_node(
             self.shared.node.hostname.clone(),
             self.shared.node.group.clone(),
-            self.state.target_state.clone()
+            self.state.target_state.clone(),
+            self.state.spec.clone()
         ))
         .await;
 
@@ -89,6 +105,57 @@ impl Data<Provisioning> {
         })
     }
 
+    async fn check_completion(self) -> NodeMachine {
+        let checked_recently = self
+            .state
+            .last_completion_check
+            .map_or(false, |checked_at| {
+                checked_at.elapsed() < COMPLETION_CHECK_INTERVAL
+            });
+
+        if checked_recently {
+            return NodeMachine::Provisioning(self);
+        }
+
+        let node_info = self.state.node_info.clone().unwrap();
+
+        let status_result = call!(self
+            .shared
+            .cloud_provider
+            .get_provisioning_status(node_info))
+        .await;
+
+        let completed_node = match status_result {
+            Ok(ProvisioningStatus::Completed) => {
+                info!("Node is up and running");
+                true
+            }
+            Ok(ProvisioningStatus::Pending) => false,
+            Ok(ProvisioningStatus::Failed(reason)) => {
+                error!(%reason, "Node failed to come up");
+
+                return quarantine_or_deprovision(
+                    self.shared,
+                    self.state.node_info,
+                    QuarantineReason::ProvisioningFailed,
+                );
+            }
+            Err(e) => {
+                error!("Failed to get provisioning status {:?}", e);
+                false
+            }
+        };
+
+        NodeMachine::Provisioning(Data {
+            state: Provisioning {
+                completed_node,
+                last_completion_check: Some(Instant::now()),
+                ..self.state
+            },
+            ..self
+        })
+    }
+
     async fn create_dns_records(self) -> NodeMachine {
         let node_info = self.state.node_info.as_ref().unwrap();
 
@@ -118,4 +185,49 @@ impl Data<Provisioning> {
             ..self
         })
     }
+
+    async fn create_ptr_records(self) -> NodeMachine {
+        info!("Create ptr records via CloudProvider");
+
+        let node_info = self.state.node_info.clone().unwrap();
+
+        let create_records_result =
+            call!(self.shared.cloud_provider.create_ptr_records(node_info)).await;
+
+        if let Err(e) = create_records_result.as_ref() {
+            error!("Failed to create ptr records {:?}", e);
+        }
+
+        NodeMachine::Provisioning(Data {
+            state: Provisioning {
+                created_ptr_records: create_records_result.is_ok(),
+                ..self.state
+            },
+            ..self
+        })
+    }
+
+    async fn register_node(self) -> NodeMachine {
+        info!("Register node via NodeDiscoveryProvider");
+
+        let node_info = self.state.node_info.clone().unwrap();
+
+        let register_result = call!(self
+            .shared
+            .node_discovery_provider
+            .register_node(node_info, self.state.target_state.clone()))
+        .await;
+
+        if let Err(e) = register_result.as_ref() {
+            error!("Failed to register node {:?}", e);
+        }
+
+        NodeMachine::Provisioning(Data {
+            state: Provisioning {
+                registered_node: register_result.is_ok(),
+                ..self.state
+            },
+            ..self
+        })
+    }
 }
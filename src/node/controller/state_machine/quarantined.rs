@@ -0,0 +1,124 @@
+use super::*;
+
+use act_zero::call;
+use async_trait::async_trait;
+use tracing::{error, info};
+
+impl MachineState for Quarantined {}
+
+#[async_trait]
+impl Handler for Data<Quarantined> {
+    async fn handle(self, event: Option<NodeMachineEvent>) -> NodeMachine {
+        match event {
+            Some(NodeMachineEvent::DeprovisionNode { cause }) => {
+                info!("Release quarantined node, cause {:?}", cause);
+
+                self.release()
+            }
+            Some(NodeMachineEvent::ExploredNode { node_info })
+                if node_info.quarantine == Some(QuarantineStatus::Released) =>
+            {
+                info!("Quarantined node was released by an operator");
+
+                self.release()
+            }
+            _ if self.reached_quarantine_time() => {
+                info!("Reached quarantine time of node; start de-provisioning");
+
+                self.release()
+            }
+            _ if !self.state.deleted_dns_records => self.delete_dns_records().await,
+            _ if !self.state.labelled_node => self.label_node().await,
+            _ => NodeMachine::Quarantined(self),
+        }
+    }
+}
+
+impl Data<Quarantined> {
+    fn reached_quarantine_time(&self) -> bool {
+        match self.shared.config.quarantine_duration {
+            Some(duration) => Utc::now()
+                .signed_duration_since(self.state.quarantined_at)
+                .to_std()
+                .map_or(false, |elapsed| elapsed >= duration),
+            None => true,
+        }
+    }
+
+    fn release(self) -> NodeMachine {
+        NodeMachine::Deprovisioning(Data {
+            shared: self.shared,
+            state: Deprovisioning::new(self.state.node_info),
+        })
+    }
+
+    async fn delete_dns_records(self) -> NodeMachine {
+        info!("Delete dns records of quarantined node");
+
+        let result = call!(self
+            .shared
+            .dns_provider
+            .delete_records(self.shared.node.hostname.clone()))
+        .await;
+
+        let deleted_dns_records = match result {
+            Ok(_) => true,
+            Err(e) => {
+                error!("Failed deleting dns records {:?}", e);
+
+                false
+            }
+        };
+
+        NodeMachine::Quarantined(Data {
+            state: Quarantined {
+                deleted_dns_records,
+                ..self.state
+            },
+            ..self
+        })
+    }
+
+    async fn label_node(self) -> NodeMachine {
+        let node_info = match self.state.node_info.clone() {
+            Some(v) => v,
+            None => {
+                info!("Missing node info, skip labelling quarantined node");
+
+                return NodeMachine::Quarantined(Data {
+                    state: Quarantined {
+                        labelled_node: true,
+                        ..self.state
+                    },
+                    ..self
+                });
+            }
+        };
+
+        info!(reason = %self.state.reason, "Label quarantined node");
+
+        let result = call!(self.shared.cloud_provider.quarantine_node(
+            node_info,
+            self.state.reason,
+            self.state.quarantined_at
+        ))
+        .await;
+
+        let labelled_node = match result {
+            Ok(_) => true,
+            Err(e) => {
+                error!("Failed labelling quarantined node {:?}", e);
+
+                false
+            }
+        };
+
+        NodeMachine::Quarantined(Data {
+            state: Quarantined {
+                labelled_node,
+                ..self.state
+            },
+            ..self
+        })
+    }
+}
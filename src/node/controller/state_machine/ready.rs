@@ -1,7 +1,7 @@
 use super::*;
 use crate::node::discovery::NodeDiscoveryState;
 use act_zero::call;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 impl MachineState for Ready {}
 
@@ -12,10 +12,17 @@ impl Handler for Data<Ready> {
             Some(NodeMachineEvent::ActivateNode) => {
                 info!("Activate Node");
 
-                NodeMachine::Active(Data {
-                    shared: self.shared,
-                    state: Active::new(self.state.node_info, self.state.stats_streamer),
-                })
+                Data {
+                    state: Ready {
+                        activation_requested_at: self
+                            .state
+                            .activation_requested_at
+                            .or_else(|| Some(Instant::now())),
+                        ..self.state
+                    },
+                    ..self
+                }
+                .activate()
             }
             Some(NodeMachineEvent::DiscoveredNode {
                 discovery_data: NodeDiscoveryData { state, .. },
@@ -30,6 +37,20 @@ impl Handler for Data<Ready> {
                         },
                     })
                 }
+                _ if self.state.marked_as_ready => {
+                    warn!(
+                        state = format!("{:?}", state).as_str(),
+                        "State update of ready node got lost; marking as ready again"
+                    );
+
+                    NodeMachine::Ready(Data {
+                        shared: self.shared,
+                        state: Ready {
+                            marked_as_ready: false,
+                            ..self.state
+                        },
+                    })
+                }
                 _ => {
                     info!(
                         state = format!("{:?}", state).as_str(),
@@ -50,13 +71,12 @@ impl Handler for Data<Ready> {
             _ if self.reached_discovery_timeout() => {
                 info!("Reached node discovery timeout");
 
-                NodeMachine::Deprovisioning(Data {
-                    shared: self.shared,
-                    state: Deprovisioning::new(Some(self.state.node_info)),
-                })
+                recover_or_replace(self.shared, self.state.node_info)
             }
+            _ if self.state.activation_requested_at.is_some() => self.activate(),
             _ if self.state.stats_streamer.is_none() => self.start_stats_streamer(),
             _ if !self.state.marked_as_ready => self.mark_as_ready().await,
+            _ if !self.state.ran_post_ready_hooks => self.run_post_ready_hooks(),
             _ => NodeMachine::Ready(self),
         }
     }
@@ -72,8 +92,58 @@ impl Data<Ready> {
         Instant::now().duration_since(cmp_instant) >= self.shared.config.discovery_timeout
     }
 
+    fn activate(mut self) -> NodeMachine {
+        let requested_at = self
+            .state
+            .activation_requested_at
+            .unwrap_or_else(Instant::now);
+
+        let payload = hook_payload(
+            &self.shared,
+            NodeLifecycleEvent::PreActivate,
+            Some(&self.state.node_info),
+            None,
+        );
+
+        let gate = pass_hook_gate(
+            &self.shared,
+            &mut self.state.pre_activate_hooks,
+            payload,
+            requested_at,
+            true,
+        );
+
+        match gate {
+            HookGate::Open => NodeMachine::Active(Data {
+                shared: self.shared,
+                state: Active::new(self.state.node_info, self.state.stats_streamer),
+            }),
+            HookGate::Closed => {
+                self.state.activation_requested_at = None;
+                self.state.pre_activate_hooks = None;
+
+                NodeMachine::Ready(self)
+            }
+            HookGate::Pending => NodeMachine::Ready(self),
+        }
+    }
+
+    fn run_post_ready_hooks(mut self) -> NodeMachine {
+        let payload = hook_payload(
+            &self.shared,
+            NodeLifecycleEvent::PostReady,
+            Some(&self.state.node_info),
+            None,
+        );
+
+        LifecycleHooks::spawn(&self.shared.lifecycle_hooks, payload);
+        self.state.ran_post_ready_hooks = true;
+
+        NodeMachine::Ready(self)
+    }
+
     fn start_stats_streamer(mut self) -> NodeMachine {
-        self.state.stats_streamer = Some(start_stats_streamer(&self.shared));
+        self.state.stats_streamer = Some(start_stats_streamer(&self.shared, &self.state.node_info));
 
         NodeMachine::Ready(self)
     }
@@ -84,7 +154,7 @@ impl Data<Ready> {
         let update_state_result = call!(self
             .shared
             .node_discovery_provider
-            .update_state(self.shared.node.hostname.clone(), NodeDiscoveryState::Ready))
+            .update_state(self.shared.node.clone(), NodeDiscoveryState::Ready))
         .await;
 
         if let Err(e) = update_state_result {
@@ -0,0 +1,219 @@
+use super::*;
+
+use act_zero::call;
+use tracing::error;
+
+/// Minimum delay between two attempts to run a failed recovery action
+const ACTION_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+impl MachineState for Recovering {}
+
+#[async_trait]
+impl Handler for Data<Recovering> {
+    async fn handle(self, event: Option<NodeMachineEvent>) -> NodeMachine {
+        match event {
+            Some(NodeMachineEvent::DeprovisionNode { cause }) => {
+                info!("De-provision recovering node, cause {:?}", cause);
+
+                NodeMachine::Deprovisioning(Data {
+                    shared: self.shared,
+                    state: Deprovisioning::new(Some(self.state.node_info)),
+                })
+            }
+            Some(NodeMachineEvent::DiscoveredNode {
+                discovery_data: NodeDiscoveryData { state, .. },
+            }) => match state {
+                NodeDiscoveryState::Active | NodeDiscoveryState::Ready
+                    if self.state.deleted_dns_records =>
+                {
+                    info!(
+                        state = format!("{:?}", state).as_str(),
+                        "Recovered node, restoring its dns records"
+                    );
+
+                    NodeMachine::Recovering(Data {
+                        state: Recovering {
+                            recovered_state: Some(state),
+                            ..self.state
+                        },
+                        ..self
+                    })
+                }
+                NodeDiscoveryState::Active | NodeDiscoveryState::Ready => {
+                    self.complete_recovery(state)
+                }
+                _ => {
+                    info!(
+                        state = format!("{:?}", state).as_str(),
+                        "Discovered recovering node in unexpected state"
+                    );
+
+                    NodeMachine::Recovering(self)
+                }
+            },
+            _ if self.state.recovered_state.is_some() => self.restore_dns_records().await,
+            _ if self.current_step().is_none() => {
+                info!("Exhausted the recovery steps of the node");
+
+                quarantine_or_deprovision(
+                    self.shared,
+                    Some(self.state.node_info),
+                    QuarantineReason::DiscoveryTimeout,
+                )
+            }
+            _ if self.reached_step_timeout() => {
+                info!(step = self.state.step, "Reached recovery step timeout");
+
+                NodeMachine::Recovering(Data {
+                    state: Recovering {
+                        step: self.state.step + 1,
+                        entered_step_at: Instant::now(),
+                        ran_action: false,
+                        last_action_attempt: None,
+                        ..self.state
+                    },
+                    ..self
+                })
+            }
+            _ if !self.state.deleted_dns_records => self.delete_dns_records().await,
+            _ if !self.state.ran_action => self.run_action().await,
+            _ => NodeMachine::Recovering(self),
+        }
+    }
+}
+
+impl Data<Recovering> {
+    fn current_step(&self) -> Option<&NodeRecoveryStep> {
+        self.shared.config.recovery_steps.get(self.state.step)
+    }
+
+    fn reached_step_timeout(&self) -> bool {
+        match self.current_step() {
+            Some(step) => Instant::now().duration_since(self.state.entered_step_at) >= step.timeout,
+            None => true,
+        }
+    }
+
+    fn complete_recovery(self, state: NodeDiscoveryState) -> NodeMachine {
+        match state {
+            NodeDiscoveryState::Active => {
+                info!("Recovered active node");
+
+                NodeMachine::Active(Data {
+                    shared: self.shared,
+                    state: Active::new_marked(self.state.node_info, None),
+                })
+            }
+            _ => {
+                info!("Recovered ready node");
+
+                NodeMachine::Ready(Data {
+                    shared: self.shared,
+                    state: Ready::new(self.state.node_info, None),
+                })
+            }
+        }
+    }
+
+    async fn delete_dns_records(self) -> NodeMachine {
+        info!("Delete dns records of recovering node");
+
+        let result = call!(self
+            .shared
+            .dns_provider
+            .delete_records(self.shared.node.hostname.clone()))
+        .await;
+
+        let deleted_dns_records = match result {
+            Ok(_) => true,
+            Err(e) => {
+                error!("Failed deleting dns records {:?}", e);
+
+                false
+            }
+        };
+
+        NodeMachine::Recovering(Data {
+            state: Recovering {
+                deleted_dns_records,
+                ..self.state
+            },
+            ..self
+        })
+    }
+
+    async fn restore_dns_records(mut self) -> NodeMachine {
+        info!("Restore dns records of recovered node");
+
+        let result = call!(self.shared.dns_provider.create_records(
+            self.shared.node.hostname.clone(),
+            self.state.node_info.ip_addresses.clone()
+        ))
+        .await;
+
+        match result {
+            Ok(_) => {
+                let state = self.state.recovered_state.take().unwrap();
+
+                self.complete_recovery(state)
+            }
+            Err(e) => {
+                error!("Failed restoring dns records {:?}", e);
+
+                NodeMachine::Recovering(self)
+            }
+        }
+    }
+
+    async fn run_action(self) -> NodeMachine {
+        let attempted_recently = self
+            .state
+            .last_action_attempt
+            .map(|attempted_at| Instant::now().duration_since(attempted_at) < ACTION_RETRY_INTERVAL)
+            .unwrap_or(false);
+
+        if attempted_recently {
+            return NodeMachine::Recovering(self);
+        }
+
+        let action = match self.current_step() {
+            Some(step) => step.action,
+            None => return NodeMachine::Recovering(self),
+        };
+
+        info!(
+            step = self.state.step,
+            action = format!("{:?}", action).as_str(),
+            "Run recovery action"
+        );
+
+        let result = call!(self
+            .shared
+            .cloud_provider
+            .recover_node(self.state.node_info.clone(), action))
+        .await;
+
+        match result {
+            // the step timeout starts once the action went through
+            Ok(_) => NodeMachine::Recovering(Data {
+                state: Recovering {
+                    ran_action: true,
+                    entered_step_at: Instant::now(),
+                    ..self.state
+                },
+                ..self
+            }),
+            Err(e) => {
+                error!("Failed running recovery action {:?}", e);
+
+                NodeMachine::Recovering(Data {
+                    state: Recovering {
+                        last_action_attempt: Some(Instant::now()),
+                        ..self.state
+                    },
+                    ..self
+                })
+            }
+        }
+    }
+}
@@ -3,11 +3,13 @@ use crate::node::stats::NodeStatsStreamFactory;
 use crate::node::{NodeStatsInfo, NodeStatsObserver};
 use act_zero::{call, Actor, ActorError, ActorResult, Addr, AddrLike, Produces, WeakAddr};
 use async_trait::async_trait;
+use std::net::IpAddr;
 use tokio::stream::StreamExt;
 use tracing::{info, trace, warn};
 
 pub struct StatsStreamer {
     hostname: String,
+    private_address: Option<IpAddr>,
     stats_observer: WeakAddr<dyn NodeStatsObserver>,
     node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
 }
@@ -27,9 +29,12 @@ impl Actor for StatsStreamer {
         addr.send_fut({
             let stats_observer = self.stats_observer.clone();
             let hostname = self.hostname.clone();
+            let private_address = self.private_address;
             let stream_factory = self.node_stats_stream_factory.clone();
 
-            async move { Self::poll_stream(stats_observer, hostname, stream_factory).await }
+            async move {
+                Self::poll_stream(stats_observer, hostname, private_address, stream_factory).await
+            }
         });
 
         Produces::ok(())
@@ -43,11 +48,13 @@ impl Actor for StatsStreamer {
 impl StatsStreamer {
     pub fn new(
         hostname: String,
+        private_address: Option<IpAddr>,
         stats_observer: WeakAddr<dyn NodeStatsObserver>,
         node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
     ) -> Self {
         Self {
             hostname,
+            private_address,
             stats_observer,
             node_stats_stream_factory,
         }
@@ -57,11 +64,13 @@ impl StatsStreamer {
     async fn poll_stream(
         addr: WeakAddr<dyn NodeStatsObserver>,
         hostname: String,
+        private_address: Option<IpAddr>,
         stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
     ) {
         loop {
             info!("Opening StatsStream");
-            let mut stats_stream = stats_stream_factory.create_stream(hostname.clone());
+            let mut stats_stream =
+                stats_stream_factory.create_stream(hostname.clone(), private_address);
 
             while let Some(stats) = stats_stream.next().await {
                 trace!("Received node stats from stream {:?}", stats);
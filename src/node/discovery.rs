@@ -19,11 +19,35 @@ pub trait NodeDiscoveryObserver: Actor {
     async fn observe_node_discovery(&mut self, data: NodeDiscoveryData);
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct NodeDiscoveryData {
     pub hostname: String,
     pub group: String,
     pub state: NodeDiscoveryState,
+    /// Datacenter the node was discovered in, for providers that span multiple datacenters
+    #[serde(default)]
+    pub datacenter: Option<String>,
+}
+
+/// Nodes of all datacenters of a provider; the nodes of datacenters that couldn't be queried are
+/// missing, so callers acting on absent nodes have to check for completeness
+#[derive(Debug, Clone, Default)]
+pub struct NodeDiscoveries {
+    pub nodes: Vec<NodeDiscoveryData>,
+    pub failed_datacenters: Vec<Option<String>>,
+}
+
+impl NodeDiscoveries {
+    pub fn complete(nodes: Vec<NodeDiscoveryData>) -> Self {
+        Self {
+            nodes,
+            failed_datacenters: vec![],
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.failed_datacenters.is_empty()
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -108,6 +132,8 @@ impl Actor for NodeDiscovery {
         self.timer
             .set_interval_weak(self.addr.clone(), self.discovery_interval);
 
+        send!(self.provider.watch_nodes(self.observer.clone()));
+
         Produces::ok(())
     }
 
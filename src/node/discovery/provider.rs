@@ -1,44 +1,119 @@
 mod consul;
+mod dns;
 mod file;
 mod mock;
+mod static_list;
 
+use crate::cloud_provider::CloudNodeInfo;
 use crate::config;
-use crate::node::discovery::{NodeDiscoveryData, NodeDiscoveryState};
+use crate::dns_provider::DnsProvider;
+use crate::node::discovery::{
+    NodeDiscoveries, NodeDiscoveryData, NodeDiscoveryObserver, NodeDiscoveryState,
+};
+use crate::node::Node;
 use crate::AppConfig;
 use act_zero::runtimes::tokio::spawn_actor;
-use act_zero::{upcast, Actor, ActorResult, Addr};
+use act_zero::{upcast, Actor, ActorResult, Addr, Produces};
+use anyhow::bail;
 use async_trait::async_trait;
 
 #[async_trait]
 pub trait NodeDiscoveryProvider: Actor {
-    async fn update_state(
-        &mut self,
-        hostname: String,
-        state: NodeDiscoveryState,
-    ) -> ActorResult<()>;
+    async fn update_state(&mut self, node: Node, state: NodeDiscoveryState) -> ActorResult<()>;
 
     async fn discover_nodes(&mut self) -> ActorResult<Vec<NodeDiscoveryData>>;
+
+    /// Discovers the nodes of every datacenter and reports the datacenters that failed, for
+    /// callers that act on nodes missing from the discovery
+    async fn discover_all_nodes(&mut self) -> ActorResult<NodeDiscoveries>;
+
+    /// Starts pushing discovery changes to the observer as soon as they happen, providers without
+    /// change notifications rely on the periodic discovery only
+    async fn watch_nodes(&mut self, _observer: Addr<dyn NodeDiscoveryObserver>) -> ActorResult<()> {
+        Produces::ok(())
+    }
+
+    /// Registers a freshly provisioned node with the given state, for providers that don't rely
+    /// on the nodes registering themselves
+    async fn register_node(
+        &mut self,
+        _node_info: CloudNodeInfo,
+        _state: NodeDiscoveryState,
+    ) -> ActorResult<()> {
+        Produces::ok(())
+    }
+
+    /// Removes the registration of a de-provisioned node again
+    async fn deregister_node(&mut self, _hostname: String) -> ActorResult<()> {
+        Produces::ok(())
+    }
 }
 
-pub fn build_from_config(config: AppConfig) -> anyhow::Result<Addr<dyn NodeDiscoveryProvider>> {
+pub fn build_from_config(
+    config: AppConfig,
+    dns_provider: Addr<dyn DnsProvider>,
+) -> anyhow::Result<Addr<dyn NodeDiscoveryProvider>> {
     Ok(match &config.node_discovery_provider {
         config::NodeDiscoveryProvider::Mock => upcast!(spawn_actor(mock::MockNodeDiscovery)),
-        config::NodeDiscoveryProvider::File { path } => {
-            upcast!(spawn_actor(file::FileNodeDiscovery::new(path)))
+        config::NodeDiscoveryProvider::File { path, watch } => {
+            upcast!(spawn_actor(file::FileNodeDiscovery::new(
+                path,
+                watch.clone()
+            )))
         }
         config::NodeDiscoveryProvider::Consul {
             service_name,
-            address,
+            connection,
+            watch,
+            registration,
         } => {
-            let consul_client = consul_api_client::Client::new(
-                consul_api_client::Config::builder()
-                    .address(address.into())
-                    .build()?,
-            )?;
+            let datacenters = if connection.datacenters.is_empty() {
+                vec![consul::ConsulDatacenter {
+                    name: None,
+                    consul: crate::consul::build_client(connection, None)?,
+                    txn: crate::consul::build_txn_client(connection, None)?,
+                }]
+            } else {
+                connection
+                    .datacenters
+                    .iter()
+                    .map(|dc| {
+                        Ok(consul::ConsulDatacenter {
+                            name: Some(dc.clone()),
+                            consul: crate::consul::build_client(connection, Some(dc))?,
+                            txn: crate::consul::build_txn_client(connection, Some(dc))?,
+                        })
+                    })
+                    .collect::<anyhow::Result<Vec<consul::ConsulDatacenter>>>()?
+            };
 
-            let provider = consul::ConsulNodeDiscovery::new(consul_client, service_name.into());
+            let provider = consul::ConsulNodeDiscovery::new(
+                datacenters,
+                service_name.into(),
+                watch.clone(),
+                registration.clone(),
+            );
 
             upcast!(spawn_actor(provider))
         }
+        config::NodeDiscoveryProvider::Dns {
+            srv_name,
+            srv_port,
+            propagation_delay,
+        } => {
+            if let config::DnsProvider::Cloudflare { .. } = config.dns_provider {
+                bail!("The dns node discovery requires a dns provider with srv record support");
+            }
+
+            upcast!(spawn_actor(dns::DnsNodeDiscovery::new(
+                srv_name.clone(),
+                *srv_port,
+                *propagation_delay,
+                dns_provider
+            )))
+        }
+        config::NodeDiscoveryProvider::Static { state_path, nodes } => upcast!(spawn_actor(
+            static_list::StaticNodeDiscovery::new(state_path, nodes)?
+        )),
     })
 }
@@ -1,46 +1,213 @@
 use crate::actor;
-use crate::node::discovery::{NodeDiscoveryData, NodeDiscoveryProvider, NodeDiscoveryState};
+use crate::cloud_provider::CloudNodeInfo;
+use crate::config::{ConsulCheck, ConsulRegistration, ConsulWatch};
+use crate::consul::{next_consul_index, TxnClient};
+use crate::node::discovery::{
+    NodeDiscoveries, NodeDiscoveryData, NodeDiscoveryObserver, NodeDiscoveryProvider,
+    NodeDiscoveryState,
+};
+use crate::node::Node;
 use crate::node::NodeDrainingCause::{RollingUpdate, Scaling, Termination};
-use act_zero::{Actor, ActorError, ActorResult, Addr, Produces};
+use act_zero::runtimes::tokio::spawn_actor;
+use act_zero::{send, Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use consul_api_client::catalog::{Catalog, CatalogRegistration};
+use consul_api_client::agent::{AgentCheck, AgentService, HealthCheckDefinition};
+use consul_api_client::catalog::{Catalog, CatalogDeregistration, CatalogRegistration};
 use consul_api_client::health::{Health, ServiceEntry};
 use consul_api_client::Client as ConsulClient;
+use consul_api_client::QueryOptions;
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
+use std::future::Future;
+use std::net::IpAddr;
 use std::str::FromStr;
-use tracing::info;
+use std::time::Duration;
 use tracing::warn;
+use tracing::{error, info};
+
+/// Delay before a failed blocking query is retried
+const WATCH_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Number of check-and-set attempts to write a node state
+const STATE_UPDATE_ATTEMPTS: u32 = 3;
+
+/// Outcome of a single check-and-set write of a node state
+#[derive(Debug, PartialEq)]
+enum StateWrite {
+    Written,
+    /// The service was modified concurrently, a fresh read may succeed
+    Conflict,
+    /// The node has no service definition, retrying won't change that
+    MissingService,
+}
+
+/// Consul client bound to a single datacenter; without a name the datacenter of the agent is used
+#[derive(Clone)]
+pub struct ConsulDatacenter {
+    pub name: Option<String>,
+    pub consul: ConsulClient,
+    pub txn: TxnClient,
+}
 
 pub struct ConsulNodeDiscovery {
-    consul: ConsulClient,
+    datacenters: Vec<ConsulDatacenter>,
     service_name: String,
+    watch_config: Option<ConsulWatch>,
+    watches: Vec<Addr<ConsulNodeDiscoveryWatch>>,
+    /// New nodes are registered in the datacenter of their node group
+    registration_config: Option<ConsulRegistration>,
+    /// Index of the datacenter that owns the node per hostname, as of the last discovery
+    node_datacenters: HashMap<String, usize>,
+    /// Index of the datacenter the nodes of a group were discovered in; groups without nodes
+    /// belong to the first datacenter
+    group_datacenters: HashMap<String, usize>,
 }
 
 impl ConsulNodeDiscovery {
-    pub fn new(consul: ConsulClient, service_name: String) -> Self {
+    pub fn new(
+        datacenters: Vec<ConsulDatacenter>,
+        service_name: String,
+        watch_config: Option<ConsulWatch>,
+        registration_config: Option<ConsulRegistration>,
+    ) -> Self {
         Self {
-            consul,
+            datacenters,
             service_name,
+            watch_config,
+            watches: vec![],
+            registration_config,
+            node_datacenters: HashMap::new(),
+            group_datacenters: HashMap::new(),
         }
     }
 
-    async fn find_service_definition(&self, hostname: &str) -> Result<ServiceEntry> {
-        let mut params = HashMap::new();
-        params.insert(
-            "filter".to_owned(),
-            format!("Node.Node == \"{}\"", hostname),
-        );
+    async fn find_service_definition(
+        &self,
+        hostname: &str,
+    ) -> Result<Option<(usize, ServiceEntry)>> {
+        // ask the owning datacenter first and fall back to all others for unknown nodes
+        let mut candidates = (0..self.datacenters.len()).collect::<Vec<usize>>();
+        if let Some(owner) = self.node_datacenters.get(hostname) {
+            candidates.retain(|dc| dc != owner);
+            candidates.insert(0, *owner);
+        }
 
-        let (mut services, _meta) = self
-            .consul
-            .service(&self.service_name, None, true, Some(params), None)
-            .await?;
+        for dc in candidates {
+            let mut params = HashMap::new();
+            params.insert(
+                "filter".to_owned(),
+                format!("Node.Node == \"{}\"", hostname),
+            );
+
+            let (mut services, _meta) = self.datacenters[dc]
+                .consul
+                .service(&self.service_name, None, true, Some(params), None)
+                .await?;
+
+            if let Some(service_entry) = services.pop() {
+                return Ok(Some((dc, service_entry)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Replaces the state tag of the node's service with a check-and-set on its modify index
+    async fn write_state(&self, hostname: &str, state: &NodeDiscoveryState) -> Result<StateWrite> {
+        let (dc, service_entry) = match self.find_service_definition(hostname).await? {
+            Some(v) => v,
+            None => return Ok(StateWrite::MissingService),
+        };
+
+        let datacenter = &self.datacenters[dc];
+        let node = &service_entry.Node.Node;
+
+        let mut service = match datacenter
+            .txn
+            .get_service(node, &service_entry.Service.ID)
+            .await?
+        {
+            Some(v) => v,
+            None => return Ok(StateWrite::MissingService),
+        };
+
+        info!(datacenter = ?datacenter.name, "Fetched service definition");
+
+        let mut tags = service_state_tags(&service);
+        tags.retain(|t| !is_state_tag(t));
+        tags.push(format!("state={}", state.to_string()));
+
+        service["Tags"] = serde_json::json!(tags);
+
+        Ok(match datacenter.txn.cas_service(node, &service).await? {
+            true => StateWrite::Written,
+            false => StateWrite::Conflict,
+        })
+    }
 
-        services
-            .pop()
-            .ok_or_else(|| anyhow!("Failed to find service definition"))
+    /// Nodes of all reachable datacenters, together with the datacenters that couldn't be queried;
+    /// fails only if no datacenter could be queried at all
+    async fn discover_datacenters(
+        &mut self,
+    ) -> Result<(Vec<NodeDiscoveryData>, Vec<Option<String>>)> {
+        let mut ndd = vec![];
+        let mut node_datacenters = HashMap::new();
+        let mut group_datacenters = HashMap::new();
+        let mut failed_datacenters = vec![];
+        let mut last_error = None;
+
+        for (dc, datacenter) in self.datacenters.iter().enumerate() {
+            let services = match datacenter
+                .consul
+                .service(&self.service_name, None, true, None, None)
+                .await
+            {
+                Ok((services, _meta)) => services,
+                Err(e) => {
+                    error!(
+                        datacenter = ?datacenter.name,
+                        error = format!("{:?}", e).as_str(),
+                        "Failed to discover nodes in datacenter"
+                    );
+
+                    failed_datacenters.push(dc);
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+
+            for discovery in into_discovery_data(services, datacenter.name.as_ref()) {
+                node_datacenters.insert(discovery.hostname.clone(), dc);
+                group_datacenters
+                    .entry(discovery.group.clone())
+                    .or_insert(dc);
+                ndd.push(discovery);
+            }
+        }
+
+        match last_error {
+            Some(e) if failed_datacenters.len() == self.datacenters.len() => Err(e.into()),
+            _ => {
+                merge_datacenters(
+                    &mut self.node_datacenters,
+                    node_datacenters,
+                    &failed_datacenters,
+                );
+                merge_datacenters(
+                    &mut self.group_datacenters,
+                    group_datacenters,
+                    &failed_datacenters,
+                );
+
+                let failed_datacenters = failed_datacenters
+                    .into_iter()
+                    .map(|dc| self.datacenters[dc].name.clone())
+                    .collect();
+
+                Ok((ndd, failed_datacenters))
+            }
+        }
     }
 }
 
@@ -66,88 +233,465 @@ impl Actor for ConsulNodeDiscovery {
 
 #[async_trait]
 impl NodeDiscoveryProvider for ConsulNodeDiscovery {
+    /// The state tag is written with a check-and-set on the modify index of the service, so a
+    /// node agent that re-registers its service concurrently doesn't get overwritten with stale
+    /// data; a failed check-and-set is retried with a fresh read
     #[tracing::instrument(name = "ConsulNodeDiscovery::update_state", skip(self))]
-    async fn update_state(
+    async fn update_state(&mut self, node: Node, state: NodeDiscoveryState) -> ActorResult<()> {
+        let provider = &*self;
+
+        write_with_retries(|| provider.write_state(&node.hostname, &state))
+            .await
+            .map_err(actor::Error::from)?;
+
+        Produces::ok(())
+    }
+
+    #[tracing::instrument(name = "ConsulNodeDiscovery::discover_nodes", skip(self))]
+    async fn discover_nodes(&mut self) -> ActorResult<Vec<NodeDiscoveryData>> {
+        // nodes of an unreachable datacenter are simply not refreshed this time
+        let (ndd, _failed_datacenters) = self
+            .discover_datacenters()
+            .await
+            .map_err(actor::Error::from)?;
+
+        Produces::ok(ndd)
+    }
+
+    #[tracing::instrument(name = "ConsulNodeDiscovery::discover_all_nodes", skip(self))]
+    async fn discover_all_nodes(&mut self) -> ActorResult<NodeDiscoveries> {
+        // failed datacenters are logged and reported along with the nodes of the others
+        let (nodes, failed_datacenters) = self
+            .discover_datacenters()
+            .await
+            .map_err(actor::Error::from)?;
+
+        Produces::ok(NodeDiscoveries {
+            nodes,
+            failed_datacenters,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "ConsulNodeDiscovery::register_node",
+        skip(self, node_info),
+        fields(hostname = %node_info.hostname)
+    )]
+    async fn register_node(
         &mut self,
-        hostname: String,
+        node_info: CloudNodeInfo,
         state: NodeDiscoveryState,
     ) -> ActorResult<()> {
-        let service_entry = self
-            .find_service_definition(&hostname)
-            .await
-            .map_err(actor::Error::from)?;
+        let registration_config = match self.registration_config.as_ref() {
+            Some(v) => v,
+            None => return Produces::ok(()),
+        };
 
-        info!("Fetched service definition");
+        let address = service_address(&node_info, registration_config.use_private_address)
+            .ok_or_else(|| anyhow!("Node has no ip address"))
+            .map_err(actor::Error::from)?
+            .to_string();
 
-        let mut service = service_entry.Service;
+        let dc = self
+            .group_datacenters
+            .get(&node_info.group)
+            .copied()
+            .unwrap_or(0);
+        let datacenter = &self.datacenters[dc];
 
-        let mut tags = service.Tags.clone().unwrap_or_else(Vec::new);
-        tags.retain(|t| !is_state_tag(t));
+        let mut tags = registration_config.tags.clone();
         tags.push(format!("state={}", state.to_string()));
 
-        service.Tags = Some(tags);
+        let mut meta = HashMap::new();
+        meta.insert("node_group".to_owned(), node_info.group.clone());
 
-        let registration = CatalogRegistration {
-            ID: service_entry.Node.ID,
-            Node: service_entry.Node.Node,
-            Address: service_entry.Node.Address,
+        // marks the node as external, so consul-esm runs its health checks
+        let mut node_meta = HashMap::new();
+        node_meta.insert("external-node".to_owned(), "true".to_owned());
+        node_meta.insert("external-probe".to_owned(), "true".to_owned());
+
+        let service = AgentService {
+            ID: self.service_name.clone(),
+            Service: self.service_name.clone(),
+            Tags: Some(tags),
+            Port: registration_config.port,
+            Address: address.clone(),
+            Meta: meta,
+            ..Default::default()
+        };
+
+        let mut registrations = vec![CatalogRegistration {
+            ID: String::new(),
+            Node: node_info.hostname.clone(),
+            Address: address.clone(),
             TaggedAddresses: HashMap::new(),
-            NodeMeta: HashMap::new(),
-            Datacenter: service_entry.Node.Datacenter.unwrap_or_default(),
+            NodeMeta: node_meta,
+            Datacenter: datacenter.name.clone().unwrap_or_default(),
             Service: Some(service),
             Check: None,
-            SkipNodeUpdate: true,
+            SkipNodeUpdate: false,
+        }];
+
+        // every registration carries a single check only
+        for check_config in registration_config.checks.iter() {
+            registrations.push(CatalogRegistration {
+                ID: String::new(),
+                Node: node_info.hostname.clone(),
+                Address: address.clone(),
+                TaggedAddresses: HashMap::new(),
+                NodeMeta: HashMap::new(),
+                Datacenter: datacenter.name.clone().unwrap_or_default(),
+                Service: None,
+                Check: Some(build_check(
+                    &node_info.hostname,
+                    &self.service_name,
+                    &address,
+                    registration_config.port,
+                    check_config,
+                )),
+                SkipNodeUpdate: true,
+            });
+        }
+
+        for registration in registrations.iter() {
+            datacenter
+                .consul
+                .register(registration, None)
+                .await
+                .map_err(anyhow::Error::new)
+                .map_err(actor::Error::from)?;
+        }
+
+        info!(datacenter = ?datacenter.name, "Registered node");
+
+        self.node_datacenters.insert(node_info.hostname, dc);
+        self.group_datacenters.entry(node_info.group).or_insert(dc);
+
+        Produces::ok(())
+    }
+
+    #[tracing::instrument(name = "ConsulNodeDiscovery::deregister_node", skip(self))]
+    async fn deregister_node(&mut self, hostname: String) -> ActorResult<()> {
+        if self.registration_config.is_none() {
+            return Produces::ok(());
+        }
+
+        let dc = self.node_datacenters.get(&hostname).copied().unwrap_or(0);
+        let datacenter = &self.datacenters[dc];
+
+        // deregistering the external node removes its service and checks as well
+        let deregistration = CatalogDeregistration {
+            Node: hostname.clone(),
+            Address: String::new(),
+            Datacenter: datacenter.name.clone().unwrap_or_default(),
+            ServiceID: String::new(),
+            CheckID: String::new(),
         };
 
-        self.consul
-            .register(&registration, None)
+        datacenter
+            .consul
+            .deregister(&deregistration, None)
             .await
             .map_err(anyhow::Error::new)
             .map_err(actor::Error::from)?;
 
-        info!("Updated service definition");
+        info!(datacenter = ?datacenter.name, "Deregistered node");
+
+        self.node_datacenters.remove(&hostname);
 
         Produces::ok(())
     }
 
-    #[tracing::instrument(name = "ConsulNodeDiscovery::discover_nodes", skip(self))]
-    async fn discover_nodes(&mut self) -> ActorResult<Vec<NodeDiscoveryData>> {
-        let (services, _meta) = self
+    #[tracing::instrument(name = "ConsulNodeDiscovery::watch_nodes", skip(self, observer))]
+    async fn watch_nodes(&mut self, observer: Addr<dyn NodeDiscoveryObserver>) -> ActorResult<()> {
+        let watch_config = match &self.watch_config {
+            Some(watch_config) if self.watches.is_empty() => watch_config,
+            _ => return Produces::ok(()),
+        };
+
+        info!("Start watching nodes");
+
+        for datacenter in self.datacenters.iter() {
+            self.watches.push(spawn_actor(ConsulNodeDiscoveryWatch::new(
+                datacenter.clone(),
+                self.service_name.clone(),
+                watch_config.wait_time,
+                observer.clone(),
+            )));
+        }
+
+        Produces::ok(())
+    }
+}
+
+/// Runs consul blocking queries on the health endpoint of the service within a single datacenter
+/// and pushes every node whose discovery data changed since the last query to the observer
+pub struct ConsulNodeDiscoveryWatch {
+    datacenter: ConsulDatacenter,
+    service_name: String,
+    wait_time: Duration,
+    observer: Addr<dyn NodeDiscoveryObserver>,
+    index: Option<u64>,
+    known_nodes: HashMap<String, NodeDiscoveryData>,
+    addr: WeakAddr<Self>,
+}
+
+impl ConsulNodeDiscoveryWatch {
+    fn new(
+        datacenter: ConsulDatacenter,
+        service_name: String,
+        wait_time: Duration,
+        observer: Addr<dyn NodeDiscoveryObserver>,
+    ) -> Self {
+        Self {
+            datacenter,
+            service_name,
+            wait_time,
+            observer,
+            index: None,
+            known_nodes: HashMap::new(),
+            addr: Default::default(),
+        }
+    }
+
+    #[tracing::instrument(
+        name = "ConsulNodeDiscoveryWatch::watch",
+        skip(self),
+        fields(datacenter = ?self.datacenter.name, index = ?self.index)
+    )]
+    async fn watch(&mut self) {
+        let options = QueryOptions {
+            wait_index: self.index,
+            wait_time: Some(self.wait_time),
+            ..Default::default()
+        };
+
+        let result = self
+            .datacenter
             .consul
-            .service(&self.service_name, None, true, None, None)
-            .await
-            .map_err(anyhow::Error::new)
-            .map_err(actor::Error::from)?;
+            .service(&self.service_name, None, true, None, Some(&options))
+            .await;
 
-        let ndd = services
-            .into_iter()
-            .filter_map(|se| -> Option<NodeDiscoveryData> {
-                let node = se.Node.Node.clone();
-
-                se.try_into()
-                    .map_err(|e: String| {
-                        warn!(
-                            error = e.as_str(),
-                            node = node.as_str(),
-                            "Failed to convert ServiceEntry into NodeDiscoveryData"
-                        );
-
-                        e
-                    })
-                    .ok()
-            })
-            .collect();
+        match result {
+            Ok((services, meta)) => {
+                self.index = next_consul_index(self.index, meta.last_index);
+                self.publish_changes(services);
+            }
+            Err(e) => {
+                error!(error = format!("{:?}", e).as_str(), "Failed to watch nodes");
 
-        Produces::ok(ndd)
+                self.index = None;
+                tokio::time::delay_for(WATCH_RETRY_DELAY).await;
+            }
+        }
+
+        send!(self.addr.watch());
+    }
+
+    fn publish_changes(&mut self, services: Vec<ServiceEntry>) {
+        let discoveries = into_discovery_data(services, self.datacenter.name.as_ref());
+
+        for discovery in changed_discoveries(&mut self.known_nodes, discoveries) {
+            info!(
+                hostname = %discovery.hostname,
+                state = %discovery.state,
+                "Node discovery changed"
+            );
+
+            send!(self.observer.observe_node_discovery(discovery));
+        }
     }
 }
 
+#[async_trait]
+impl Actor for ConsulNodeDiscoveryWatch {
+    #[tracing::instrument(
+        name = "ConsulNodeDiscoveryWatch::started"
+        skip(self, addr),
+    )]
+    async fn started(&mut self, addr: Addr<Self>) -> ActorResult<()>
+    where
+        Self: Sized,
+    {
+        info!("Started");
+
+        self.addr = addr.downgrade();
+        send!(self.addr.watch());
+
+        Produces::ok(())
+    }
+
+    async fn error(&mut self, error: ActorError) -> bool {
+        actor::handle_error(error)
+    }
+}
+
+/// Replaces the known datacenters of nodes or groups with the discovered ones; the known ones of
+/// datacenters that could not be queried are kept
+fn merge_datacenters(
+    known: &mut HashMap<String, usize>,
+    discovered: HashMap<String, usize>,
+    failed_datacenters: &[usize],
+) {
+    known.retain(|_, dc| failed_datacenters.contains(dc));
+
+    for (key, dc) in discovered {
+        known.entry(key).or_insert(dc);
+    }
+}
+
+/// Replaces the known nodes with the discovered ones and returns the nodes that are new or whose
+/// discovery data changed
+fn changed_discoveries(
+    known_nodes: &mut HashMap<String, NodeDiscoveryData>,
+    discoveries: Vec<NodeDiscoveryData>,
+) -> Vec<NodeDiscoveryData> {
+    let discoveries = discoveries
+        .into_iter()
+        .map(|ndd| (ndd.hostname.clone(), ndd))
+        .collect::<HashMap<String, NodeDiscoveryData>>();
+
+    let changed = discoveries
+        .values()
+        .filter(|discovery| known_nodes.get(&discovery.hostname) != Some(discovery))
+        .cloned()
+        .collect();
+
+    *known_nodes = discoveries;
+
+    changed
+}
+
+/// Retries failed and concurrently modified writes, each attempt reads the service again; a
+/// missing service is never retried, as it doesn't show up by itself
+async fn write_with_retries<F, Fut>(mut write: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<StateWrite>>,
+{
+    for attempt in 1..=STATE_UPDATE_ATTEMPTS {
+        match write().await {
+            Ok(StateWrite::Written) => {
+                info!(attempt, "Updated service definition");
+
+                return Ok(());
+            }
+            Ok(StateWrite::Conflict) => {
+                warn!(attempt, "Service definition was modified concurrently")
+            }
+            Ok(StateWrite::MissingService) => {
+                return Err(anyhow!("Missing service definition of the node"))
+            }
+            Err(e) => warn!(
+                attempt,
+                error = format!("{:?}", e).as_str(),
+                "Failed to write node state"
+            ),
+        }
+    }
+
+    Err(anyhow!(
+        "Node state update failed after {} attempts",
+        STATE_UPDATE_ATTEMPTS
+    ))
+}
+
+/// Prefers ipv4 addresses, as not every checker is able to reach ipv6 addresses
+fn service_address(node_info: &CloudNodeInfo, use_private_address: bool) -> Option<IpAddr> {
+    if use_private_address {
+        if let Some(ip) = node_info.private_ip_addresses.first() {
+            return Some(*ip);
+        }
+    }
+
+    node_info
+        .ip_addresses
+        .iter()
+        .find(|ip| ip.is_ipv4())
+        .or_else(|| node_info.ip_addresses.first())
+        .copied()
+}
+
+fn build_check(
+    hostname: &str,
+    service_name: &str,
+    address: &str,
+    port: u16,
+    check_config: &ConsulCheck,
+) -> AgentCheck {
+    let (http, tcp) = match check_config.http.as_ref() {
+        Some(http) => (http.clone(), String::new()),
+        None => (String::new(), format!("{}:{}", address, port)),
+    };
+
+    AgentCheck {
+        Node: hostname.to_owned(),
+        CheckID: format!("{}:{}", service_name, check_config.name),
+        Name: check_config.name.clone(),
+        // stays critical until the external checker ran it the first time
+        Status: "critical".to_owned(),
+        ServiceID: service_name.to_owned(),
+        ServiceName: service_name.to_owned(),
+        Definition: HealthCheckDefinition {
+            HTTP: http,
+            TCP: tcp,
+            Interval: format!("{}ms", check_config.interval.as_millis()),
+            Timeout: format!("{}ms", check_config.timeout.as_millis()),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Converts the service entries of a datacenter, nodes are annotated with the datacenter they
+/// were discovered in
+fn into_discovery_data(
+    services: Vec<ServiceEntry>,
+    datacenter: Option<&String>,
+) -> Vec<NodeDiscoveryData> {
+    let discoveries = services
+        .into_iter()
+        .filter_map(|se| -> Option<NodeDiscoveryData> {
+            let node = se.Node.Node.clone();
+
+            se.try_into()
+                .map_err(|e: String| {
+                    warn!(
+                        error = e.as_str(),
+                        node = node.as_str(),
+                        "Failed to convert ServiceEntry into NodeDiscoveryData"
+                    );
+
+                    e
+                })
+                .ok()
+        })
+        .collect();
+
+    with_datacenter(discoveries, datacenter)
+}
+
+/// Nodes whose service entry doesn't name a datacenter belong to the queried one
+fn with_datacenter(
+    discoveries: Vec<NodeDiscoveryData>,
+    datacenter: Option<&String>,
+) -> Vec<NodeDiscoveryData> {
+    discoveries
+        .into_iter()
+        .map(|ndd| NodeDiscoveryData {
+            datacenter: ndd.datacenter.or_else(|| datacenter.cloned()),
+            ..ndd
+        })
+        .collect()
+}
+
 impl TryFrom<ServiceEntry> for NodeDiscoveryData {
     type Error = String;
 
     fn try_from(s: ServiceEntry) -> Result<Self, Self::Error> {
         Ok(Self {
             hostname: s.Node.Node,
+            datacenter: s.Node.Datacenter,
             group: s
                 .Service
                 .Meta
@@ -180,6 +724,17 @@ fn parse_node_state_from_tags(tags: &[String]) -> Option<NodeDiscoveryState> {
     })
 }
 
+fn service_state_tags(service: &serde_json::Value) -> Vec<String> {
+    service["Tags"]
+        .as_array()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|tag| tag.as_str().map(ToOwned::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 fn is_state_tag(s: &str) -> bool {
     s.starts_with("state=")
 }
@@ -198,3 +753,160 @@ impl FromStr for NodeDiscoveryState {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+
+    async fn write_outcomes(outcomes: Vec<Result<StateWrite>>) -> (Result<()>, usize) {
+        let mut outcomes = outcomes.into_iter();
+        let mut attempts = 0;
+
+        let result = write_with_retries(|| {
+            attempts += 1;
+            future::ready(outcomes.next().unwrap())
+        })
+        .await;
+
+        (result, attempts)
+    }
+
+    #[tokio::test]
+    async fn test_write_with_retries_retries_conflicts() {
+        let (result, attempts) =
+            write_outcomes(vec![Ok(StateWrite::Conflict), Ok(StateWrite::Written)]).await;
+
+        assert!(result.is_ok());
+        assert_eq!(2, attempts);
+
+        let (result, attempts) = write_outcomes(vec![
+            Ok(StateWrite::Conflict),
+            Err(anyhow!("connection refused")),
+            Ok(StateWrite::Conflict),
+        ])
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(STATE_UPDATE_ATTEMPTS as usize, attempts);
+    }
+
+    #[tokio::test]
+    async fn test_write_with_retries_gives_up_on_missing_service() {
+        let (result, attempts) = write_outcomes(vec![
+            Ok(StateWrite::MissingService),
+            Ok(StateWrite::Written),
+        ])
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(1, attempts);
+    }
+
+    fn discovery(hostname: &str, state: NodeDiscoveryState) -> NodeDiscoveryData {
+        NodeDiscoveryData {
+            hostname: hostname.to_owned(),
+            group: "edge".to_owned(),
+            state,
+            datacenter: Some("dc1".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_changed_discoveries() {
+        let mut known_nodes = HashMap::new();
+
+        let changed = changed_discoveries(
+            &mut known_nodes,
+            vec![
+                discovery("edge-1", NodeDiscoveryState::Ready),
+                discovery("edge-2", NodeDiscoveryState::Active),
+            ],
+        );
+        assert_eq!(2, changed.len());
+
+        let changed = changed_discoveries(
+            &mut known_nodes,
+            vec![
+                discovery("edge-1", NodeDiscoveryState::Active),
+                discovery("edge-2", NodeDiscoveryState::Active),
+            ],
+        );
+        assert_eq!(
+            vec![discovery("edge-1", NodeDiscoveryState::Active)],
+            changed
+        );
+
+        let changed = changed_discoveries(
+            &mut known_nodes,
+            vec![discovery("edge-1", NodeDiscoveryState::Active)],
+        );
+        assert!(changed.is_empty());
+        assert_eq!(1, known_nodes.len());
+    }
+
+    #[test]
+    fn test_parse_node_state_from_tags() {
+        let tags = vec!["edge".to_owned(), "state=draining-scaling".to_owned()];
+
+        assert_eq!(
+            Some(NodeDiscoveryState::Draining(Scaling)),
+            parse_node_state_from_tags(&tags)
+        );
+        assert_eq!(None, parse_node_state_from_tags(&["state=gone".to_owned()]));
+    }
+
+    #[test]
+    fn test_merge_datacenters_keeps_failed_ones() {
+        let mut known = HashMap::new();
+        known.insert("edge-1".to_owned(), 0);
+        known.insert("edge-2".to_owned(), 1);
+        known.insert("edge-3".to_owned(), 1);
+
+        let mut discovered = HashMap::new();
+        discovered.insert("edge-1".to_owned(), 0);
+        discovered.insert("edge-4".to_owned(), 0);
+
+        merge_datacenters(&mut known, discovered, &[1]);
+
+        let mut expected = HashMap::new();
+        expected.insert("edge-1".to_owned(), 0);
+        expected.insert("edge-2".to_owned(), 1);
+        expected.insert("edge-3".to_owned(), 1);
+        expected.insert("edge-4".to_owned(), 0);
+        assert_eq!(expected, known);
+    }
+
+    #[test]
+    fn test_merge_datacenters_forgets_undiscovered_ones() {
+        let mut known = HashMap::new();
+        known.insert("edge-1".to_owned(), 0);
+        known.insert("edge-2".to_owned(), 1);
+
+        let mut discovered = HashMap::new();
+        discovered.insert("edge-2".to_owned(), 0);
+
+        merge_datacenters(&mut known, discovered, &[]);
+
+        let mut expected = HashMap::new();
+        expected.insert("edge-2".to_owned(), 0);
+        assert_eq!(expected, known);
+    }
+
+    #[test]
+    fn test_discovery_data_gets_the_queried_datacenter() {
+        let discoveries = vec![
+            discovery("edge-1", NodeDiscoveryState::Ready),
+            NodeDiscoveryData {
+                datacenter: None,
+                ..discovery("edge-2", NodeDiscoveryState::Ready)
+            },
+        ];
+        let datacenter = "dc2".to_owned();
+
+        let annotated = with_datacenter(discoveries, Some(&datacenter));
+
+        assert_eq!(Some("dc1"), annotated[0].datacenter.as_deref());
+        assert_eq!(Some("dc2"), annotated[1].datacenter.as_deref());
+    }
+}
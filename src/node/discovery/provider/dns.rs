@@ -0,0 +1,336 @@
+use crate::actor;
+use crate::cloud_provider::CloudNodeInfo;
+use crate::dns_provider::DnsProvider;
+use crate::node::discovery::{
+    NodeDiscoveries, NodeDiscoveryData, NodeDiscoveryProvider, NodeDiscoveryState,
+};
+use crate::node::Node;
+use act_zero::{call, Actor, ActorError, ActorResult, Addr, Produces};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use trust_dns_resolver::TokioAsyncResolver;
+
+/// Discovers the targets of a SRV record as nodes, their group and state are read from the txt
+/// records `group=<name>` and `state=<state>` of each target; provisioned nodes are added to and
+/// removed from the srv record through the dns provider
+pub struct DnsNodeDiscovery {
+    srv_name: String,
+    srv_port: u16,
+    propagation_delay: Duration,
+    dns_provider: Addr<dyn DnsProvider>,
+    resolver: Option<TokioAsyncResolver>,
+    /// Recently written states that may not be visible to resolvers yet
+    written_states: HashMap<String, (NodeDiscoveryState, Instant)>,
+}
+
+impl DnsNodeDiscovery {
+    pub fn new(
+        srv_name: String,
+        srv_port: u16,
+        propagation_delay: Duration,
+        dns_provider: Addr<dyn DnsProvider>,
+    ) -> Self {
+        Self {
+            srv_name,
+            srv_port,
+            propagation_delay,
+            dns_provider,
+            resolver: None,
+            written_states: HashMap::new(),
+        }
+    }
+
+    async fn resolver(&mut self) -> Result<&TokioAsyncResolver> {
+        if self.resolver.is_none() {
+            self.resolver = Some(TokioAsyncResolver::tokio_from_system_conf().await?);
+        }
+
+        Ok(self.resolver.as_ref().unwrap())
+    }
+
+    async fn resolve_node(&mut self, hostname: String) -> Result<NodeDiscoveryData> {
+        let txt_records = self.resolver().await?.txt_lookup(hostname.as_str()).await?;
+
+        let values = txt_records
+            .iter()
+            .flat_map(|txt| txt.txt_data().iter())
+            .map(|data| String::from_utf8_lossy(data).into_owned())
+            .collect::<Vec<String>>();
+
+        parse_txt_values(hostname, &values)
+    }
+
+    async fn lookup_nodes(&mut self) -> Result<Vec<NodeDiscoveryData>> {
+        let srv_name = self.srv_name.clone();
+        let srv_records = self.resolver().await?.srv_lookup(srv_name.as_str()).await?;
+
+        let hostnames = srv_records
+            .iter()
+            .map(|srv| srv_target_hostname(&srv.target().to_utf8()))
+            .collect::<Vec<String>>();
+
+        let mut ndd = Vec::with_capacity(hostnames.len());
+        for hostname in hostnames {
+            match self.resolve_node(hostname.clone()).await {
+                Ok(discovery) => ndd.push(self.apply_written_state(discovery)),
+                Err(e) => warn!(
+                    error = format!("{:?}", e).as_str(),
+                    hostname = hostname.as_str(),
+                    "Failed to resolve node txt records"
+                ),
+            }
+        }
+
+        Ok(ndd)
+    }
+
+    /// Prefers the written state over the resolved one until the propagation delay passed
+    fn apply_written_state(&mut self, discovery: NodeDiscoveryData) -> NodeDiscoveryData {
+        let propagation_delay = self.propagation_delay;
+        self.written_states.retain(|_, (_, written_at)| {
+            Instant::now().duration_since(*written_at) < propagation_delay
+        });
+
+        match self.written_states.get(&discovery.hostname) {
+            Some((state, _)) => NodeDiscoveryData {
+                state: state.clone(),
+                ..discovery
+            },
+            None => discovery,
+        }
+    }
+}
+
+#[async_trait]
+impl Actor for DnsNodeDiscovery {
+    #[tracing::instrument(
+        name = "DnsNodeDiscovery::started"
+        skip(self, _addr),
+    )]
+    async fn started(&mut self, _addr: Addr<Self>) -> ActorResult<()>
+    where
+        Self: Sized,
+    {
+        info!("Started");
+
+        Produces::ok(())
+    }
+
+    async fn error(&mut self, error: ActorError) -> bool {
+        actor::handle_error(error)
+    }
+}
+
+#[async_trait]
+impl NodeDiscoveryProvider for DnsNodeDiscovery {
+    #[tracing::instrument(name = "DnsNodeDiscovery::update_state", skip(self))]
+    async fn update_state(&mut self, node: Node, state: NodeDiscoveryState) -> ActorResult<()> {
+        call!(self.dns_provider.set_txt_records(
+            node.hostname.clone(),
+            vec![
+                format!("group={}", node.group),
+                format!("state={}", state.to_string())
+            ]
+        ))
+        .await?;
+
+        info!("Updated state txt record");
+
+        self.written_states
+            .insert(node.hostname, (state, Instant::now()));
+
+        Produces::ok(())
+    }
+
+    #[tracing::instrument(name = "DnsNodeDiscovery::discover_nodes", skip(self))]
+    async fn discover_nodes(&mut self) -> ActorResult<Vec<NodeDiscoveryData>> {
+        let ndd = self.lookup_nodes().await.map_err(actor::Error::from)?;
+
+        Produces::ok(ndd)
+    }
+
+    #[tracing::instrument(name = "DnsNodeDiscovery::discover_all_nodes", skip(self))]
+    async fn discover_all_nodes(&mut self) -> ActorResult<NodeDiscoveries> {
+        let ndd = self.lookup_nodes().await.map_err(actor::Error::from)?;
+
+        Produces::ok(NodeDiscoveries::complete(ndd))
+    }
+
+    /// Writes the txt records of the node before it becomes a target of the srv record, so it
+    /// is never discovered without its group and state
+    #[tracing::instrument(
+        name = "DnsNodeDiscovery::register_node",
+        skip(self, node_info),
+        fields(hostname = %node_info.hostname)
+    )]
+    async fn register_node(
+        &mut self,
+        node_info: CloudNodeInfo,
+        state: NodeDiscoveryState,
+    ) -> ActorResult<()> {
+        let node = Node {
+            hostname: node_info.hostname,
+            group: node_info.group,
+        };
+
+        self.update_state(node.clone(), state).await?;
+
+        call!(self.dns_provider.add_srv_record(
+            self.srv_name.clone(),
+            srv_value(self.srv_port, &node.hostname)
+        ))
+        .await?;
+
+        info!("Registered node");
+
+        Produces::ok(())
+    }
+
+    #[tracing::instrument(name = "DnsNodeDiscovery::deregister_node", skip(self))]
+    async fn deregister_node(&mut self, hostname: String) -> ActorResult<()> {
+        call!(self
+            .dns_provider
+            .remove_srv_record(self.srv_name.clone(), srv_value(self.srv_port, &hostname)))
+        .await?;
+
+        call!(self.dns_provider.set_txt_records(hostname.clone(), vec![])).await?;
+        self.written_states.remove(&hostname);
+
+        info!("Deregistered node");
+
+        Produces::ok(())
+    }
+}
+
+fn parse_txt_values(hostname: String, values: &[String]) -> Result<NodeDiscoveryData> {
+    let group = find_txt_value(values, "group")
+        .ok_or_else(|| anyhow!("Missing group txt record"))?
+        .to_owned();
+
+    let state = find_txt_value(values, "state")
+        .ok_or_else(|| anyhow!("Missing state txt record"))?
+        .parse()
+        .map_err(|e: String| anyhow!(e))?;
+
+    Ok(NodeDiscoveryData {
+        hostname,
+        group,
+        state,
+        datacenter: None,
+    })
+}
+
+/// Srv record value with the node as target, in the `<priority> <weight> <port> <target>` format
+fn srv_value(port: u16, hostname: &str) -> String {
+    format!("0 0 {} {}.", port, hostname)
+}
+
+fn srv_target_hostname(target: &str) -> String {
+    target.trim_end_matches('.').to_owned()
+}
+
+fn find_txt_value<'a>(values: &'a [String], key: &str) -> Option<&'a str> {
+    values.iter().find_map(|value| {
+        let parts: Vec<&str> = value.splitn(2, '=').collect();
+
+        match parts.as_slice() {
+            [k, v] if *k == key => Some(*v),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::NodeDrainingCause;
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| (*v).to_owned()).collect()
+    }
+
+    fn discovery(propagation_delay: Duration) -> DnsNodeDiscovery {
+        DnsNodeDiscovery::new(
+            "_edge._tcp.example.com".to_owned(),
+            443,
+            propagation_delay,
+            Addr::detached(),
+        )
+    }
+
+    fn discovery_data(state: NodeDiscoveryState) -> NodeDiscoveryData {
+        NodeDiscoveryData {
+            hostname: "edge-1.example.com".to_owned(),
+            group: "edge".to_owned(),
+            state,
+            datacenter: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_txt_values() {
+        let ndd = parse_txt_values(
+            "edge-1.example.com".to_owned(),
+            &values(&["v=spf1 -all", "group=edge", "state=draining-scaling"]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            discovery_data(NodeDiscoveryState::Draining(NodeDrainingCause::Scaling)),
+            ndd
+        );
+    }
+
+    #[test]
+    fn test_parse_txt_values_requires_group_and_state() {
+        let hostname = "edge-1.example.com".to_owned();
+
+        assert!(parse_txt_values(hostname.clone(), &values(&["state=ready"])).is_err());
+        assert!(parse_txt_values(hostname.clone(), &values(&["group=edge"])).is_err());
+        assert!(parse_txt_values(hostname, &values(&["group=edge", "state=unknown"])).is_err());
+    }
+
+    #[test]
+    fn test_srv_value_targets_the_node() {
+        let value = srv_value(443, "edge-1.example.com");
+
+        assert_eq!("0 0 443 edge-1.example.com.", value);
+        assert_eq!(
+            "edge-1.example.com",
+            srv_target_hostname(value.rsplit(' ').next().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_written_state_wins_within_the_propagation_delay() {
+        let mut discovery = discovery(Duration::from_secs(60));
+        discovery.written_states.insert(
+            "edge-1.example.com".to_owned(),
+            (NodeDiscoveryState::Active, Instant::now()),
+        );
+
+        let ndd = discovery.apply_written_state(discovery_data(NodeDiscoveryState::Ready));
+
+        assert_eq!(NodeDiscoveryState::Active, ndd.state);
+    }
+
+    #[test]
+    fn test_written_state_expires_after_the_propagation_delay() {
+        let mut discovery = discovery(Duration::from_secs(1));
+        discovery.written_states.insert(
+            "edge-1.example.com".to_owned(),
+            (
+                NodeDiscoveryState::Active,
+                Instant::now() - Duration::from_secs(2),
+            ),
+        );
+
+        let ndd = discovery.apply_written_state(discovery_data(NodeDiscoveryState::Ready));
+
+        assert_eq!(NodeDiscoveryState::Ready, ndd.state);
+        assert!(discovery.written_states.is_empty());
+    }
+}
@@ -1,28 +1,69 @@
-use crate::node::discovery::{NodeDiscoveryData, NodeDiscoveryProvider, NodeDiscoveryState};
+use crate::config::FileWatch;
+use crate::file_watcher::{FileChange, FileWatcher};
+use crate::node::discovery::{
+    NodeDiscoveries, NodeDiscoveryData, NodeDiscoveryObserver, NodeDiscoveryProvider,
+    NodeDiscoveryState,
+};
+use crate::node::Node;
 use crate::utils::path_append;
 use crate::{actor, utils};
-use act_zero::{Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
+use act_zero::{send, Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
 use anyhow::Context;
 use async_trait::async_trait;
 use futures::TryFutureExt;
 use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 pub struct FileNodeDiscovery {
     directory_path: PathBuf,
+    watch_config: Option<FileWatch>,
+    watcher: Option<FileWatcher>,
+    observer: Option<Addr<dyn NodeDiscoveryObserver>>,
     addr: WeakAddr<Self>,
 }
 
 impl FileNodeDiscovery {
-    pub fn new(directory_path: impl AsRef<Path>) -> Self {
+    pub fn new(directory_path: impl AsRef<Path>, watch_config: Option<FileWatch>) -> Self {
         Self {
             directory_path: directory_path.as_ref().into(),
+            watch_config,
+            watcher: None,
+            observer: None,
             addr: Default::default(),
         }
     }
+
+    /// Removed discovery files need no handling, the nodes just run into their discovery timeout
+    #[tracing::instrument(
+        name = "FileNodeDiscovery::handle_file_change"
+        skip(self),
+        fields(path = %self.directory_path.display())
+    )]
+    async fn handle_file_change(&mut self, change: FileChange) {
+        let observer = match self.observer.as_ref() {
+            Some(v) => v,
+            None => return,
+        };
+
+        match change {
+            FileChange::Changed(path) => match parse_node_discovery_file(&path) {
+                Ok(discovery) => send!(observer.observe_node_discovery(discovery)),
+                Err(e) => warn!(
+                    error = format!("{:?}", e).as_str(),
+                    "Failed to parse changed node discovery file"
+                ),
+            },
+            FileChange::Removed(_) => {}
+            FileChange::Rescan => {
+                for discovery in scan_for_node_discoveries(&self.directory_path).await {
+                    send!(observer.observe_node_discovery(discovery));
+                }
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -77,32 +118,38 @@ impl NodeDiscoveryProvider for FileNodeDiscovery {
         Produces::ok(node_discoveries)
     }
 
+    #[tracing::instrument(
+        name = "FileNodeDiscovery::discover_all_nodes"
+        skip(self),
+        fields(path = %self.directory_path.display())
+    )]
+    async fn discover_all_nodes(&mut self) -> ActorResult<NodeDiscoveries> {
+        let node_discoveries = scan_for_node_discoveries(&self.directory_path).await;
+
+        Produces::ok(NodeDiscoveries::complete(node_discoveries))
+    }
+
     #[tracing::instrument(
         name = "FileNodeDiscovery::update_state"
         skip(self),
         fields(path = %self.directory_path.display())
     )]
-    async fn update_state(
-        &mut self,
-        hostname: String,
-        state: NodeDiscoveryState,
-    ) -> ActorResult<()> {
-        info!("Updating state of node {} {:?}", hostname, state);
-
-        let path = path_append(&self.directory_path.join(&hostname), ".yml");
+    async fn update_state(&mut self, node: Node, state: NodeDiscoveryState) -> ActorResult<()> {
+        info!("Updating state of node {} {:?}", node.hostname, state);
+
+        let path = path_append(&self.directory_path.join(&node.hostname), ".yml");
         let result = File::open(&path)
             .with_context(|| format!("Failed to open {} for reading", &path.display()))
             .map(BufReader::new)
             .and_then(|reader| serde_yaml::from_reader(reader).map_err(anyhow::Error::new))
             .and_then(|discovery_data: NodeDiscoveryData| {
-                File::create(&path)
-                    .with_context(|| format!("Failed to open {} for writing", &path.display()))
-                    .map(BufWriter::new)
-                    .map(|writer| (discovery_data, writer))
+                serde_yaml::to_vec(&NodeDiscoveryData {
+                    state,
+                    ..discovery_data
+                })
+                .map_err(anyhow::Error::new)
             })
-            .and_then(|(discovery_data, writer)| {
-                serde_yaml::to_writer(writer, &discovery_data).map_err(anyhow::Error::new)
-            });
+            .and_then(|data| utils::write_file_atomically(&path, &data));
 
         if let Err(e) = result {
             error!(error = format!("{:?}", e).as_str(), "Failed updating");
@@ -110,6 +157,36 @@ impl NodeDiscoveryProvider for FileNodeDiscovery {
 
         Produces::ok(())
     }
+
+    #[tracing::instrument(
+        name = "FileNodeDiscovery::watch_nodes"
+        skip(self, observer),
+        fields(path = %self.directory_path.display())
+    )]
+    async fn watch_nodes(&mut self, observer: Addr<dyn NodeDiscoveryObserver>) -> ActorResult<()> {
+        let watch_config = match (&self.watch_config, &self.watcher) {
+            (Some(watch_config), None) => watch_config,
+            _ => return Produces::ok(()),
+        };
+
+        let addr = self.addr.clone();
+        let watcher = FileWatcher::new(&self.directory_path, watch_config.debounce, move |c| {
+            send!(addr.handle_file_change(c))
+        });
+
+        match watcher {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+                self.observer = Some(observer);
+            }
+            Err(e) => warn!(
+                error = format!("{:?}", e).as_str(),
+                "Failed to watch node discovery files, falling back to polling"
+            ),
+        }
+
+        Produces::ok(())
+    }
 }
 
 async fn scan_for_node_discoveries(path: impl AsRef<Path>) -> Vec<NodeDiscoveryData> {
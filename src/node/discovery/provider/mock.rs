@@ -1,5 +1,6 @@
 use super::NodeDiscoveryProvider;
-use crate::node::discovery::{NodeDiscoveryData, NodeDiscoveryState};
+use crate::node::discovery::{NodeDiscoveries, NodeDiscoveryData, NodeDiscoveryState};
+use crate::node::Node;
 use act_zero::{Actor, ActorResult, Produces};
 use async_trait::async_trait;
 use tracing::info;
@@ -10,12 +11,8 @@ impl Actor for MockNodeDiscovery {}
 
 #[async_trait]
 impl NodeDiscoveryProvider for MockNodeDiscovery {
-    async fn update_state(
-        &mut self,
-        hostname: String,
-        state: NodeDiscoveryState,
-    ) -> ActorResult<()> {
-        info!("Updating state of node {} {:?}", hostname, state);
+    async fn update_state(&mut self, node: Node, state: NodeDiscoveryState) -> ActorResult<()> {
+        info!("Updating state of node {} {:?}", node.hostname, state);
 
         Produces::ok(())
     }
@@ -23,4 +20,8 @@ impl NodeDiscoveryProvider for MockNodeDiscovery {
     async fn discover_nodes(&mut self) -> ActorResult<Vec<NodeDiscoveryData>> {
         Produces::ok(vec![])
     }
+
+    async fn discover_all_nodes(&mut self) -> ActorResult<NodeDiscoveries> {
+        Produces::ok(NodeDiscoveries::default())
+    }
 }
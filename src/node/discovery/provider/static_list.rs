@@ -0,0 +1,165 @@
+use crate::actor;
+use crate::cloud_provider::CloudNodeInfo;
+use crate::config;
+use crate::node::discovery::{
+    NodeDiscoveries, NodeDiscoveryData, NodeDiscoveryProvider, NodeDiscoveryState,
+};
+use crate::node::Node;
+use crate::utils::write_file_atomically;
+use act_zero::{Actor, ActorError, ActorResult, Addr, Produces};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Static list of nodes that is kept in a json state file; every change gets persisted
+/// atomically, so the list survives restarts
+pub struct StaticNodeDiscovery {
+    state_path: PathBuf,
+    nodes: Vec<NodeDiscoveryData>,
+}
+
+impl StaticNodeDiscovery {
+    /// Loads the node list from the state file, the given nodes are only used if there is no
+    /// state file yet
+    pub fn new(
+        state_path: impl AsRef<Path>,
+        seed_nodes: &[config::StaticNode],
+    ) -> anyhow::Result<Self> {
+        let state_path = state_path.as_ref().to_path_buf();
+
+        let nodes = if state_path.exists() {
+            let data = fs::read(&state_path)
+                .with_context(|| format!("Failed to read {}", state_path.display()))?;
+
+            serde_json::from_slice(&data)
+                .with_context(|| format!("Failed to parse {}", state_path.display()))?
+        } else {
+            seed_nodes
+                .iter()
+                .map(parse_seed_node)
+                .collect::<anyhow::Result<_>>()?
+        };
+
+        Ok(Self { state_path, nodes })
+    }
+
+    fn persist(&self) -> anyhow::Result<()> {
+        let data = serde_json::to_vec_pretty(&self.nodes)?;
+
+        write_file_atomically(&self.state_path, &data)
+    }
+}
+
+#[async_trait]
+impl Actor for StaticNodeDiscovery {
+    #[tracing::instrument(
+        name = "StaticNodeDiscovery::started"
+        skip(self, _addr),
+        fields(path = %self.state_path.display())
+    )]
+    async fn started(&mut self, _addr: Addr<Self>) -> ActorResult<()>
+    where
+        Self: Sized,
+    {
+        info!(nodes = self.nodes.len(), "Started");
+
+        Produces::ok(())
+    }
+
+    async fn error(&mut self, error: ActorError) -> bool {
+        actor::handle_error(error)
+    }
+}
+
+#[async_trait]
+impl NodeDiscoveryProvider for StaticNodeDiscovery {
+    #[tracing::instrument(
+        name = "StaticNodeDiscovery::update_state"
+        skip(self),
+        fields(path = %self.state_path.display())
+    )]
+    async fn update_state(&mut self, node: Node, state: NodeDiscoveryState) -> ActorResult<()> {
+        let discovered = self
+            .nodes
+            .iter_mut()
+            .find(|discovered| discovered.hostname == node.hostname)
+            .ok_or_else(|| anyhow!("Failed to find node"))
+            .map_err(actor::Error::from)?;
+
+        discovered.state = state;
+        self.persist().map_err(actor::Error::from)?;
+
+        info!("Updated node state");
+
+        Produces::ok(())
+    }
+
+    async fn discover_nodes(&mut self) -> ActorResult<Vec<NodeDiscoveryData>> {
+        Produces::ok(self.nodes.clone())
+    }
+
+    async fn discover_all_nodes(&mut self) -> ActorResult<NodeDiscoveries> {
+        Produces::ok(NodeDiscoveries::complete(self.nodes.clone()))
+    }
+
+    #[tracing::instrument(
+        name = "StaticNodeDiscovery::register_node"
+        skip(self, node_info),
+        fields(path = %self.state_path.display(), hostname = %node_info.hostname)
+    )]
+    async fn register_node(
+        &mut self,
+        node_info: CloudNodeInfo,
+        state: NodeDiscoveryState,
+    ) -> ActorResult<()> {
+        self.nodes
+            .retain(|node| node.hostname != node_info.hostname);
+        self.nodes.push(NodeDiscoveryData {
+            hostname: node_info.hostname,
+            group: node_info.group,
+            state,
+            datacenter: None,
+        });
+
+        self.persist().map_err(actor::Error::from)?;
+
+        info!("Registered node");
+
+        Produces::ok(())
+    }
+
+    #[tracing::instrument(
+        name = "StaticNodeDiscovery::deregister_node"
+        skip(self),
+        fields(path = %self.state_path.display())
+    )]
+    async fn deregister_node(&mut self, hostname: String) -> ActorResult<()> {
+        let node_count = self.nodes.len();
+        self.nodes.retain(|node| node.hostname != hostname);
+
+        if self.nodes.len() != node_count {
+            self.persist().map_err(actor::Error::from)?;
+
+            info!("Deregistered node");
+        }
+
+        Produces::ok(())
+    }
+}
+
+fn parse_seed_node(node: &config::StaticNode) -> anyhow::Result<NodeDiscoveryData> {
+    let state = node
+        .state
+        .parse()
+        .map_err(|e: String| anyhow!(e))
+        .with_context(|| format!("Invalid state of seed node {}", node.hostname))?;
+
+    Ok(NodeDiscoveryData {
+        hostname: node.hostname.clone(),
+        group: node.group.clone(),
+        state,
+        datacenter: None,
+    })
+}
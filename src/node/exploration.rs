@@ -67,6 +67,8 @@ impl Actor for NodeExploration {
         self.timer
             .set_interval_weak(self.addr.clone(), self.exploration_interval);
 
+        send!(self.provider.watch_nodes(self.observer.clone()));
+
         Produces::ok(())
     }
 
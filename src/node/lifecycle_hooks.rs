@@ -0,0 +1,358 @@
+use crate::config::{
+    NodeLifecycleEvent, NodeLifecycleHook, NodeLifecycleHookAction, NodeLifecycleHooks,
+};
+use anyhow::{anyhow, Context};
+use http::StatusCode;
+use serde::Serialize;
+use std::fmt;
+use std::net::IpAddr;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Exit code a blocking command hook uses to veto a transition
+const COMMAND_VETO_EXIT_CODE: i32 = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HookOutcome {
+    Proceed,
+    Delay,
+    Veto,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HookPayload {
+    pub event: NodeLifecycleEvent,
+    pub hostname: String,
+    pub group: String,
+    pub ip_addresses: Vec<IpAddr>,
+    pub cause: Option<String>,
+}
+
+/// Runs the configured hooks of a node lifecycle event.
+///
+/// A blocking hook proceeds on success (2xx / exit code 0), vetoes with HTTP 409 or exit code 10
+/// and delays the transition on everything else, including timeouts.
+#[derive(Debug)]
+pub struct LifecycleHooks {
+    config: Option<NodeLifecycleHooks>,
+    http_client: reqwest::Client,
+}
+
+impl LifecycleHooks {
+    pub fn new(config: Option<NodeLifecycleHooks>) -> anyhow::Result<Self> {
+        Ok(Self {
+            config,
+            http_client: reqwest::ClientBuilder::new().build()?,
+        })
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        self.config
+            .as_ref()
+            .map(|c| c.max_delay)
+            .unwrap_or_else(|| Duration::from_secs(0))
+    }
+
+    /// Runs the hooks in the background, so slow blocking hooks don't stall the caller
+    pub fn spawn(hooks: &Arc<Self>, payload: HookPayload) -> JoinHandle<HookOutcome> {
+        let hooks = Arc::clone(hooks);
+
+        tokio::spawn(async move { hooks.run(payload).await })
+    }
+
+    #[tracing::instrument(
+        name = "LifecycleHooks::run",
+        skip(self, payload),
+        fields(event = %payload.event, hostname = %payload.hostname)
+    )]
+    pub async fn run(&self, payload: HookPayload) -> HookOutcome {
+        let hooks = match self.config.as_ref() {
+            Some(config) => config.hooks.iter().filter(|h| h.event == payload.event),
+            None => return HookOutcome::Proceed,
+        };
+
+        let mut outcome = HookOutcome::Proceed;
+
+        for hook in hooks {
+            if !hook.blocking {
+                tokio::spawn({
+                    let hook = hook.clone();
+                    let http_client = self.http_client.clone();
+                    let payload = payload.clone();
+
+                    async move { run_hook(&hook, &http_client, &payload).await }
+                });
+
+                continue;
+            }
+
+            match run_hook(hook, &self.http_client, &payload).await {
+                HookOutcome::Proceed => {}
+                HookOutcome::Delay => {
+                    if outcome == HookOutcome::Proceed {
+                        outcome = HookOutcome::Delay;
+                    }
+                }
+                HookOutcome::Veto => {
+                    outcome = HookOutcome::Veto;
+                    break;
+                }
+            }
+        }
+
+        outcome
+    }
+}
+
+impl fmt::Display for NodeLifecycleEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                NodeLifecycleEvent::PreActivate => "pre-activate",
+                NodeLifecycleEvent::PostReady => "post-ready",
+                NodeLifecycleEvent::PreDrain => "pre-drain",
+                NodeLifecycleEvent::PreDelete => "pre-delete",
+                NodeLifecycleEvent::PostDeprovision => "post-deprovision",
+                NodeLifecycleEvent::DeletionFailed => "deletion-failed",
+            }
+        )
+    }
+}
+
+async fn run_hook(
+    hook: &NodeLifecycleHook,
+    http_client: &reqwest::Client,
+    payload: &HookPayload,
+) -> HookOutcome {
+    let result = match &hook.action {
+        NodeLifecycleHookAction::Http { url, bearer_token } => {
+            tokio::time::timeout(
+                hook.timeout,
+                run_http_hook(http_client, url, bearer_token.as_deref(), payload),
+            )
+            .await
+        }
+        NodeLifecycleHookAction::Command { program, args } => {
+            tokio::time::timeout(hook.timeout, run_command_hook(program, args, payload)).await
+        }
+    };
+
+    let outcome = match result {
+        Ok(Ok(outcome)) => outcome,
+        Ok(Err(e)) => {
+            warn!(error = format!("{:?}", e).as_str(), "Lifecycle hook failed");
+            HookOutcome::Delay
+        }
+        Err(_) => {
+            warn!(
+                timeout_ms = hook.timeout.as_millis() as u64,
+                "Lifecycle hook timed out"
+            );
+            HookOutcome::Delay
+        }
+    };
+
+    info!(
+        action = format!("{:?}", hook.action).as_str(),
+        outcome = format!("{:?}", outcome).as_str(),
+        "Ran lifecycle hook"
+    );
+
+    outcome
+}
+
+async fn run_http_hook(
+    http_client: &reqwest::Client,
+    url: &str,
+    bearer_token: Option<&str>,
+    payload: &HookPayload,
+) -> anyhow::Result<HookOutcome> {
+    let mut request_builder = http_client.post(url).json(payload);
+
+    if let Some(bearer_token) = bearer_token {
+        request_builder = request_builder.bearer_auth(bearer_token);
+    }
+
+    let response = request_builder.send().await?;
+
+    Ok(http_status_outcome(response.status()))
+}
+
+fn http_status_outcome(status: StatusCode) -> HookOutcome {
+    match status {
+        s if s.is_success() => HookOutcome::Proceed,
+        StatusCode::CONFLICT => HookOutcome::Veto,
+        _ => HookOutcome::Delay,
+    }
+}
+
+/// Runs the command with the payload as json on stdin; the placeholders `{event}`, `{hostname}`,
+/// `{group}`, `{ips}` and `{cause}` in the configured arguments get replaced
+async fn run_command_hook(
+    program: &str,
+    args: &[String],
+    payload: &HookPayload,
+) -> anyhow::Result<HookOutcome> {
+    let body = serde_json::to_vec(payload)?;
+
+    let mut child = tokio::process::Command::new(program)
+        .args(args.iter().map(|arg| replace_placeholders(arg, payload)))
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to spawn {}", program))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&body).await?;
+    }
+
+    let status = child.await?;
+
+    Ok(match status.code() {
+        Some(0) => HookOutcome::Proceed,
+        Some(COMMAND_VETO_EXIT_CODE) => HookOutcome::Veto,
+        Some(_) => HookOutcome::Delay,
+        None => return Err(anyhow!("{} was terminated by a signal", program)),
+    })
+}
+
+fn replace_placeholders(arg: &str, payload: &HookPayload) -> String {
+    let ips = payload
+        .ip_addresses
+        .iter()
+        .map(|ip| ip.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+
+    arg.replace("{event}", &payload.event.to_string())
+        .replace("{hostname}", &payload.hostname)
+        .replace("{group}", &payload.group)
+        .replace("{ips}", &ips)
+        .replace("{cause}", payload.cause.as_deref().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> HookPayload {
+        HookPayload {
+            event: NodeLifecycleEvent::PreDelete,
+            hostname: "edge-1.example.com".to_owned(),
+            group: "edge".to_owned(),
+            ip_addresses: vec!["10.0.0.2".parse().unwrap(), "10.0.0.3".parse().unwrap()],
+            cause: None,
+        }
+    }
+
+    fn command_hook(script: &str, blocking: bool) -> NodeLifecycleHook {
+        NodeLifecycleHook {
+            event: NodeLifecycleEvent::PreDelete,
+            blocking,
+            timeout: Duration::from_secs(5),
+            action: NodeLifecycleHookAction::Command {
+                program: "sh".to_owned(),
+                args: vec!["-c".to_owned(), script.to_owned()],
+            },
+        }
+    }
+
+    fn lifecycle_hooks(hooks: Vec<NodeLifecycleHook>) -> LifecycleHooks {
+        LifecycleHooks::new(Some(NodeLifecycleHooks {
+            max_delay: Duration::from_secs(60),
+            hooks,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_http_status_outcome() {
+        assert_eq!(HookOutcome::Proceed, http_status_outcome(StatusCode::OK));
+        assert_eq!(
+            HookOutcome::Proceed,
+            http_status_outcome(StatusCode::NO_CONTENT)
+        );
+        assert_eq!(HookOutcome::Veto, http_status_outcome(StatusCode::CONFLICT));
+        assert_eq!(
+            HookOutcome::Delay,
+            http_status_outcome(StatusCode::NOT_FOUND)
+        );
+        assert_eq!(
+            HookOutcome::Delay,
+            http_status_outcome(StatusCode::SERVICE_UNAVAILABLE)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_exit_code_outcome() {
+        let http_client = reqwest::Client::new();
+
+        for (script, expected) in vec![
+            ("exit 0", HookOutcome::Proceed),
+            ("exit 10", HookOutcome::Veto),
+            ("exit 1", HookOutcome::Delay),
+        ] {
+            let hook = command_hook(script, true);
+
+            assert_eq!(expected, run_hook(&hook, &http_client, &payload()).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_hook_delays() {
+        let hook = NodeLifecycleHook {
+            timeout: Duration::from_millis(50),
+            ..command_hook("sleep 5", true)
+        };
+
+        assert_eq!(
+            HookOutcome::Delay,
+            run_hook(&hook, &reqwest::Client::new(), &payload()).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocking_veto_wins_over_delay() {
+        let hooks = lifecycle_hooks(vec![
+            command_hook("exit 1", true),
+            command_hook("exit 10", true),
+            command_hook("exit 0", true),
+        ]);
+
+        assert_eq!(HookOutcome::Veto, hooks.run(payload()).await);
+    }
+
+    #[tokio::test]
+    async fn test_only_blocking_hooks_decide() {
+        let hooks = lifecycle_hooks(vec![
+            command_hook("exit 10", false),
+            command_hook("exit 0", true),
+        ]);
+
+        assert_eq!(HookOutcome::Proceed, hooks.run(payload()).await);
+    }
+
+    #[tokio::test]
+    async fn test_hooks_of_other_events_are_skipped() {
+        let hooks = lifecycle_hooks(vec![NodeLifecycleHook {
+            event: NodeLifecycleEvent::PreDrain,
+            ..command_hook("exit 10", true)
+        }]);
+
+        assert_eq!(HookOutcome::Proceed, hooks.run(payload()).await);
+    }
+
+    #[test]
+    fn test_replace_placeholders() {
+        assert_eq!(
+            "pre-delete edge edge-1.example.com 10.0.0.2,10.0.0.3 ",
+            replace_placeholders("{event} {group} {hostname} {ips} {cause}", &payload())
+        );
+    }
+}
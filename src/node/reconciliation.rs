@@ -0,0 +1,411 @@
+use crate::cloud_provider::{CloudNodeInfo, CloudProvider, QuarantineStatus};
+use crate::config;
+use crate::dns_provider::{DnsProvider, DnsRecordInfo};
+use crate::node::discovery::{NodeDiscoveries, NodeDiscoveryProvider};
+use crate::node_groups::NodeGroupsController;
+use act_zero::{call, send, Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use tracing::{error, info, warn};
+
+use crate::actor;
+use act_zero::runtimes::tokio::Timer;
+use act_zero::timer::Tick;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Orphan {
+    Node { hostname: String },
+    DnsRecords { hostname: String },
+}
+
+/// Periodically compares cloud nodes, dns records and node discoveries and removes the leftovers
+/// of nodes that were never discovered or are already gone.
+///
+/// Nodes of configured node groups are left to their node controllers, which time out nodes that
+/// never show up in the discovery. Everything else is taken from the node group label and the
+/// discovery, so the reconciliation doesn't depend on state that is lost on restarts.
+pub struct NodeReconciliation {
+    node_groups_controller: Addr<NodeGroupsController>,
+    node_discovery_provider: Addr<dyn NodeDiscoveryProvider>,
+    cloud_provider: Addr<dyn CloudProvider>,
+    dns_provider: Addr<dyn DnsProvider>,
+    node_hostname_suffix: String,
+    config: config::NodeReconciliation,
+    orphans: HashMap<Orphan, Instant>,
+    addr: WeakAddr<Self>,
+    timer: Timer,
+}
+
+impl NodeReconciliation {
+    pub fn new(
+        node_groups_controller: Addr<NodeGroupsController>,
+        node_discovery_provider: Addr<dyn NodeDiscoveryProvider>,
+        cloud_provider: Addr<dyn CloudProvider>,
+        dns_provider: Addr<dyn DnsProvider>,
+        node_hostname_suffix: String,
+        config: config::NodeReconciliation,
+    ) -> Self {
+        Self {
+            node_groups_controller,
+            node_discovery_provider,
+            cloud_provider,
+            dns_provider,
+            node_hostname_suffix,
+            config,
+            orphans: HashMap::new(),
+            addr: Default::default(),
+            timer: Default::default(),
+        }
+    }
+
+    #[tracing::instrument(
+        name = "NodeReconciliation::reconcile",
+        skip(self),
+        fields(dry_run = self.config.dry_run)
+    )]
+    async fn reconcile(&mut self) {
+        let discoveries = call!(self.node_discovery_provider.discover_all_nodes()).await;
+        let nodes = call!(self.cloud_provider.get_nodes()).await;
+        let records = call!(self.dns_provider.get_records()).await;
+        let managed_groups = call!(self.node_groups_controller.get_managed_groups()).await;
+
+        let (discoveries, nodes, records, managed_groups) =
+            match (discoveries, nodes, records, managed_groups) {
+                (Ok(discoveries), Ok(nodes), Ok(records), Ok(managed_groups)) => {
+                    (discoveries, nodes, records, managed_groups)
+                }
+                (discoveries, nodes, records, managed_groups) => {
+                    error!(
+                        discoveries = format!("{:?}", discoveries.err()).as_str(),
+                        nodes = format!("{:?}", nodes.err()).as_str(),
+                        records = format!("{:?}", records.err()).as_str(),
+                        managed_groups = format!("{:?}", managed_groups.err()).as_str(),
+                        "Failed to fetch the data to reconcile"
+                    );
+
+                    return;
+                }
+            };
+
+        let current_orphans = self.find_orphans(&managed_groups, &discoveries, &nodes, &records);
+
+        for orphan in self.expire_orphans(current_orphans) {
+            if self.remove_orphan(&orphan, &nodes).await {
+                self.orphans.remove(&orphan);
+            }
+        }
+    }
+
+    /// Remembers when orphans were first seen and returns the ones that outlasted the grace
+    /// period; in dry-run mode those are only reported
+    fn expire_orphans(&mut self, current_orphans: HashSet<Orphan>) -> Vec<Orphan> {
+        // forget orphans that were adopted in the meantime and remember new ones
+        self.orphans
+            .retain(|orphan, _| current_orphans.contains(orphan));
+        for orphan in current_orphans {
+            self.orphans.entry(orphan).or_insert_with(Instant::now);
+        }
+
+        let expired_orphans = self
+            .orphans
+            .iter()
+            .filter(|(_, first_seen)| {
+                Instant::now().duration_since(**first_seen) >= self.config.grace_period
+            })
+            .map(|(orphan, _)| orphan.clone())
+            .collect::<Vec<Orphan>>();
+
+        if self.config.dry_run {
+            for orphan in expired_orphans {
+                warn!(orphan = format!("{:?}", orphan).as_str(), "Found orphan");
+            }
+
+            return vec![];
+        }
+
+        expired_orphans
+    }
+
+    fn find_orphans(
+        &self,
+        managed_groups: &HashSet<String>,
+        discoveries: &NodeDiscoveries,
+        nodes: &[CloudNodeInfo],
+        records: &[DnsRecordInfo],
+    ) -> HashSet<Orphan> {
+        let node_hostnames = nodes
+            .iter()
+            .map(|n| n.hostname.as_str())
+            .collect::<HashSet<&str>>();
+        let discovered_hostnames = discoveries
+            .nodes
+            .iter()
+            .map(|d| d.hostname.as_str())
+            .collect::<HashSet<&str>>();
+
+        if !discoveries.is_complete() {
+            warn!(
+                failed_datacenters = format!("{:?}", discoveries.failed_datacenters).as_str(),
+                "Skip looking for orphaned nodes, the discovery is partial"
+            );
+        }
+
+        let orphaned_nodes = nodes
+            .iter()
+            // nodes of datacenters that couldn't be queried are missing from partial discoveries
+            .filter(|_| discoveries.is_complete())
+            // node controllers of configured groups time out nodes that are never discovered
+            .filter(|n| !managed_groups.contains(&n.group))
+            // discovered nodes are kept, even if they fail their health checks
+            .filter(|n| !discovered_hostnames.contains(n.hostname.as_str()))
+            // quarantined nodes are kept on purpose
+            .filter(|n| !matches!(n.quarantine, Some(QuarantineStatus::Quarantined { .. })))
+            .filter(|n| {
+                Utc::now()
+                    .signed_duration_since(n.created_at)
+                    .to_std()
+                    .map(|age| age >= self.config.grace_period)
+                    .unwrap_or(false)
+            })
+            .map(|n| Orphan::Node {
+                hostname: n.hostname.clone(),
+            });
+
+        let node_hostname_suffix = format!(".{}", self.node_hostname_suffix);
+        let orphaned_records = records
+            .iter()
+            .filter(|r| r.hostname.ends_with(&node_hostname_suffix))
+            .filter(|r| !node_hostnames.contains(r.hostname.as_str()))
+            .map(|r| Orphan::DnsRecords {
+                hostname: r.hostname.clone(),
+            });
+
+        orphaned_nodes.chain(orphaned_records).collect()
+    }
+
+    async fn remove_orphan(&self, orphan: &Orphan, nodes: &[CloudNodeInfo]) -> bool {
+        let result = match orphan {
+            Orphan::Node { hostname } => {
+                let node_info = match nodes.iter().find(|n| &n.hostname == hostname) {
+                    Some(v) => v.clone(),
+                    None => return true,
+                };
+
+                info!(%hostname, "Delete orphaned node");
+                call!(self.cloud_provider.delete_node(node_info)).await
+            }
+            Orphan::DnsRecords { hostname } => {
+                info!(%hostname, "Delete orphaned dns records");
+                call!(self.dns_provider.delete_records(hostname.clone())).await
+            }
+        };
+
+        match result {
+            Ok(_) => true,
+            Err(e) => {
+                error!(
+                    orphan = format!("{:?}", orphan).as_str(),
+                    error = format!("{:?}", e).as_str(),
+                    "Failed to remove orphan"
+                );
+
+                false
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Actor for NodeReconciliation {
+    #[tracing::instrument(name = "NodeReconciliation::started", skip(self, addr))]
+    async fn started(&mut self, addr: Addr<Self>) -> ActorResult<()>
+    where
+        Self: Sized,
+    {
+        info!("Started");
+
+        self.addr = addr.downgrade();
+        self.timer
+            .set_interval_weak(self.addr.clone(), self.config.interval);
+
+        Produces::ok(())
+    }
+
+    async fn error(&mut self, error: ActorError) -> bool {
+        actor::handle_error(error)
+    }
+}
+
+#[async_trait]
+impl Tick for NodeReconciliation {
+    async fn tick(&mut self) -> ActorResult<()> {
+        if self.timer.tick() {
+            send!(self.addr.reconcile());
+        }
+
+        Produces::ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::discovery::{NodeDiscoveryData, NodeDiscoveryState};
+    use crate::node::QuarantineReason;
+    use std::time::Duration;
+
+    fn reconciliation(grace_period: Duration, dry_run: bool) -> NodeReconciliation {
+        NodeReconciliation::new(
+            Addr::detached(),
+            Addr::detached(),
+            Addr::detached(),
+            Addr::detached(),
+            "nodes.example.com".to_owned(),
+            config::NodeReconciliation {
+                interval: Duration::from_secs(60),
+                grace_period,
+                dry_run,
+            },
+        )
+    }
+
+    fn node(hostname: &str, group: &str, age: chrono::Duration) -> CloudNodeInfo {
+        CloudNodeInfo {
+            identifier: hostname.to_owned(),
+            hostname: hostname.to_owned(),
+            group: group.to_owned(),
+            created_at: Utc::now() - age,
+            ip_addresses: vec![],
+            private_ip_addresses: vec![],
+            location: None,
+            server_type: None,
+            image: None,
+            quarantine: None,
+        }
+    }
+
+    fn discovery(hostname: &str, group: &str) -> NodeDiscoveryData {
+        NodeDiscoveryData {
+            hostname: hostname.to_owned(),
+            group: group.to_owned(),
+            state: NodeDiscoveryState::Active,
+            datacenter: None,
+        }
+    }
+
+    fn record(hostname: &str) -> DnsRecordInfo {
+        DnsRecordInfo {
+            hostname: hostname.to_owned(),
+            record_type: "A".to_owned(),
+            value: "10.0.0.1".to_owned(),
+        }
+    }
+
+    fn node_orphan(hostname: &str) -> Orphan {
+        Orphan::Node {
+            hostname: hostname.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_find_orphans_of_unmanaged_groups() {
+        let reconciliation = reconciliation(Duration::from_secs(3600), false);
+        let day = chrono::Duration::days(1);
+
+        let mut quarantined = node("d.nodes.example.com", "old", day);
+        quarantined.quarantine = Some(QuarantineStatus::Quarantined {
+            reason: QuarantineReason::DiscoveryTimeout,
+            since: None,
+        });
+
+        let nodes = vec![
+            node("a.nodes.example.com", "edge", day),
+            node("b.nodes.example.com", "old", day),
+            node("c.nodes.example.com", "old", day),
+            quarantined,
+            node("e.nodes.example.com", "old", chrono::Duration::minutes(5)),
+        ];
+        let discoveries = NodeDiscoveries::complete(vec![discovery("c.nodes.example.com", "old")]);
+        let records = vec![
+            record("a.nodes.example.com"),
+            record("f.nodes.example.com"),
+            record("f.other.example.com"),
+        ];
+        let managed_groups = vec!["edge".to_owned()].into_iter().collect();
+
+        let orphans = reconciliation.find_orphans(&managed_groups, &discoveries, &nodes, &records);
+
+        let expected = vec![
+            node_orphan("b.nodes.example.com"),
+            Orphan::DnsRecords {
+                hostname: "f.nodes.example.com".to_owned(),
+            },
+        ]
+        .into_iter()
+        .collect::<HashSet<Orphan>>();
+        assert_eq!(expected, orphans);
+    }
+
+    #[test]
+    fn test_find_no_orphaned_nodes_in_partial_discoveries() {
+        let reconciliation = reconciliation(Duration::from_secs(3600), false);
+        let nodes = vec![node(
+            "b.nodes.example.com",
+            "old",
+            chrono::Duration::days(1),
+        )];
+        let discoveries = NodeDiscoveries {
+            nodes: vec![],
+            failed_datacenters: vec![Some("dc2".to_owned())],
+        };
+
+        let orphans = reconciliation.find_orphans(&HashSet::new(), &discoveries, &nodes, &[]);
+
+        assert!(orphans.is_empty());
+    }
+
+    #[test]
+    fn test_orphans_are_kept_for_the_grace_period() {
+        let mut reconciliation = reconciliation(Duration::from_secs(3600), false);
+        let orphans = vec![node_orphan("b.nodes.example.com")]
+            .into_iter()
+            .collect::<HashSet<Orphan>>();
+
+        assert!(reconciliation.expire_orphans(orphans.clone()).is_empty());
+        assert!(reconciliation
+            .orphans
+            .contains_key(&node_orphan("b.nodes.example.com")));
+
+        // adopted orphans are forgotten
+        assert!(reconciliation.expire_orphans(HashSet::new()).is_empty());
+        assert!(reconciliation.orphans.is_empty());
+    }
+
+    #[test]
+    fn test_expired_orphans_are_removed() {
+        let mut reconciliation = reconciliation(Duration::from_secs(0), false);
+        let orphans = vec![node_orphan("b.nodes.example.com")]
+            .into_iter()
+            .collect::<HashSet<Orphan>>();
+
+        assert_eq!(
+            vec![node_orphan("b.nodes.example.com")],
+            reconciliation.expire_orphans(orphans)
+        );
+    }
+
+    #[test]
+    fn test_expired_orphans_are_only_reported_in_dry_run() {
+        let mut reconciliation = reconciliation(Duration::from_secs(0), true);
+        let orphans = vec![node_orphan("b.nodes.example.com")]
+            .into_iter()
+            .collect::<HashSet<Orphan>>();
+
+        assert!(reconciliation.expire_orphans(orphans).is_empty());
+        assert!(reconciliation
+            .orphans
+            .contains_key(&node_orphan("b.nodes.example.com")));
+    }
+}
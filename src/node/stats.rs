@@ -11,10 +11,16 @@ pub use file::FileNodeStatsStream;
 pub use file::FileNodeStatsStreamFactory;
 pub use nss::NSSStreamFactory;
 use std::fs;
+use std::net::IpAddr;
 use std::pin::Pin;
 
 pub trait NodeStatsStreamFactory: Send + Sync + CloneNodeStatsStreamFactory + Debug {
-    fn create_stream(&self, hostname: String) -> Pin<Box<dyn Stream<Item = NodeStats> + Send>>;
+    /// The private address is only set if the node is attached to a private network
+    fn create_stream(
+        &self,
+        hostname: String,
+        private_address: Option<IpAddr>,
+    ) -> Pin<Box<dyn Stream<Item = NodeStats> + Send>>;
 }
 
 pub trait CloneNodeStatsStreamFactory {
@@ -40,15 +46,26 @@ pub fn build_stream_factory_from_config(
     config: AppConfig,
 ) -> anyhow::Result<Box<dyn NodeStatsStreamFactory>> {
     match &config.node_stats {
-        config::NodeStats::File { interval, path } => Ok(Box::new(
-            FileNodeStatsStreamFactory::new(path.clone(), *interval),
-        )),
-        config::NodeStats::NSS { tls, port } => Ok(Box::new(NSSStreamFactory::new(
+        config::NodeStats::File {
+            interval,
+            path,
+            watch,
+        } => Ok(Box::new(FileNodeStatsStreamFactory::new(
+            path.clone(),
+            *interval,
+            watch.clone(),
+        ))),
+        config::NodeStats::NSS {
+            tls,
+            port,
+            use_private_address,
+        } => Ok(Box::new(NSSStreamFactory::new(
             fs::read(&tls.ca_cert_path)?,
             fs::read(&tls.client_cert_path)?,
             fs::read(&tls.client_key_path)?,
             tls.target_sni_name.clone(),
             *port,
+            *use_private_address,
         ))),
     }
 }
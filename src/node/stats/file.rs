@@ -1,53 +1,195 @@
+use crate::config::FileWatch;
+use crate::file_watcher::{FileChange, FileWatcher};
 use crate::node::stats::NodeStatsStreamFactory;
 use crate::node::NodeStats;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::future::Future;
 use std::io::BufReader;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::Duration;
 use tokio::stream::Stream;
-use tracing::info;
+use tracing::{info, warn};
 
 #[derive(Clone, Debug)]
 pub struct FileNodeStatsStreamFactory {
     path: String,
     interval: Duration,
+    watch: Option<Arc<StatsDirectoryWatch>>,
 }
 
 impl FileNodeStatsStreamFactory {
-    pub fn new(path: String, interval: Duration) -> Self {
-        Self { path, interval }
+    pub fn new(path: String, interval: Duration, watch: Option<FileWatch>) -> Self {
+        let watch =
+            watch.and_then(
+                |watch| match StatsDirectoryWatch::new(path.as_ref(), watch.debounce) {
+                    Ok(v) => Some(Arc::new(v)),
+                    Err(e) => {
+                        warn!(
+                            error = format!("{:?}", e).as_str(),
+                            %path,
+                            "Failed to watch node stats directory, falling back to polling"
+                        );
+
+                        None
+                    }
+                },
+            );
+
+        Self {
+            path,
+            interval,
+            watch,
+        }
     }
 }
 
 impl NodeStatsStreamFactory for FileNodeStatsStreamFactory {
-    fn create_stream(&self, hostname: String) -> Pin<Box<dyn Stream<Item = NodeStats> + Send>> {
+    fn create_stream(
+        &self,
+        hostname: String,
+        _private_address: Option<IpAddr>,
+    ) -> Pin<Box<dyn Stream<Item = NodeStats> + Send>> {
         info!("Creating NodeStatsStream for {}", hostname);
         let filename = format!("{}.yml", hostname);
 
         let path: &Path = self.path.as_ref();
-        let path = path.join(filename);
+        let path = path.join(&filename);
 
-        Box::pin(FileNodeStatsStream::new(path))
+        let changes = self.watch.as_ref().map(|watch| watch.subscribe(&filename));
+
+        Box::pin(FileNodeStatsStream::new(path, self.interval, changes))
     }
 }
 
+type Subscribers = HashMap<PathBuf, Vec<mpsc::UnboundedSender<FileChange>>>;
+
+/// Single watch on the stats directory that fans the changes out to the streams by filename; the
+/// directory is watched instead of the files to survive atomic replacements of the stats files
+struct StatsDirectoryWatch {
+    /// Canonical path of the directory, the paths of reported changes are based on it
+    directory: PathBuf,
+    _watcher: FileWatcher,
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+impl StatsDirectoryWatch {
+    fn new(directory: &Path, debounce: Duration) -> anyhow::Result<Self> {
+        let directory = directory.canonicalize()?;
+        let subscribers = Arc::new(Mutex::new(Subscribers::new()));
+
+        let watcher = FileWatcher::new(&directory, debounce, {
+            let subscribers = Arc::clone(&subscribers);
+
+            move |change| publish_change(&mut subscribers.lock().unwrap(), change)
+        })?;
+
+        Ok(Self {
+            directory,
+            _watcher: watcher,
+            subscribers,
+        })
+    }
+
+    fn subscribe(&self, filename: &str) -> mpsc::UnboundedReceiver<FileChange> {
+        let (tx, rx) = mpsc::unbounded();
+
+        self.subscribers
+            .lock()
+            .unwrap()
+            .entry(self.directory.join(filename))
+            .or_default()
+            .push(tx);
+
+        rx
+    }
+}
+
+/// Sends the change to the subscribers of the changed file, dropped streams are cleaned up along
+/// the way
+fn publish_change(subscribers: &mut Subscribers, change: FileChange) {
+    match &change {
+        FileChange::Changed(path) => {
+            if let Some(senders) = subscribers.get_mut(path) {
+                senders.retain(|tx| tx.unbounded_send(change.clone()).is_ok());
+            }
+        }
+        FileChange::Removed(_) => {}
+        FileChange::Rescan => {
+            for senders in subscribers.values_mut() {
+                senders.retain(|tx| tx.unbounded_send(change.clone()).is_ok());
+            }
+        }
+    }
+
+    subscribers.retain(|_, senders| !senders.is_empty());
+}
+
+impl fmt::Debug for StatsDirectoryWatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StatsDirectoryWatch")
+    }
+}
+
+/// Reads the node stats file whenever the directory watch reports a change of it, or on every
+/// interval tick if it isn't watched
 pub struct FileNodeStatsStream {
     path: PathBuf,
     interval: tokio::time::Interval,
+    changes: Option<mpsc::UnboundedReceiver<FileChange>>,
+    initial_read: bool,
     read_stats_fut: Option<Pin<Box<dyn Future<Output = anyhow::Result<NodeStats>> + Send>>>,
 }
 
 impl FileNodeStatsStream {
-    pub fn new(path: impl AsRef<Path>) -> Self {
+    pub fn new(
+        path: impl AsRef<Path>,
+        interval: Duration,
+        changes: Option<mpsc::UnboundedReceiver<FileChange>>,
+    ) -> Self {
         Self {
             path: path.as_ref().into(),
-            interval: tokio::time::interval(Duration::from_secs(5)),
+            interval: tokio::time::interval(interval),
+            changes,
+            initial_read: true,
             read_stats_fut: None,
         }
     }
+
+    fn poll_trigger(&mut self, cx: &mut Context<'_>) -> bool {
+        let mut triggered = std::mem::replace(&mut self.initial_read, false);
+
+        if let Some(changes) = self.changes.as_mut() {
+            loop {
+                match changes.poll_next_unpin(cx) {
+                    Poll::Ready(Some(_)) => triggered = true,
+                    Poll::Ready(None) => {
+                        warn!(
+                            path = %self.path.display(),
+                            "Node stats watch ended, falling back to polling"
+                        );
+
+                        self.changes = None;
+                        break;
+                    }
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        if self.changes.is_none() && Pin::new(&mut self.interval).poll_next(cx).is_ready() {
+            triggered = true;
+        }
+
+        triggered
+    }
 }
 
 impl Stream for FileNodeStatsStream {
@@ -55,11 +197,10 @@ impl Stream for FileNodeStatsStream {
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         if self.read_stats_fut.is_none() {
-            match Pin::new(&mut self.interval).poll_next(cx) {
-                Poll::Ready(_) => {
-                    self.read_stats_fut = Some(Box::pin(parse_node_stats(self.path.clone())));
-                }
-                Poll::Pending => return Poll::Pending,
+            if self.poll_trigger(cx) {
+                self.read_stats_fut = Some(Box::pin(parse_node_stats(self.path.clone())));
+            } else {
+                return Poll::Pending;
             }
         }
 
@@ -93,3 +234,105 @@ async fn parse_node_stats(path: impl AsRef<Path>) -> anyhow::Result<NodeStats> {
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn stats_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "edge-auto-scaler-stats-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("edge-1.yml"), "tx_bps: 10\nrx_bps: 20\n").unwrap();
+
+        directory
+    }
+
+    #[test]
+    fn test_publish_change_fans_out_by_file() {
+        let mut subscribers = Subscribers::new();
+        let (tx_a, mut rx_a) = mpsc::unbounded();
+        let (tx_b, mut rx_b) = mpsc::unbounded();
+        let (tx_dropped, rx_dropped) = mpsc::unbounded();
+        drop(rx_dropped);
+
+        subscribers.insert(PathBuf::from("/stats/a.yml"), vec![tx_a]);
+        subscribers.insert(PathBuf::from("/stats/b.yml"), vec![tx_b]);
+        subscribers.insert(PathBuf::from("/stats/c.yml"), vec![tx_dropped]);
+
+        publish_change(
+            &mut subscribers,
+            FileChange::Changed(PathBuf::from("/stats/a.yml")),
+        );
+
+        assert!(matches!(rx_a.try_next(), Ok(Some(FileChange::Changed(_)))));
+        assert!(rx_b.try_next().is_err());
+
+        publish_change(&mut subscribers, FileChange::Rescan);
+
+        assert!(matches!(rx_a.try_next(), Ok(Some(FileChange::Rescan))));
+        assert!(matches!(rx_b.try_next(), Ok(Some(FileChange::Rescan))));
+        assert!(!subscribers.contains_key(Path::new("/stats/c.yml")));
+    }
+
+    #[test]
+    fn test_subscriptions_are_keyed_by_canonical_path() {
+        let directory = stats_directory("canonical");
+
+        let watch =
+            StatsDirectoryWatch::new(&directory.join("."), Duration::from_millis(10)).unwrap();
+        let _rx = watch.subscribe("edge-1.yml");
+
+        let expected = directory.canonicalize().unwrap().join("edge-1.yml");
+        assert!(watch.subscribers.lock().unwrap().contains_key(&expected));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watched_stream_only_reads_on_changes() {
+        let directory = stats_directory("watched");
+        let (tx, rx) = mpsc::unbounded();
+        let mut stream = FileNodeStatsStream::new(
+            directory.join("edge-1.yml"),
+            Duration::from_millis(10),
+            Some(rx),
+        );
+
+        // the initial read doesn't wait for a change
+        assert_eq!(10, stream.next().await.unwrap().tx_bps);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), stream.next())
+                .await
+                .is_err()
+        );
+
+        tx.unbounded_send(FileChange::Rescan).unwrap();
+        assert_eq!(20, stream.next().await.unwrap().rx_bps);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_falls_back_to_polling_when_the_watch_ends() {
+        let directory = stats_directory("fallback");
+        let (tx, rx) = mpsc::unbounded();
+        let mut stream = FileNodeStatsStream::new(
+            directory.join("edge-1.yml"),
+            Duration::from_millis(10),
+            Some(rx),
+        );
+
+        assert!(stream.next().await.is_some());
+
+        drop(tx);
+        let polled = tokio::time::timeout(Duration::from_secs(1), stream.next()).await;
+        assert!(matches!(polled, Ok(Some(_))));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
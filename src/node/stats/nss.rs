@@ -2,6 +2,7 @@ use crate::node::stats::NodeStatsStreamFactory;
 use crate::node::NodeStats;
 use async_stream::stream;
 use http::Uri;
+use std::net::IpAddr;
 use std::pin::Pin;
 use std::time::Duration;
 use tokio::stream::Stream;
@@ -19,6 +20,7 @@ use grpc::{node_stats_service_client::NodeStatsServiceClient, LiveNodeStatsReque
 pub struct NSSStreamFactory {
     client_tls_config: ClientTlsConfig,
     service_port: u16,
+    use_private_address: bool,
 }
 
 impl NSSStreamFactory {
@@ -28,6 +30,7 @@ impl NSSStreamFactory {
         client_key: Vec<u8>,
         sni_domain_name: String,
         service_port: u16,
+        use_private_address: bool,
     ) -> Self {
         Self {
             client_tls_config: ClientTlsConfig::new()
@@ -35,16 +38,38 @@ impl NSSStreamFactory {
                 .ca_certificate(Certificate::from_pem(ca_cert))
                 .identity(Identity::from_pem(client_cert, client_key)),
             service_port,
+            use_private_address,
+        }
+    }
+}
+
+impl NSSStreamFactory {
+    /// The sni name is configured explicitly, so connecting by address keeps tls intact
+    fn authority(&self, hostname: &str, private_address: Option<IpAddr>) -> String {
+        match private_address {
+            Some(IpAddr::V4(ip)) if self.use_private_address => {
+                format!("{}:{}", ip, self.service_port)
+            }
+            Some(IpAddr::V6(ip)) if self.use_private_address => {
+                format!("[{}]:{}", ip, self.service_port)
+            }
+            _ => format!("{}:{}", hostname, self.service_port),
         }
     }
 }
 
 impl NodeStatsStreamFactory for NSSStreamFactory {
-    fn create_stream(&self, hostname: String) -> Pin<Box<dyn Stream<Item = NodeStats> + Send>> {
+    fn create_stream(
+        &self,
+        hostname: String,
+        private_address: Option<IpAddr>,
+    ) -> Pin<Box<dyn Stream<Item = NodeStats> + Send>> {
         let client_tls_config = self.client_tls_config.clone();
+        let authority = self.authority(&hostname, private_address);
+
         let uri = Uri::builder()
             .scheme("https")
-            .authority(format!("{}:{}", hostname, self.service_port).as_str())
+            .authority(authority.as_str())
             .path_and_query("")
             .build()
             .unwrap();
@@ -118,3 +143,47 @@ impl NodeStatsStreamFactory for NSSStreamFactory {
         Box::pin(stream.instrument(tracing_span))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn factory(use_private_address: bool) -> NSSStreamFactory {
+        NSSStreamFactory::new(
+            vec![],
+            vec![],
+            vec![],
+            "nss.example.com".to_owned(),
+            8443,
+            use_private_address,
+        )
+    }
+
+    #[test]
+    fn test_authority_prefers_private_address_if_enabled() {
+        let factory = factory(true);
+
+        assert_eq!(
+            "10.0.0.2:8443",
+            factory.authority("edge-1.example.com", Some("10.0.0.2".parse().unwrap()))
+        );
+        assert_eq!(
+            "[fd00::2]:8443",
+            factory.authority("edge-1.example.com", Some("fd00::2".parse().unwrap()))
+        );
+        assert_eq!(
+            "edge-1.example.com:8443",
+            factory.authority("edge-1.example.com", None)
+        );
+    }
+
+    #[test]
+    fn test_authority_uses_hostname_by_default() {
+        let factory = factory(false);
+
+        assert_eq!(
+            "edge-1.example.com:8443",
+            factory.authority("edge-1.example.com", Some("10.0.0.2".parse().unwrap()))
+        );
+    }
+}
@@ -5,7 +5,7 @@ use crate::dns_provider::DnsProvider;
 use crate::node::discovery::{NodeDiscoveryData, NodeDiscoveryObserver, NodeDiscoveryProvider};
 use crate::node::exploration::NodeExplorationObserver;
 use crate::node::stats::NodeStatsStreamFactory;
-use crate::node::HostnameGenerator;
+use crate::node::{HostnameGenerator, LifecycleHooks};
 use crate::node_groups::controller::state_machine::{Event, NodeGroupMachine};
 use crate::node_groups::discovery::NodeGroupDiscoveryObserver;
 use crate::node_groups::scaler::NodeGroupScaler;
@@ -13,9 +13,9 @@ use crate::node_groups::NodeGroup;
 use crate::{actor, AppConfig};
 use act_zero::runtimes::tokio::Timer;
 use act_zero::timer::Tick;
-use act_zero::{send, Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
+use act_zero::{call, send, Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -37,6 +37,7 @@ pub struct NodeGroupsController {
     dns_provider: Addr<dyn DnsProvider>,
     node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
     hostname_generator: Arc<dyn HostnameGenerator>,
+    lifecycle_hooks: Arc<LifecycleHooks>,
     config: AppConfig,
 }
 
@@ -47,6 +48,7 @@ impl NodeGroupsController {
         dns_provider: Addr<dyn DnsProvider>,
         node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
         hostname_generator: Arc<dyn HostnameGenerator>,
+        lifecycle_hooks: Arc<LifecycleHooks>,
         config: AppConfig,
     ) -> Self {
         NodeGroupsController {
@@ -58,6 +60,7 @@ impl NodeGroupsController {
             dns_provider,
             node_stats_stream_factory,
             hostname_generator,
+            lifecycle_hooks,
             config,
         }
     }
@@ -113,7 +116,7 @@ impl NodeGroupDiscoveryObserver for NodeGroupsController {
     #[tracing::instrument(
         name = "NodeGroupsController::observe_node_group_discovery",
         skip(self, node_group),
-        fields(group = %node_group.name),
+        fields(group = %node_group.name, source = %node_group.source),
     )]
     async fn observe_node_group_discovery(&mut self, node_group: NodeGroup) {
         match self.node_groups.get_mut(&node_group.name) {
@@ -139,6 +142,7 @@ impl NodeGroupDiscoveryObserver for NodeGroupsController {
                                 self.dns_provider.clone(),
                                 self.node_stats_stream_factory.clone(),
                                 Arc::clone(&self.hostname_generator),
+                                Arc::clone(&self.lifecycle_hooks),
                                 Arc::clone(&self.config),
                             ),
                             Some(state_machine::Event::Initialize),
@@ -149,6 +153,20 @@ impl NodeGroupDiscoveryObserver for NodeGroupsController {
             }
         }
     }
+
+    #[tracing::instrument(
+        name = "NodeGroupsController::observe_node_group_removal",
+        skip(self, group),
+        fields(group = %group),
+    )]
+    async fn observe_node_group_removal(&mut self, group: String) {
+        if let Some(ngmo) = self.node_groups.get_mut(&group) {
+            info!("Node group was removed");
+
+            let ngm = ngmo.take().unwrap();
+            *ngmo = Some(ngm.handle(Some(state_machine::Event::Discard)).await);
+        }
+    }
 }
 
 #[async_trait]
@@ -220,6 +238,29 @@ impl NodeExplorationObserver for NodeGroupsController {
 }
 
 impl NodeGroupsController {
+    /// Names of all node groups that are still configured and take care of their nodes
+    #[tracing::instrument(name = "NodeGroupsController::get_managed_groups", skip(self))]
+    pub async fn get_managed_groups(&mut self) -> ActorResult<HashSet<String>> {
+        let scalers = self
+            .node_groups
+            .iter()
+            .filter_map(|(group, ngmo)| {
+                ngmo.as_ref()
+                    .and_then(|ngm| ngm.scaler())
+                    .map(|scaler| (group.clone(), scaler.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        let mut groups = HashSet::new();
+        for (group, scaler) in scalers {
+            if call!(scaler.is_configured()).await? {
+                groups.insert(group);
+            }
+        }
+
+        Produces::ok(groups)
+    }
+
     #[tracing::instrument(name = "NodeGroupsController::process_node_groups", skip(self))]
     async fn process_node_groups(&mut self) {
         for ngmo in self.node_groups.values_mut() {
@@ -243,6 +284,7 @@ impl NodeGroupsController {
         let node_group = NodeGroup {
             name: group_name.as_ref().into(),
             config: None,
+            source: String::from("node-discovery"),
         };
 
         let ngm = NodeGroupMachine::new(
@@ -252,6 +294,7 @@ impl NodeGroupsController {
             self.dns_provider.clone(),
             self.node_stats_stream_factory.clone(),
             Arc::clone(&self.hostname_generator),
+            Arc::clone(&self.lifecycle_hooks),
             Arc::clone(&self.config),
         );
 
@@ -3,7 +3,7 @@ use crate::dns_provider::DnsProvider;
 use crate::node::discovery::{NodeDiscoveryData, NodeDiscoveryObserver, NodeDiscoveryProvider};
 use crate::node::exploration::NodeExplorationObserver;
 use crate::node::stats::NodeStatsStreamFactory;
-use crate::node::HostnameGenerator;
+use crate::node::{HostnameGenerator, LifecycleHooks};
 use crate::node_groups::scaler::NodeGroupScaler;
 use crate::node_groups::NodeGroup;
 use crate::AppConfig;
@@ -42,6 +42,7 @@ pub struct Shared {
     dns_provider: Addr<dyn DnsProvider>,
     node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
     hostname_generator: Arc<dyn HostnameGenerator>,
+    lifecycle_hooks: Arc<LifecycleHooks>,
     config: AppConfig,
 }
 
@@ -65,6 +66,7 @@ impl Handler for Data<Initializing> {
                     self.shared.dns_provider.clone(),
                     self.shared.node_stats_stream_factory.clone(),
                     Arc::clone(&self.shared.hostname_generator),
+                    Arc::clone(&self.shared.lifecycle_hooks),
                     Arc::clone(&self.shared.config),
                 ));
 
@@ -208,6 +210,7 @@ impl NodeGroupMachine {
         dns_provider: Addr<dyn DnsProvider>,
         node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
         hostname_generator: Arc<dyn HostnameGenerator>,
+        lifecycle_hooks: Arc<LifecycleHooks>,
         config: AppConfig,
     ) -> Self {
         Self::Initializing(Data {
@@ -218,6 +221,7 @@ impl NodeGroupMachine {
                 dns_provider,
                 node_stats_stream_factory,
                 hostname_generator,
+                lifecycle_hooks,
                 config,
             },
             state: Initializing,
@@ -232,4 +236,12 @@ impl NodeGroupMachine {
             Self::Discarded(_) => self,
         }
     }
+
+    pub fn scaler(&self) -> Option<&Addr<NodeGroupScaler>> {
+        match self {
+            Self::Running(m) => Some(&m.state.scaler),
+            Self::Discarding(m) => Some(&m.state.scaler),
+            _ => None,
+        }
+    }
 }
@@ -1,62 +1,321 @@
 pub mod provider;
 
-use crate::node_groups::discovery::provider::NodeGroupDiscoveryProvider;
-use crate::node_groups::NodeGroup;
+use crate::node_groups::{Config, NodeGroup};
 use act_zero::runtimes::tokio::Timer;
 use act_zero::timer::Tick;
-use act_zero::{call, send, ActorError};
+use act_zero::{call, send, upcast, ActorError};
 use act_zero::{Actor, ActorResult, Addr, Produces, WeakAddr};
 use async_trait::async_trait;
+use opentelemetry::api::metrics::UpDownCounter;
+use opentelemetry::global;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::HashMap;
 use std::time::Duration;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::actor;
 pub use provider::file::FileNodeGroupDiscovery;
+pub use provider::DiscoverySource;
 
 #[async_trait]
 pub trait NodeGroupDiscoveryObserver: Actor {
     async fn observe_node_group_discovery(&mut self, node_group: NodeGroup);
+    async fn observe_node_group_removal(&mut self, group: String);
 }
 
+/// Receives the node group definitions that providers push on their own
+#[async_trait]
+pub trait NodeGroupDefinitionObserver: Actor {
+    async fn observe_node_group_definition(
+        &mut self,
+        source: usize,
+        definition: NodeGroupDefinition,
+    );
+    async fn observe_node_group_definition_removal(&mut self, source: usize, group: String);
+}
+
+/// Node group as defined by a single discovery source; the config may be partial and is only
+/// deserialized after the definitions of all sources were merged
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct NodeGroupDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub config: Option<Value>,
+}
+
+/// Merges the node group definitions of all discovery sources by priority and forwards the
+/// resulting node groups to the observer
 pub struct NodeGroupDiscovery {
-    providers: Vec<Addr<dyn NodeGroupDiscoveryProvider>>,
+    sources: Vec<DiscoverySource>,
     observer: Addr<dyn NodeGroupDiscoveryObserver>,
     interval: Duration,
+    /// Definitions per group name and source index
+    definitions: HashMap<String, HashMap<usize, NodeGroupDefinition>>,
+    /// Last reported conflicts per group name, to warn only once per change
+    conflicts: HashMap<String, Vec<String>>,
+    /// Last published node group per group name that passed validation
+    known_good: HashMap<String, NodeGroup>,
+    /// Last reported validation errors per group name, to log only once per change
+    validation_errors: HashMap<String, Vec<String>>,
+    /// Number of node groups with validation errors
+    invalid_node_groups: UpDownCounter<i64>,
     timer: Timer,
     addr: WeakAddr<Self>,
 }
 
 impl NodeGroupDiscovery {
     pub fn new(
-        providers: Vec<Addr<dyn NodeGroupDiscoveryProvider>>,
+        sources: Vec<DiscoverySource>,
         observer: Addr<dyn NodeGroupDiscoveryObserver>,
         interval: Duration,
     ) -> Self {
         Self {
-            providers,
+            sources,
             observer,
             interval,
+            definitions: HashMap::new(),
+            conflicts: HashMap::new(),
+            known_good: HashMap::new(),
+            validation_errors: HashMap::new(),
+            invalid_node_groups: global::meter("edge-auto-scaler")
+                .i64_up_down_counter("node_groups.invalid")
+                .with_description("Node groups whose config fails validation")
+                .init(),
             timer: Default::default(),
             addr: Default::default(),
         }
     }
 
+    #[tracing::instrument(name = "NodeGroupDiscovery::discover", skip(self))]
     async fn discover(&mut self) {
-        for provider in self.providers.iter() {
-            let discoveries = call!(provider.discover_node_groups()).await;
+        let mut results = Vec::with_capacity(self.sources.len());
+        for source in self.sources.iter() {
+            results.push(call!(source.provider.discover_node_groups()).await);
+        }
 
-            match discoveries {
-                Ok(discoveries) => {
-                    for discovery in discoveries {
-                        send!(self.observer.observe_node_group_discovery(discovery));
-                    }
-                }
+        for (source, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(definitions) => self.replace_definitions(source, definitions),
+                // keep the previous definitions of the source until it recovers
                 Err(e) => error!(
+                    source = self.sources[source].name.as_str(),
                     error = format!("{:?}", e).as_str(),
-                    "Failed to discovery node groups"
+                    "Failed to discover node groups"
                 ),
             }
         }
+
+        let groups = self.definitions.keys().cloned().collect::<Vec<String>>();
+        for group in groups {
+            self.publish(&group);
+        }
+    }
+
+    fn replace_definitions(&mut self, source: usize, definitions: Vec<NodeGroupDefinition>) {
+        for source_definitions in self.definitions.values_mut() {
+            source_definitions.remove(&source);
+        }
+
+        for definition in definitions {
+            self.definitions
+                .entry(definition.name.clone())
+                .or_default()
+                .insert(source, definition);
+        }
+
+        let removed_groups = self
+            .definitions
+            .iter()
+            .filter(|(_, source_definitions)| source_definitions.is_empty())
+            .map(|(group, _)| group.clone())
+            .collect::<Vec<String>>();
+
+        for group in removed_groups {
+            info!(%group, "Node group is no longer defined by any source");
+            self.remove_group(group);
+        }
+    }
+
+    /// Forgets everything about a group that is no longer defined by any source
+    fn remove_group(&mut self, group: String) {
+        self.definitions.remove(&group);
+        self.conflicts.remove(&group);
+        self.known_good.remove(&group);
+        self.clear_validation_errors(&group);
+        send!(self.observer.observe_node_group_removal(group));
+    }
+
+    #[tracing::instrument(name = "NodeGroupDiscovery::publish", skip(self))]
+    fn publish(&mut self, group: &str) {
+        let source_definitions = match self.definitions.get(group) {
+            Some(v) => v,
+            None => return,
+        };
+
+        // apply the definitions from the lowest to the highest priority
+        let mut ordered_definitions = source_definitions
+            .iter()
+            .map(|(source, definition)| (*source, definition))
+            .collect::<Vec<(usize, &NodeGroupDefinition)>>();
+        ordered_definitions.sort_by_key(|(source, _)| (self.sources[*source].priority, *source));
+
+        let mut merged_config: Option<Value> = None;
+        let mut conflicts = vec![];
+        let mut source_names = vec![];
+
+        for (source, definition) in ordered_definitions {
+            let source_name = self.sources[source].name.as_str();
+            source_names.insert(0, source_name);
+
+            if let Some(config) = definition.config.as_ref() {
+                merged_config = Some(match merged_config {
+                    None => config.clone(),
+                    Some(base) => {
+                        let mut source_conflicts = vec![];
+                        let merged = overlay(base, config, "", &mut source_conflicts);
+
+                        conflicts.extend(
+                            source_conflicts
+                                .into_iter()
+                                .map(|field| format!("{} overridden by {}", field, source_name)),
+                        );
+
+                        merged
+                    }
+                });
+            }
+        }
+
+        let source = source_names.join(", ");
+
+        if self.conflicts.get(group) != Some(&conflicts) {
+            if !conflicts.is_empty() {
+                warn!(
+                    %source,
+                    conflicts = format!("{:?}", conflicts).as_str(),
+                    "Node group definitions of multiple sources conflict"
+                );
+            }
+
+            self.conflicts.insert(group.to_owned(), conflicts);
+        }
+
+        let config = match merged_config
+            .map(serde_yaml::from_value::<Config>)
+            .transpose()
+        {
+            Ok(v) => v,
+            Err(e) => {
+                self.reject(group, &source, vec![e.to_string()]);
+                return;
+            }
+        };
+
+        if let Some(Err(errors)) = config.as_ref().map(Config::validate) {
+            let errors = errors.iter().map(|e| e.to_string()).collect();
+            self.reject(group, &source, errors);
+            return;
+        }
+
+        if self.clear_validation_errors(group) {
+            info!(%source, "Node group config is valid again");
+        }
+
+        let node_group = NodeGroup {
+            name: group.to_owned(),
+            config,
+            source,
+        };
+
+        self.known_good.insert(group.to_owned(), node_group.clone());
+        send!(self.observer.observe_node_group_discovery(node_group));
+    }
+
+    /// Reports the validation errors of the group and keeps the last known-good definition alive
+    /// so the group does not run into its discovery timeout
+    fn reject(&mut self, group: &str, source: &str, errors: Vec<String>) {
+        let known_good = self.known_good.get(group);
+
+        if self.validation_errors.get(group) != Some(&errors) {
+            for e in errors.iter() {
+                error!(%source, error = e.as_str(), "Invalid node group config");
+            }
+
+            match known_good {
+                Some(_) => warn!(%source, "Keeping last known-good node group config"),
+                None => warn!(%source, "Ignoring node group without a valid config"),
+            }
+
+            if self
+                .validation_errors
+                .insert(group.to_owned(), errors)
+                .is_none()
+            {
+                self.invalid_node_groups.add(1, &[]);
+            }
+        }
+
+        if let Some(node_group) = known_good {
+            send!(self
+                .observer
+                .observe_node_group_discovery(node_group.clone()));
+        }
+    }
+
+    fn clear_validation_errors(&mut self, group: &str) -> bool {
+        let cleared = self.validation_errors.remove(group).is_some();
+        if cleared {
+            self.invalid_node_groups.add(-1, &[]);
+        }
+
+        cleared
+    }
+}
+
+#[async_trait]
+impl NodeGroupDefinitionObserver for NodeGroupDiscovery {
+    #[tracing::instrument(
+        name = "NodeGroupDiscovery::observe_node_group_definition",
+        skip(self, definition),
+        fields(group = %definition.name, source = self.sources[source].name.as_str())
+    )]
+    async fn observe_node_group_definition(
+        &mut self,
+        source: usize,
+        definition: NodeGroupDefinition,
+    ) {
+        let group = definition.name.clone();
+
+        self.definitions
+            .entry(group.clone())
+            .or_default()
+            .insert(source, definition);
+
+        self.publish(&group);
+    }
+
+    #[tracing::instrument(
+        name = "NodeGroupDiscovery::observe_node_group_definition_removal",
+        skip(self),
+        fields(source = self.sources[source].name.as_str())
+    )]
+    async fn observe_node_group_definition_removal(&mut self, source: usize, group: String) {
+        let is_defined_elsewhere = match self.definitions.get_mut(&group) {
+            Some(source_definitions) => {
+                source_definitions.remove(&source);
+                !source_definitions.is_empty()
+            }
+            None => return,
+        };
+
+        if is_defined_elsewhere {
+            self.publish(&group);
+        } else {
+            info!("Node group is no longer defined by any source");
+
+            self.remove_group(group);
+        }
     }
 }
 
@@ -77,6 +336,12 @@ impl Actor for NodeGroupDiscovery {
         self.timer
             .set_interval_weak(self.addr.clone(), self.interval);
 
+        for (source, discovery_source) in self.sources.iter().enumerate() {
+            send!(discovery_source
+                .provider
+                .watch_node_groups(source, upcast!(addr.clone())));
+        }
+
         Produces::ok(())
     }
 
@@ -95,3 +360,141 @@ impl Tick for NodeGroupDiscovery {
         Produces::ok(())
     }
 }
+
+/// Overlays the given value onto the base, mappings are merged key by key while everything else
+/// gets replaced; overridden fields with differing values are collected as conflicts
+fn overlay(base: Value, overlay_value: &Value, path: &str, conflicts: &mut Vec<String>) -> Value {
+    match (base, overlay_value) {
+        (Value::Mapping(mut base), Value::Mapping(overlay_mapping)) => {
+            for (key, value) in overlay_mapping.iter() {
+                let field_path = match key.as_str() {
+                    Some(key) if path.is_empty() => key.to_owned(),
+                    Some(key) => format!("{}.{}", path, key),
+                    None => format!("{}.{:?}", path, key),
+                };
+
+                let merged = match base.remove(key) {
+                    Some(base_value) => overlay(base_value, value, &field_path, conflicts),
+                    None => value.clone(),
+                };
+
+                base.insert(key.clone(), merged);
+            }
+
+            Value::Mapping(base)
+        }
+        (base, overlay_value) => {
+            if &base != overlay_value {
+                conflicts.push(path.to_owned());
+            }
+
+            overlay_value.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_overlay_merges_mappings_field_by_field() {
+        let base = yaml("a: 1\nb:\n  c: 2\n  d: 3\n");
+        let overlay_value = yaml("b:\n  d: 4\ne: 5\n");
+
+        let mut conflicts = vec![];
+        let merged = overlay(base, &overlay_value, "", &mut conflicts);
+
+        assert_eq!(yaml("a: 1\nb:\n  c: 2\n  d: 4\ne: 5\n"), merged);
+        assert_eq!(vec!["b.d".to_owned()], conflicts);
+    }
+
+    fn discovery() -> NodeGroupDiscovery {
+        let source = |name: &str, priority| DiscoverySource {
+            name: name.to_owned(),
+            priority,
+            provider: Addr::detached(),
+        };
+
+        NodeGroupDiscovery::new(
+            vec![source("base", 0), source("overlay", 1)],
+            Addr::detached(),
+            Duration::from_secs(30),
+        )
+    }
+
+    fn definition(config: &str) -> NodeGroupDefinition {
+        NodeGroupDefinition {
+            name: "edge".to_owned(),
+            config: Some(yaml(config)),
+        }
+    }
+
+    fn known_good_tx_bps(discovery: &NodeGroupDiscovery) -> Option<u64> {
+        discovery
+            .known_good
+            .get("edge")
+            .and_then(|node_group| node_group.config.as_ref())
+            .map(|config| config.node_bandwidth_capacity.tx_bps)
+    }
+
+    #[test]
+    fn test_invalid_overlay_keeps_last_known_good_group() {
+        let mut discovery = discovery();
+
+        discovery.replace_definitions(
+            0,
+            vec![definition(
+                "node_bandwidth_capacity:\n  tx_bps: 1000\n  rx_bps: 1000\n",
+            )],
+        );
+        discovery.publish("edge");
+
+        assert_eq!(Some(1000), known_good_tx_bps(&discovery));
+
+        discovery.replace_definitions(
+            1,
+            vec![definition("node_bandwidth_capacity:\n  tx_bps: 0\n")],
+        );
+        discovery.publish("edge");
+
+        assert_eq!(Some(1000), known_good_tx_bps(&discovery));
+        assert!(discovery.validation_errors.contains_key("edge"));
+    }
+
+    #[test]
+    fn test_replace_definitions_forgets_groups_without_definitions() {
+        let mut discovery = discovery();
+
+        discovery.replace_definitions(
+            0,
+            vec![definition(
+                "node_bandwidth_capacity:\n  tx_bps: 0\n  rx_bps: 0\n",
+            )],
+        );
+        discovery.publish("edge");
+
+        assert!(discovery.validation_errors.contains_key("edge"));
+
+        discovery.replace_definitions(0, vec![]);
+
+        assert!(discovery.definitions.is_empty());
+        assert!(discovery.known_good.is_empty());
+        assert!(discovery.validation_errors.is_empty());
+    }
+
+    #[test]
+    fn test_overlay_ignores_equal_values() {
+        let base = yaml("a: 1\n");
+        let overlay_value = yaml("a: 1\n");
+
+        let mut conflicts = vec![];
+        overlay(base, &overlay_value, "", &mut conflicts);
+
+        assert!(conflicts.is_empty());
+    }
+}
@@ -1,52 +1,145 @@
 use crate::config;
-use crate::node_groups::NodeGroup;
+use crate::node_groups::discovery::{NodeGroupDefinition, NodeGroupDefinitionObserver};
 use crate::AppConfig;
 use act_zero::runtimes::tokio::spawn_actor;
-use act_zero::{upcast, Actor, ActorResult, Addr};
+use act_zero::{upcast, Actor, ActorResult, Addr, Produces};
+use anyhow::bail;
 use async_trait::async_trait;
+use std::fs;
 
 pub mod consul;
 pub mod file;
+pub mod http;
 
 #[async_trait]
 pub trait NodeGroupDiscoveryProvider: Actor {
-    async fn discover_node_groups(&mut self) -> ActorResult<Vec<NodeGroup>>;
+    async fn discover_node_groups(&mut self) -> ActorResult<Vec<NodeGroupDefinition>>;
+
+    /// Starts pushing node group changes and removals to the observer as soon as they happen,
+    /// tagged with the given source index
+    async fn watch_node_groups(
+        &mut self,
+        _source: usize,
+        _observer: Addr<dyn NodeGroupDefinitionObserver>,
+    ) -> ActorResult<()> {
+        Produces::ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct DiscoverySource {
+    pub name: String,
+    pub priority: i32,
+    pub provider: Addr<dyn NodeGroupDiscoveryProvider>,
 }
 
-pub fn build_from_config(
-    config: AppConfig,
-) -> anyhow::Result<Vec<Addr<dyn NodeGroupDiscoveryProvider>>> {
-    let mut providers = Vec::with_capacity(config.node_group_discovery_providers.len());
+pub fn build_from_config(config: AppConfig) -> anyhow::Result<Vec<DiscoverySource>> {
+    let mut sources = Vec::with_capacity(config.node_group_discovery_providers.len());
+
+    for source_config in config.node_group_discovery_providers.iter() {
+        let provider_config = &source_config.provider;
+
+        // consul sources are split up per datacenter
+        let datacenters: Vec<Option<&str>> = match provider_config {
+            config::NodeGroupDiscoveryProvider::Consul { connection, .. }
+                if !connection.datacenters.is_empty() =>
+            {
+                connection
+                    .datacenters
+                    .iter()
+                    .map(|dc| Some(dc.as_str()))
+                    .collect()
+            }
+            _ => vec![None],
+        };
 
-    for provider_config in config.node_group_discovery_providers.iter() {
-        providers.push(build_provider_from_config(&config, provider_config)?);
+        for datacenter in datacenters {
+            sources.push(DiscoverySource {
+                name: source_name(provider_config, datacenter),
+                priority: source_config.priority,
+                provider: build_provider_from_config(&config, provider_config, datacenter)?,
+            });
+        }
     }
 
-    Ok(providers)
+    Ok(sources)
+}
+
+fn source_name(
+    provider_config: &config::NodeGroupDiscoveryProvider,
+    datacenter: Option<&str>,
+) -> String {
+    let name = match provider_config {
+        config::NodeGroupDiscoveryProvider::File { path, .. } => format!("file:{}", path),
+        config::NodeGroupDiscoveryProvider::Consul { key_prefix, .. } => {
+            format!("consul:{}", key_prefix)
+        }
+        config::NodeGroupDiscoveryProvider::Http { url, .. } => format!("http:{}", url),
+    };
+
+    match datacenter {
+        Some(datacenter) => format!("{}@{}", name, datacenter),
+        None => name,
+    }
 }
 
 fn build_provider_from_config(
     _config: &AppConfig,
     provider_config: &config::NodeGroupDiscoveryProvider,
+    datacenter: Option<&str>,
 ) -> anyhow::Result<Addr<dyn NodeGroupDiscoveryProvider>> {
     Ok(match provider_config {
-        config::NodeGroupDiscoveryProvider::File { path } => {
-            upcast!(spawn_actor(file::FileNodeGroupDiscovery::new(path)))
-        }
+        config::NodeGroupDiscoveryProvider::File { path, watch } => upcast!(spawn_actor(
+            file::FileNodeGroupDiscovery::new(path, watch.clone())
+        )),
         config::NodeGroupDiscoveryProvider::Consul {
             key_prefix,
-            address,
+            connection,
+            watch,
         } => {
-            let consul_client = consul_api_client::Client::new(
-                consul_api_client::Config::builder()
-                    .address(address.into())
-                    .build()?,
-            )?;
+            let consul_client = crate::consul::build_client(connection, datacenter)?;
 
             upcast!(spawn_actor(consul::ConsulNodeGroupDiscovery::new(
                 consul_client,
                 key_prefix.into(),
+                watch.clone(),
             )))
         }
+        config::NodeGroupDiscoveryProvider::Http {
+            url,
+            bearer_token,
+            tls,
+        } => upcast!(spawn_actor(http::HttpNodeGroupDiscovery::new(
+            build_http_client(tls.as_ref())?,
+            url.parse()?,
+            bearer_token.clone(),
+        ))),
     })
 }
+
+fn build_http_client(tls: Option<&config::HttpTls>) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::ClientBuilder::new();
+
+    if let Some(tls) = tls {
+        builder = builder.use_rustls_tls();
+
+        match (tls.client_cert_path.as_ref(), tls.client_key_path.as_ref()) {
+            (Some(client_cert_path), Some(client_key_path)) => {
+                // the client identity expects certificate and private key in one pem bundle
+                let mut identity = fs::read(client_cert_path)?;
+                identity.extend(fs::read(client_key_path)?);
+
+                builder = builder.identity(reqwest::Identity::from_pem(&identity)?);
+            }
+            (None, None) => {}
+            _ => bail!("Client certificate and key have to be configured together"),
+        }
+
+        if let Some(ca_cert_path) = tls.ca_cert_path.as_ref() {
+            builder = builder
+                .add_root_certificate(reqwest::Certificate::from_pem(&fs::read(ca_cert_path)?)?);
+        }
+    }
+
+    Ok(builder.build()?)
+}
@@ -1,21 +1,38 @@
 use crate::actor;
+use crate::config::ConsulWatch;
+use crate::consul::next_consul_index;
 use crate::node_groups::discovery::provider::NodeGroupDiscoveryProvider;
-use crate::node_groups::NodeGroup;
-use act_zero::{Actor, ActorError, ActorResult, Addr, Produces};
+use crate::node_groups::discovery::{NodeGroupDefinition, NodeGroupDefinitionObserver};
+use act_zero::runtimes::tokio::spawn_actor;
+use act_zero::{send, Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
 use async_trait::async_trait;
-use consul_api_client::kv::KV;
-use tracing::{info, warn};
+use consul_api_client::kv::{KVPair, KV};
+use consul_api_client::QueryOptions;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Delay before a failed blocking query is retried
+const WATCH_RETRY_DELAY: Duration = Duration::from_secs(5);
 
 pub struct ConsulNodeGroupDiscovery {
     consul_client: consul_api_client::Client,
     key_prefix: String,
+    watch_config: Option<ConsulWatch>,
+    watch: Option<Addr<ConsulNodeGroupDiscoveryWatch>>,
 }
 
 impl ConsulNodeGroupDiscovery {
-    pub fn new(consul_client: consul_api_client::Client, key_prefix: String) -> Self {
+    pub fn new(
+        consul_client: consul_api_client::Client,
+        key_prefix: String,
+        watch_config: Option<ConsulWatch>,
+    ) -> Self {
         Self {
             consul_client,
             key_prefix,
+            watch_config,
+            watch: None,
         }
     }
 }
@@ -40,7 +57,7 @@ impl Actor for ConsulNodeGroupDiscovery {
 #[async_trait]
 impl NodeGroupDiscoveryProvider for ConsulNodeGroupDiscovery {
     #[tracing::instrument(name = "ConsulNodeGroupDiscovery::discover_node_groups", skip(self))]
-    async fn discover_node_groups(&mut self) -> ActorResult<Vec<NodeGroup>> {
+    async fn discover_node_groups(&mut self) -> ActorResult<Vec<NodeGroupDefinition>> {
         let (kv_pairs, _meta) = self
             .consul_client
             .list(&self.key_prefix, None)
@@ -48,34 +65,334 @@ impl NodeGroupDiscoveryProvider for ConsulNodeGroupDiscovery {
             .map_err(anyhow::Error::new)
             .map_err(actor::Error::from)?;
 
-        let node_groups: Vec<NodeGroup> = kv_pairs
-            .iter()
-            .filter_map(|kv_pair| match base64::decode(&kv_pair.Value) {
-                Ok(v) => Some((&kv_pair.Key, v)),
-                Err(e) => {
-                    warn!(
-                        error = format!("{:?}", e).as_str(),
-                        "Failed to decode base64 node group data"
-                    );
-                    None
-                }
-            })
-            .filter_map(|(key, data)| {
-                match serde_yaml::from_reader::<&[u8], NodeGroup>(data.as_ref()) {
-                    Ok(v) => Some(v),
-                    Err(e) => {
-                        warn!(
-                            error = format!("{:?}", e).as_str(),
-                            key = key.as_str(),
-                            "Failed to parse node group yaml"
-                        );
-
-                        None
+        let node_groups: Vec<NodeGroupDefinition> =
+            kv_pairs.iter().filter_map(parse_node_group).collect();
+
+        Produces::ok(node_groups)
+    }
+
+    #[tracing::instrument(
+        name = "ConsulNodeGroupDiscovery::watch_node_groups",
+        skip(self, observer)
+    )]
+    async fn watch_node_groups(
+        &mut self,
+        source: usize,
+        observer: Addr<dyn NodeGroupDefinitionObserver>,
+    ) -> ActorResult<()> {
+        let watch_config = match (&self.watch_config, &self.watch) {
+            (Some(watch_config), None) => watch_config,
+            _ => return Produces::ok(()),
+        };
+
+        info!("Start watching node groups");
+
+        self.watch = Some(spawn_actor(ConsulNodeGroupDiscoveryWatch::new(
+            self.consul_client.clone(),
+            self.key_prefix.clone(),
+            watch_config.wait_time,
+            source,
+            observer,
+        )));
+
+        Produces::ok(())
+    }
+}
+
+#[derive(Debug)]
+struct WatchedKey {
+    modify_index: Option<u64>,
+    group: Option<String>,
+}
+
+/// Runs consul blocking queries on the key prefix, re-parses only the keys that were modified
+/// since the last query and reports the groups of deleted keys as removed; keys that fail to
+/// parse keep their previous group
+pub struct ConsulNodeGroupDiscoveryWatch {
+    consul_client: consul_api_client::Client,
+    key_prefix: String,
+    wait_time: Duration,
+    source: usize,
+    observer: Addr<dyn NodeGroupDefinitionObserver>,
+    index: Option<u64>,
+    known_keys: HashMap<String, WatchedKey>,
+    addr: WeakAddr<Self>,
+}
+
+impl ConsulNodeGroupDiscoveryWatch {
+    fn new(
+        consul_client: consul_api_client::Client,
+        key_prefix: String,
+        wait_time: Duration,
+        source: usize,
+        observer: Addr<dyn NodeGroupDefinitionObserver>,
+    ) -> Self {
+        Self {
+            consul_client,
+            key_prefix,
+            wait_time,
+            source,
+            observer,
+            index: None,
+            known_keys: HashMap::new(),
+            addr: Default::default(),
+        }
+    }
+
+    #[tracing::instrument(
+        name = "ConsulNodeGroupDiscoveryWatch::watch",
+        skip(self),
+        fields(index = ?self.index)
+    )]
+    async fn watch(&mut self) {
+        let options = QueryOptions {
+            wait_index: self.index,
+            wait_time: Some(self.wait_time),
+            ..Default::default()
+        };
+
+        let result = self
+            .consul_client
+            .list(&self.key_prefix, Some(&options))
+            .await;
+
+        match result {
+            Ok((kv_pairs, meta)) => {
+                self.index = next_consul_index(self.index, meta.last_index);
+                self.publish_changes(kv_pairs);
+            }
+            Err(e) => {
+                error!(
+                    error = format!("{:?}", e).as_str(),
+                    "Failed to watch node groups"
+                );
+
+                self.index = None;
+                tokio::time::delay_for(WATCH_RETRY_DELAY).await;
+            }
+        }
+
+        send!(self.addr.watch());
+    }
+
+    fn publish_changes(&mut self, kv_pairs: Vec<KVPair>) {
+        for change in key_changes(&mut self.known_keys, &kv_pairs) {
+            match change {
+                NodeGroupChange::Changed(node_group) => send!(self
+                    .observer
+                    .observe_node_group_definition(self.source, node_group)),
+                NodeGroupChange::Removed(group) => send!(self
+                    .observer
+                    .observe_node_group_definition_removal(self.source, group)),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Actor for ConsulNodeGroupDiscoveryWatch {
+    #[tracing::instrument(name = "ConsulNodeGroupDiscoveryWatch::started", skip(self, addr))]
+    async fn started(&mut self, addr: Addr<Self>) -> ActorResult<()>
+    where
+        Self: Sized,
+    {
+        info!("Started");
+
+        self.addr = addr.downgrade();
+        send!(self.addr.watch());
+
+        Produces::ok(())
+    }
+
+    async fn error(&mut self, error: ActorError) -> bool {
+        actor::handle_error(error)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum NodeGroupChange {
+    Changed(NodeGroupDefinition),
+    Removed(String),
+}
+
+/// Re-parses the keys that were modified since the last query and replaces the known keys with
+/// the listed ones; the groups of keys that are gone get removed
+fn key_changes(
+    known_keys: &mut HashMap<String, WatchedKey>,
+    kv_pairs: &[KVPair],
+) -> Vec<NodeGroupChange> {
+    let mut changes = vec![];
+    let mut listed_keys = HashMap::with_capacity(kv_pairs.len());
+
+    for kv_pair in kv_pairs.iter() {
+        let watched_key = match known_keys.remove(&kv_pair.Key) {
+            Some(wk) if wk.modify_index == kv_pair.ModifyIndex => wk,
+            previous => {
+                let previous_group = previous.and_then(|wk| wk.group);
+
+                let group = match parse_node_group(kv_pair) {
+                    Some(node_group) => {
+                        let group = node_group.name.clone();
+
+                        // the key now holds another group
+                        if let Some(previous_group) = previous_group {
+                            if previous_group != group {
+                                changes.push(NodeGroupChange::Removed(previous_group));
+                            }
+                        }
+
+                        info!(key = kv_pair.Key.as_str(), "Node group changed");
+                        changes.push(NodeGroupChange::Changed(node_group));
+
+                        Some(group)
                     }
+                    // an invalid edit must not discard the group, it keeps its last valid
+                    // definition until the key gets fixed or deleted
+                    None => previous_group,
+                };
+
+                WatchedKey {
+                    modify_index: kv_pair.ModifyIndex,
+                    group,
                 }
-            })
-            .collect();
+            }
+        };
 
-        Produces::ok(node_groups)
+        listed_keys.insert(kv_pair.Key.clone(), watched_key);
+    }
+
+    // everything that is left over was deleted
+    let deleted_keys = std::mem::replace(known_keys, listed_keys);
+    for (key, watched_key) in deleted_keys {
+        if let Some(group) = watched_key.group {
+            info!(key = key.as_str(), "Node group key was deleted");
+            changes.push(NodeGroupChange::Removed(group));
+        }
+    }
+
+    changes
+}
+
+fn parse_node_group(kv_pair: &KVPair) -> Option<NodeGroupDefinition> {
+    let data = match base64::decode(&kv_pair.Value) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!(
+                error = format!("{:?}", e).as_str(),
+                key = kv_pair.Key.as_str(),
+                "Failed to decode base64 node group data"
+            );
+
+            return None;
+        }
+    };
+
+    match serde_yaml::from_reader::<&[u8], NodeGroupDefinition>(data.as_ref()) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            warn!(
+                error = format!("{:?}", e).as_str(),
+                key = kv_pair.Key.as_str(),
+                "Failed to parse node group yaml"
+            );
+
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kv_pair(key: &str, modify_index: u64, yaml: &str) -> KVPair {
+        serde_json::from_value(serde_json::json!({
+            "Key": key,
+            "CreateIndex": 1,
+            "ModifyIndex": modify_index,
+            "LockIndex": 0,
+            "Flags": 0,
+            "Value": base64::encode(yaml),
+        }))
+        .unwrap()
+    }
+
+    fn changed(name: &str) -> NodeGroupChange {
+        NodeGroupChange::Changed(NodeGroupDefinition {
+            name: name.to_owned(),
+            config: None,
+        })
+    }
+
+    #[test]
+    fn test_only_modified_keys_are_published() {
+        let mut known_keys = HashMap::new();
+
+        let changes = key_changes(
+            &mut known_keys,
+            &[kv_pair("groups/edge", 1, "name: edge\n")],
+        );
+        assert_eq!(vec![changed("edge")], changes);
+
+        let changes = key_changes(
+            &mut known_keys,
+            &[
+                kv_pair("groups/edge", 1, "name: edge\n"),
+                kv_pair("groups/core", 2, "name: core\n"),
+            ],
+        );
+        assert_eq!(vec![changed("core")], changes);
+    }
+
+    #[test]
+    fn test_deleted_key_removes_its_group() {
+        let mut known_keys = HashMap::new();
+        key_changes(
+            &mut known_keys,
+            &[kv_pair("groups/edge", 1, "name: edge\n")],
+        );
+
+        let changes = key_changes(&mut known_keys, &[]);
+
+        assert_eq!(vec![NodeGroupChange::Removed("edge".to_owned())], changes);
+        assert!(known_keys.is_empty());
+    }
+
+    #[test]
+    fn test_renamed_group_removes_the_previous_one() {
+        let mut known_keys = HashMap::new();
+        key_changes(
+            &mut known_keys,
+            &[kv_pair("groups/edge", 1, "name: edge\n")],
+        );
+
+        let changes = key_changes(
+            &mut known_keys,
+            &[kv_pair("groups/edge", 2, "name: edge-v2\n")],
+        );
+
+        assert_eq!(
+            vec![
+                NodeGroupChange::Removed("edge".to_owned()),
+                changed("edge-v2")
+            ],
+            changes
+        );
+    }
+
+    #[test]
+    fn test_invalid_edit_keeps_the_group() {
+        let mut known_keys = HashMap::new();
+        key_changes(
+            &mut known_keys,
+            &[kv_pair("groups/edge", 1, "name: edge\n")],
+        );
+
+        let changes = key_changes(&mut known_keys, &[kv_pair("groups/edge", 2, "- [")]);
+        assert!(changes.is_empty());
+
+        // the group is still known, so deleting the key removes it
+        let changes = key_changes(&mut known_keys, &[]);
+        assert_eq!(vec![NodeGroupChange::Removed("edge".to_owned())], changes);
     }
 }
@@ -1,12 +1,15 @@
-use tracing::info;
+use tracing::{info, warn};
 
+use crate::config::FileWatch;
+use crate::file_watcher::{FileChange, FileWatcher};
 use crate::node_groups::discovery::provider::NodeGroupDiscoveryProvider;
-use crate::node_groups::NodeGroup;
+use crate::node_groups::discovery::{NodeGroupDefinition, NodeGroupDefinitionObserver};
 use crate::{actor, utils};
-use act_zero::{Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
+use act_zero::{send, Actor, ActorError, ActorResult, Addr, Produces, WeakAddr};
 use anyhow::Context;
 use async_trait::async_trait;
 use futures::TryFutureExt;
+use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
 use std::io::BufReader;
@@ -14,16 +17,101 @@ use std::path::{Path, PathBuf};
 
 pub struct FileNodeGroupDiscovery {
     directory_path: PathBuf,
+    watch_config: Option<FileWatch>,
+    watcher: Option<FileWatcher>,
+    /// Source index and observer of the watched node group definitions
+    observer: Option<(usize, Addr<dyn NodeGroupDefinitionObserver>)>,
+    /// Node group names of the scanned files and of the files reported by the watcher
+    watched_files: HashMap<PathBuf, String>,
     addr: WeakAddr<Self>,
 }
 
 impl FileNodeGroupDiscovery {
-    pub fn new(directory_path: impl AsRef<Path>) -> Self {
+    pub fn new(directory_path: impl AsRef<Path>, watch_config: Option<FileWatch>) -> Self {
         FileNodeGroupDiscovery {
             directory_path: directory_path.as_ref().into(),
+            watch_config,
+            watcher: None,
+            observer: None,
+            watched_files: HashMap::new(),
             addr: Default::default(),
         }
     }
+
+    #[tracing::instrument(
+        name = "FileNodeGroupDiscovery::handle_file_change"
+        skip(self),
+        fields(path = %self.directory_path.display())
+    )]
+    async fn handle_file_change(&mut self, change: FileChange) {
+        let (source, observer) = match self.observer.as_ref() {
+            Some((source, observer)) => (*source, observer),
+            None => return,
+        };
+
+        match change {
+            FileChange::Changed(path) => match parse_node_group_file(&path) {
+                Ok(node_group) => {
+                    let previous_group = self
+                        .watched_files
+                        .insert(path.clone(), node_group.name.clone());
+
+                    if let Some(previous_group) = previous_group {
+                        if previous_group != node_group.name {
+                            send!(observer
+                                .observe_node_group_definition_removal(source, previous_group));
+                        }
+                    }
+
+                    send!(observer.observe_node_group_definition(source, node_group));
+                }
+                Err(e) => warn!(
+                    error = format!("{:?}", e).as_str(),
+                    "Failed to parse changed node group file"
+                ),
+            },
+            FileChange::Removed(path) => {
+                if let Some(group) = self.watched_files.remove(&path) {
+                    send!(observer.observe_node_group_definition_removal(source, group));
+                }
+            }
+            FileChange::Rescan => {
+                let node_groups = scan_for_node_groups(&self.directory_path).await;
+                let previous_files = std::mem::take(&mut self.watched_files);
+                let node_groups = self.track_files(node_groups);
+
+                for group in previous_files.into_iter().map(|(_, group)| group) {
+                    if !node_groups
+                        .iter()
+                        .any(|node_group| node_group.name == group)
+                    {
+                        send!(observer.observe_node_group_definition_removal(source, group));
+                    }
+                }
+
+                for node_group in node_groups {
+                    send!(observer.observe_node_group_definition(source, node_group));
+                }
+            }
+        }
+    }
+
+    /// Remembers the node group names of the scanned files, so that their later removal can be
+    /// published
+    fn track_files(
+        &mut self,
+        node_groups: Vec<(PathBuf, NodeGroupDefinition)>,
+    ) -> Vec<NodeGroupDefinition> {
+        self.watched_files = node_groups
+            .iter()
+            .map(|(path, node_group)| (path.clone(), node_group.name.clone()))
+            .collect();
+
+        node_groups
+            .into_iter()
+            .map(|(_, node_group)| node_group)
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -62,8 +150,44 @@ impl NodeGroupDiscoveryProvider for FileNodeGroupDiscovery {
         skip(self),
         fields(path = %self.directory_path.display())
     )]
-    async fn discover_node_groups(&mut self) -> ActorResult<Vec<NodeGroup>> {
-        Produces::ok(scan_for_node_groups(&self.directory_path).await)
+    async fn discover_node_groups(&mut self) -> ActorResult<Vec<NodeGroupDefinition>> {
+        let node_groups = scan_for_node_groups(&self.directory_path).await;
+
+        Produces::ok(self.track_files(node_groups))
+    }
+
+    #[tracing::instrument(
+        name = "FileNodeGroupDiscovery::watch_node_groups"
+        skip(self, observer),
+        fields(path = %self.directory_path.display())
+    )]
+    async fn watch_node_groups(
+        &mut self,
+        source: usize,
+        observer: Addr<dyn NodeGroupDefinitionObserver>,
+    ) -> ActorResult<()> {
+        let watch_config = match (&self.watch_config, &self.watcher) {
+            (Some(watch_config), None) => watch_config,
+            _ => return Produces::ok(()),
+        };
+
+        let addr = self.addr.clone();
+        let watcher = FileWatcher::new(&self.directory_path, watch_config.debounce, move |c| {
+            send!(addr.handle_file_change(c))
+        });
+
+        match watcher {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+                self.observer = Some((source, observer));
+            }
+            Err(e) => warn!(
+                error = format!("{:?}", e).as_str(),
+                "Failed to watch node group files, falling back to polling"
+            ),
+        }
+
+        Produces::ok(())
     }
 }
 
@@ -83,7 +207,7 @@ impl fmt::Debug for FileNodeGroupDiscovery {
     }
 }
 
-fn parse_node_group_file(path: impl AsRef<Path>) -> anyhow::Result<NodeGroup> {
+fn parse_node_group_file(path: impl AsRef<Path>) -> anyhow::Result<NodeGroupDefinition> {
     let file = File::open(&path)?;
     let reader = BufReader::new(file);
     let result = serde_yaml::from_reader(reader)
@@ -92,9 +216,13 @@ fn parse_node_group_file(path: impl AsRef<Path>) -> anyhow::Result<NodeGroup> {
     Ok(result)
 }
 
-async fn scan_for_node_groups(path: impl AsRef<Path>) -> Vec<NodeGroup> {
+async fn scan_for_node_groups(path: impl AsRef<Path>) -> Vec<(PathBuf, NodeGroupDefinition)> {
     utils::scan_for_files(&path)
-        .and_then(|files| utils::parse_files(files, parse_node_group_file))
+        .and_then(|files| {
+            utils::parse_files(files, |path| {
+                parse_node_group_file(&path).map(|node_group| (path, node_group))
+            })
+        })
         .await
         .unwrap_or_else(|_| vec![])
 }
@@ -0,0 +1,217 @@
+use crate::actor;
+use crate::node_groups::discovery::provider::NodeGroupDiscoveryProvider;
+use crate::node_groups::discovery::NodeGroupDefinition;
+use act_zero::{Actor, ActorError, ActorResult, Addr, Produces};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use http::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use http::{HeaderValue, StatusCode};
+use reqwest::Url;
+use tracing::info;
+
+pub struct HttpNodeGroupDiscovery {
+    http_client: reqwest::Client,
+    url: Url,
+    bearer_token: Option<String>,
+    cache: Option<CachedResponse>,
+}
+
+/// Last successful response, revalidated with the etag / last-modified headers
+struct CachedResponse {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    node_groups: Vec<NodeGroupDefinition>,
+}
+
+impl HttpNodeGroupDiscovery {
+    pub fn new(http_client: reqwest::Client, url: Url, bearer_token: Option<String>) -> Self {
+        Self {
+            http_client,
+            url,
+            bearer_token,
+            cache: None,
+        }
+    }
+
+    async fn fetch_node_groups(&mut self) -> anyhow::Result<Vec<NodeGroupDefinition>> {
+        let mut request_builder = self.http_client.get(self.url.clone());
+
+        if let Some(bearer_token) = self.bearer_token.as_ref() {
+            request_builder = request_builder.bearer_auth(bearer_token);
+        }
+
+        if let Some(cache) = self.cache.as_ref() {
+            if let Some(etag) = cache.etag.as_ref() {
+                request_builder = request_builder.header(IF_NONE_MATCH, etag.clone());
+            }
+
+            if let Some(last_modified) = cache.last_modified.as_ref() {
+                request_builder = request_builder.header(IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+
+        let response = request_builder.send().await?;
+
+        match (response.status(), self.cache.as_ref()) {
+            (StatusCode::NOT_MODIFIED, Some(cache)) => return Ok(cache.node_groups.clone()),
+            (status, _) if !status.is_success() => {
+                return Err(anyhow!("Received bad response with status {}", status))
+            }
+            _ => {}
+        }
+
+        let etag = response.headers().get(ETAG).cloned();
+        let last_modified = response.headers().get(LAST_MODIFIED).cloned();
+
+        // yaml is a superset of json, so this covers both formats
+        let body = response.bytes().await?;
+        let node_groups: Vec<NodeGroupDefinition> =
+            serde_yaml::from_slice(&body).context("Failed to parse node groups")?;
+
+        info!(count = node_groups.len(), "Fetched node groups");
+
+        self.cache = Some(CachedResponse {
+            etag,
+            last_modified,
+            node_groups: node_groups.clone(),
+        });
+
+        Ok(node_groups)
+    }
+}
+
+#[async_trait]
+impl Actor for HttpNodeGroupDiscovery {
+    #[tracing::instrument(
+        name = "HttpNodeGroupDiscovery::started",
+        skip(self, _addr),
+        fields(url = %self.url)
+    )]
+    async fn started(&mut self, _addr: Addr<Self>) -> ActorResult<()>
+    where
+        Self: Sized,
+    {
+        info!("Started");
+
+        Produces::ok(())
+    }
+
+    async fn error(&mut self, error: ActorError) -> bool {
+        actor::handle_error(error)
+    }
+}
+
+#[async_trait]
+impl NodeGroupDiscoveryProvider for HttpNodeGroupDiscovery {
+    #[tracing::instrument(
+        name = "HttpNodeGroupDiscovery::discover_node_groups",
+        skip(self),
+        fields(url = %self.url)
+    )]
+    async fn discover_node_groups(&mut self) -> ActorResult<Vec<NodeGroupDefinition>> {
+        let node_groups = self.fetch_node_groups().await.map_err(actor::Error::from)?;
+
+        Produces::ok(node_groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    const NODE_GROUPS: &str = "- name: edge\n";
+
+    fn ok_response(etag: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nETag: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            etag,
+            NODE_GROUPS.len(),
+            NODE_GROUPS
+        )
+    }
+
+    fn empty_response(status: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status
+        )
+    }
+
+    /// Answers one connection per response and returns the received request heads in lowercase
+    async fn serve(responses: Vec<String>) -> (Url, JoinHandle<Vec<String>>) {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/node-groups", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let mut requests = vec![];
+
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+
+                stream.write_all(response.as_bytes()).await.unwrap();
+                requests.push(String::from_utf8_lossy(&request).to_lowercase());
+            }
+
+            requests
+        });
+
+        (url.parse().unwrap(), handle)
+    }
+
+    fn names(node_groups: &[NodeGroupDefinition]) -> Vec<&str> {
+        node_groups.iter().map(|ng| ng.name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_not_modified_response_returns_cached_node_groups() {
+        let (url, server) = serve(vec![
+            ok_response("\"v1\""),
+            empty_response("304 Not Modified"),
+        ])
+        .await;
+        let mut discovery = HttpNodeGroupDiscovery::new(reqwest::Client::new(), url, None);
+
+        assert_eq!(
+            vec!["edge"],
+            names(&discovery.fetch_node_groups().await.unwrap())
+        );
+        assert_eq!(
+            vec!["edge"],
+            names(&discovery.fetch_node_groups().await.unwrap())
+        );
+
+        let requests = server.await.unwrap();
+        assert!(!requests[0].contains("if-none-match"));
+        assert!(requests[1].contains("if-none-match: \"v1\""));
+    }
+
+    #[tokio::test]
+    async fn test_bad_response_fails() {
+        let (url, _server) = serve(vec![empty_response("500 Internal Server Error")]).await;
+        let mut discovery = HttpNodeGroupDiscovery::new(reqwest::Client::new(), url, None);
+
+        assert!(discovery.fetch_node_groups().await.is_err());
+        assert!(discovery.cache.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_not_modified_without_cache_fails() {
+        let (url, _server) = serve(vec![empty_response("304 Not Modified")]).await;
+        let mut discovery = HttpNodeGroupDiscovery::new(reqwest::Client::new(), url, None);
+
+        assert!(discovery.fetch_node_groups().await.is_err());
+    }
+}
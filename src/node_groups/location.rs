@@ -0,0 +1,149 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Location a node group spreads its nodes to; the nodes are distributed proportionally to the
+/// weights, so equal weights balance the group evenly
+#[derive(Debug, Clone, Deserialize)]
+pub struct NodeLocation {
+    pub name: String,
+    #[serde(default = "default_location_weight")]
+    pub weight: u32,
+}
+
+fn default_location_weight() -> u32 {
+    1
+}
+
+/// Node count of a location relative to its weight; unknown or no longer configured locations
+/// are over-represented by definition
+fn share(locations: &[NodeLocation], counts: &HashMap<String, u32>, location: Option<&str>) -> f64 {
+    let weight = location.and_then(|name| {
+        locations
+            .iter()
+            .find(|l| l.name == name)
+            .map(|l| l.weight)
+            .filter(|weight| *weight > 0)
+    });
+
+    match (location, weight) {
+        (Some(name), Some(weight)) => *counts.get(name).unwrap_or(&0) as f64 / weight as f64,
+        _ => f64::INFINITY,
+    }
+}
+
+/// Picks the location whose share stays the lowest with one more node, earlier locations win ties
+pub fn pick_location(locations: &[NodeLocation], counts: &HashMap<String, u32>) -> Option<String> {
+    let mut best: Option<(&NodeLocation, f64)> = None;
+
+    for location in locations.iter().filter(|l| l.weight > 0) {
+        let count = *counts.get(&location.name).unwrap_or(&0) + 1;
+        let share = count as f64 / location.weight as f64;
+
+        if best.map_or(true, |(_, best_share)| share < best_share) {
+            best = Some((location, share));
+        }
+    }
+
+    best.map(|(location, _)| location.name.clone())
+}
+
+/// Orders the candidates for removal, so that each removal takes a node from the location that
+/// is the most over-represented at that point; earlier candidates win ties
+pub fn removal_order(
+    locations: &[NodeLocation],
+    counts: &HashMap<String, u32>,
+    candidates: &[Option<&str>],
+) -> Vec<usize> {
+    let mut counts = counts.clone();
+    let mut remaining = (0..candidates.len()).collect::<Vec<usize>>();
+    let mut order = Vec::with_capacity(candidates.len());
+
+    while !remaining.is_empty() {
+        let mut best: Option<(usize, f64)> = None;
+
+        for (position, candidate) in remaining.iter().enumerate() {
+            let share = share(locations, &counts, candidates[*candidate]);
+
+            if best.map_or(true, |(_, best_share)| share > best_share) {
+                best = Some((position, share));
+            }
+        }
+
+        let (position, _) = best.unwrap();
+        let candidate = remaining.remove(position);
+
+        if let Some(count) = candidates[candidate].and_then(|name| counts.get_mut(name)) {
+            *count = count.saturating_sub(1);
+        }
+
+        order.push(candidate);
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locations(weights: &[(&str, u32)]) -> Vec<NodeLocation> {
+        weights
+            .iter()
+            .map(|(name, weight)| NodeLocation {
+                name: (*name).to_owned(),
+                weight: *weight,
+            })
+            .collect()
+    }
+
+    fn counts(counts: &[(&str, u32)]) -> HashMap<String, u32> {
+        counts
+            .iter()
+            .map(|(name, count)| ((*name).to_owned(), *count))
+            .collect()
+    }
+
+    #[test]
+    fn test_pick_location_follows_weights() {
+        let locations = locations(&[("fsn1", 2), ("nbg1", 1)]);
+        let mut current = counts(&[]);
+
+        let mut picked = vec![];
+        for _ in 0..6 {
+            let location = pick_location(&locations, &current).unwrap();
+            *current.entry(location.clone()).or_default() += 1;
+            picked.push(location);
+        }
+
+        assert_eq!(vec!["fsn1", "fsn1", "nbg1", "fsn1", "fsn1", "nbg1"], picked);
+    }
+
+    #[test]
+    fn test_pick_location_without_locations() {
+        assert_eq!(None, pick_location(&[], &counts(&[])));
+    }
+
+    #[test]
+    fn test_removal_order_prefers_over_represented_locations() {
+        let locations = locations(&[("fsn1", 1), ("nbg1", 1)]);
+        let current = counts(&[("fsn1", 1), ("nbg1", 3)]);
+
+        let order = removal_order(
+            &locations,
+            &current,
+            &[Some("fsn1"), Some("nbg1"), Some("nbg1"), Some("nbg1")],
+        );
+
+        assert_eq!(vec![1, 2, 0, 3], order);
+    }
+
+    #[test]
+    fn test_removal_order_prefers_unknown_locations() {
+        let locations = locations(&[("fsn1", 1)]);
+        let current = counts(&[("fsn1", 2), ("hel1", 1)]);
+
+        let order = removal_order(&locations, &current, &[Some("fsn1"), None, Some("hel1")]);
+
+        assert_eq!(vec![1, 2, 0], order);
+    }
+}
@@ -1,26 +1,41 @@
 mod controller;
 pub mod discovery;
+mod location;
 mod scaler;
 
+use crate::config::{HetznerNodeGroup, NodeRecovery};
+use location::NodeLocation;
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 pub use controller::NodeGroupsController;
 pub use scaler::NodeGroupScaler;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct NodeGroup {
     name: String,
     config: Option<Config>,
+    /// Discovery sources the group definition was merged from, highest priority first
+    source: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     node_bandwidth_capacity: BandwidthCapacity,
+    /// Capacity of nodes with a specific server type, like the fallbacks of a cloud provider
+    #[serde(default)]
+    server_type_bandwidth_capacities: HashMap<String, BandwidthCapacity>,
     bandwidth_thresholds: BandwidthThresholds,
     min_active_nodes: Option<u32>,
     max_nodes: Option<u32>,
     min_spare_nodes: Option<u32>,
     max_spare_nodes: Option<u32>,
+    max_quarantined_nodes: Option<u32>,
+    recovery: Option<NodeRecovery>,
+    hetzner: Option<HetznerNodeGroup>,
+    #[serde(default)]
+    locations: Vec<NodeLocation>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -34,3 +49,178 @@ pub struct BandwidthThresholds {
     scale_up_percent: u8,
     scale_down_percent: u8,
 }
+
+/// Semantic error of a single node group config field
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl Config {
+    pub fn node_bandwidth_capacity(&self, server_type: Option<&str>) -> &BandwidthCapacity {
+        server_type
+            .and_then(|server_type| self.server_type_bandwidth_capacities.get(server_type))
+            .unwrap_or(&self.node_bandwidth_capacity)
+    }
+
+    /// Checks the config for values that would deserialize fine but cannot be scaled with
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = vec![];
+        let mut invalid =
+            |field: &'static str, message: String| errors.push(ValidationError { field, message });
+
+        if self.node_bandwidth_capacity.tx_bps == 0 {
+            invalid(
+                "node_bandwidth_capacity.tx_bps",
+                String::from("must be greater than 0"),
+            );
+        }
+
+        if self.node_bandwidth_capacity.rx_bps == 0 {
+            invalid(
+                "node_bandwidth_capacity.rx_bps",
+                String::from("must be greater than 0"),
+            );
+        }
+
+        for capacity in self.server_type_bandwidth_capacities.values() {
+            if capacity.tx_bps == 0 || capacity.rx_bps == 0 {
+                invalid(
+                    "server_type_bandwidth_capacities",
+                    String::from("tx_bps and rx_bps must be greater than 0"),
+                );
+            }
+        }
+
+        let thresholds = &self.bandwidth_thresholds;
+
+        if thresholds.scale_up_percent == 0 || thresholds.scale_up_percent > 100 {
+            invalid(
+                "bandwidth_thresholds.scale_up_percent",
+                format!(
+                    "must be between 1 and 100, got {}",
+                    thresholds.scale_up_percent
+                ),
+            );
+        }
+
+        if thresholds.scale_down_percent >= thresholds.scale_up_percent {
+            invalid(
+                "bandwidth_thresholds.scale_down_percent",
+                format!(
+                    "must be less than scale_up_percent ({}), got {}",
+                    thresholds.scale_up_percent, thresholds.scale_down_percent
+                ),
+            );
+        }
+
+        if let (Some(min), Some(max)) = (self.min_spare_nodes, self.max_spare_nodes) {
+            if min > max {
+                invalid(
+                    "min_spare_nodes",
+                    format!("must not exceed max_spare_nodes ({}), got {}", max, min),
+                );
+            }
+        }
+
+        if let (Some(min), Some(max)) = (self.min_active_nodes, self.max_nodes) {
+            if min > max {
+                invalid(
+                    "min_active_nodes",
+                    format!("must not exceed max_nodes ({}), got {}", max, min),
+                );
+            }
+        }
+
+        let mut location_names = HashSet::new();
+        for location in self.locations.iter() {
+            if location.weight == 0 {
+                invalid(
+                    "locations.weight",
+                    format!("must be greater than 0 for location {}", location.name),
+                );
+            }
+
+            if !location_names.insert(location.name.as_str()) {
+                invalid(
+                    "locations.name",
+                    format!("location {} is listed more than once", location.name),
+                );
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(extra: &str) -> Config {
+        let yaml = format!(
+            "node_bandwidth_capacity:\n  tx_bps: 1000\n  rx_bps: 1000\n{}",
+            extra
+        );
+
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    #[test]
+    fn test_validate_accepts_valid_config() {
+        let config = config(
+            "bandwidth_thresholds:\n  scale_up_percent: 80\n  scale_down_percent: 40\n\
+             min_spare_nodes: 1\nmax_spare_nodes: 2\n",
+        );
+
+        assert_eq!(Ok(()), config.validate());
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_fields() {
+        let config = config(
+            "bandwidth_thresholds:\n  scale_up_percent: 40\n  scale_down_percent: 40\n\
+             min_spare_nodes: 3\nmax_spare_nodes: 2\n",
+        );
+
+        let fields = config
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.field)
+            .collect::<Vec<&str>>();
+
+        assert_eq!(
+            vec!["bandwidth_thresholds.scale_down_percent", "min_spare_nodes"],
+            fields
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_locations() {
+        let config = config(
+            "bandwidth_thresholds:\n  scale_up_percent: 80\n  scale_down_percent: 40\n\
+             locations:\n  - name: fsn1\n    weight: 0\n  - name: nbg1\n  - name: nbg1\n",
+        );
+
+        let fields = config
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.field)
+            .collect::<Vec<&str>>();
+
+        assert_eq!(vec!["locations.weight", "locations.name"], fields);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::cloud_provider::{CloudNodeInfo, CloudProvider};
+use crate::cloud_provider::{CloudNodeInfo, CloudProvider, NodeSpec};
 use crate::dns_provider::DnsProvider;
 use crate::node::discovery::{
     NodeDiscoveryData, NodeDiscoveryObserver, NodeDiscoveryProvider, NodeDiscoveryState,
@@ -6,9 +6,11 @@ use crate::node::discovery::{
 use crate::node::exploration::NodeExplorationObserver;
 use crate::node::stats::NodeStatsStreamFactory;
 use crate::node::{
-    HostnameGenerator, Node, NodeController, NodeControllerProviders, NodeDrainingCause, NodeState,
-    NodeStateInfo, NodeStateObserver, NodeStats, NodeStatsInfo, NodeStatsObserver,
+    HostnameGenerator, LifecycleHooks, Node, NodeController, NodeControllerProviders,
+    NodeDrainingCause, NodeState, NodeStateInfo, NodeStateObserver, NodeStats, NodeStatsInfo,
+    NodeStatsObserver,
 };
+use crate::node_groups::location::{self, NodeLocation};
 use crate::node_groups::{Config, NodeGroup};
 use crate::{actor, AppConfig};
 use act_zero::runtimes::tokio::{spawn_actor, Timer};
@@ -33,6 +35,7 @@ pub struct NodeGroupScaler {
     dns_provider: Addr<dyn DnsProvider>,
     node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
     hostname_generator: Arc<dyn HostnameGenerator>,
+    lifecycle_hooks: Arc<LifecycleHooks>,
     scale_locks_spare: SpareScaleLocks,
     config: AppConfig,
     is_terminating: bool,
@@ -49,6 +52,22 @@ struct ScalingNode {
     controller: Addr<NodeController>,
     last_stats: Option<NodeStats>,
     state: NodeState,
+    quarantined_at: Option<Instant>,
+    location: Option<String>,
+    server_type: Option<String>,
+}
+
+impl ScalingNode {
+    /// Nodes that are about to leave the group don't count towards the spread over locations
+    fn is_located(&self) -> bool {
+        matches!(
+            self.state,
+            NodeState::Unready
+                | NodeState::Ready
+                | NodeState::Active
+                | NodeState::Draining(NodeDrainingCause::Scaling)
+        )
+    }
 }
 
 impl NodeGroupScaler {
@@ -59,6 +78,7 @@ impl NodeGroupScaler {
         dns_provider: Addr<dyn DnsProvider>,
         node_stats_stream_factory: Box<dyn NodeStatsStreamFactory>,
         hostname_generator: Arc<dyn HostnameGenerator>,
+        lifecycle_hooks: Arc<LifecycleHooks>,
         config: AppConfig,
     ) -> Self {
         NodeGroupScaler {
@@ -75,6 +95,7 @@ impl NodeGroupScaler {
             dns_provider,
             node_stats_stream_factory,
             hostname_generator,
+            lifecycle_hooks,
             config,
         }
     }
@@ -125,6 +146,7 @@ impl Tick for NodeGroupScaler {
     async fn tick(&mut self) -> ActorResult<()> {
         if self.timer.tick() {
             send!(self.addr.remove_deprovisioned_nodes());
+            send!(self.addr.release_excess_quarantined_nodes());
 
             if self.should_scale() {
                 send!(self.addr.check_scale_locks());
@@ -191,6 +213,16 @@ impl NodeExplorationObserver for NodeGroupScaler {
             self.nodes.insert(node_info.hostname.clone(), node);
         }
 
+        let node = self.nodes.get_mut(&node_info.hostname).unwrap();
+        if node_info.location.is_some() {
+            node.location = node_info.location.clone();
+        }
+        node.server_type = node_info.server_type.clone();
+
+        send!(self
+            .cloud_provider
+            .reconcile_node(node_info.clone(), self.create_node_spec()));
+
         let node = self.nodes.get(&node_info.hostname).unwrap();
         send!(node.controller.explored_node(node_info));
     }
@@ -210,7 +242,20 @@ impl NodeStateObserver for NodeGroupScaler {
         );
 
         if let Some(scaling_node) = self.nodes.get_mut(&state_info.hostname) {
+            scaling_node.quarantined_at = match (&scaling_node.quarantined_at, &state_info.state) {
+                (None, NodeState::Quarantined(_)) => Some(Instant::now()),
+                (Some(_), NodeState::Quarantined(_)) => scaling_node.quarantined_at,
+                _ => None,
+            };
+
             scaling_node.state = state_info.state;
+
+            if state_info.location.is_some() {
+                scaling_node.location = state_info.location;
+            }
+            if state_info.server_type.is_some() {
+                scaling_node.server_type = state_info.server_type;
+            }
         }
     }
 }
@@ -269,6 +314,11 @@ impl NodeGroupScaler {
         }
     }
 
+    /// Groups without a configuration only keep their nodes until they are discarded
+    pub async fn is_configured(&mut self) -> ActorResult<bool> {
+        Produces::ok(self.node_group.config.is_some())
+    }
+
     #[tracing::instrument(
         name = "NodeGroupScaler::scale_spare"
         skip(self),
@@ -328,19 +378,13 @@ impl NodeGroupScaler {
     }
 
     fn determine_necessary_spare_node_change(&self, ready_nodes: u32) -> i32 {
-        let (min_spare_nodes, mut max_spare_nodes) = {
+        // min spare nodes never exceeds max spare nodes, see `Config::validate`
+        let (min_spare_nodes, max_spare_nodes) = {
             let config = self.node_group.config.as_ref().unwrap();
 
             (config.min_spare_nodes, config.max_spare_nodes)
         };
 
-        // ensure max spare nodes is always equal or greater than min spare nodes
-        if let (Some(min), Some(max)) = (min_spare_nodes, max_spare_nodes) {
-            if min > max {
-                max_spare_nodes = Some(min);
-            }
-        }
-
         let node_change = max_spare_nodes
             .map(|max| {
                 if ready_nodes > max {
@@ -405,6 +449,12 @@ impl NodeGroupScaler {
                 let locks = &self.scale_locks_spare;
                 move |(h, _n)| !locks.up.contains_key(*h) && !locks.down.contains_key(*h)
             })
+            .collect::<Vec<(&String, &ScalingNode)>>();
+
+        let location_counts = count_locations(self.nodes.values().filter(|n| n.is_located()));
+        let ready_nodes = self
+            .order_for_removal(ready_nodes, &location_counts)
+            .into_iter()
             .take(amount as usize);
 
         let mut locks = Vec::with_capacity(amount as usize);
@@ -560,7 +610,12 @@ impl NodeGroupScaler {
     }
 
     fn try_provision_new_node(&mut self, target_state: NodeDiscoveryState) -> Option<ScaleLock> {
-        let current_nodes = self.nodes.len() as u32;
+        // quarantined nodes are kept for inspection only and don't count against the limit
+        let current_nodes = self
+            .nodes
+            .values()
+            .filter(|n| !n.state.is_quarantined())
+            .count() as u32;
         let reached_node_limit = self
             .node_group
             .config
@@ -594,10 +649,20 @@ impl NodeGroupScaler {
             .hostname_generator
             .generate_hostname(self.node_group.name.as_ref());
 
-        info!(%hostname, "Provision node");
+        let location = self.pick_location();
+
+        info!(%hostname, location = ?location, "Provision node");
+
+        let spec = NodeSpec {
+            location: location.clone(),
+            ..self.create_node_spec()
+        };
 
-        let node = self.create_scaling_node(&hostname);
-        send!(node.controller.provision_node(target_state));
+        let node = ScalingNode {
+            location,
+            ..self.create_scaling_node(&hostname)
+        };
+        send!(node.controller.provision_node(target_state, spec));
 
         self.nodes.insert(hostname.clone(), node);
 
@@ -607,12 +672,16 @@ impl NodeGroupScaler {
     fn scale_down(&mut self) -> Option<Vec<ScaleLock>> {
         let min_active_nodes = get_min_active_nodes(&self.node_group);
 
-        let mut active_nodes_info = self
+        let active_nodes_info = self
             .nodes
             .iter()
             .filter(|(_k, v)| v.state.is_active())
             .collect::<Vec<(&String, &ScalingNode)>>();
 
+        let location_counts =
+            count_locations(active_nodes_info.iter().map(|(_hostname, node)| *node));
+        let mut active_nodes_info = self.order_for_removal(active_nodes_info, &location_counts);
+
         if min_active_nodes >= active_nodes_info.len() as u32 {
             info!(
                 min_active_nodes,
@@ -621,7 +690,7 @@ impl NodeGroupScaler {
             );
             None
         } else {
-            let (hostname, node) = active_nodes_info.pop().unwrap();
+            let (hostname, node) = active_nodes_info.remove(0);
             Some(vec![self.deprovision_node(
                 hostname.as_str(),
                 node,
@@ -659,17 +728,20 @@ impl NodeGroupScaler {
     fn calculate_bandwidth_usage_percent(&self) -> u8 {
         #[derive(Default, Debug)]
         struct ValueAcc {
-            count: u64,
+            capacity: u64,
             bandwidth: u64,
         }
 
         let node_group_config = self.node_group.config.as_ref().unwrap();
-        let tx_bps_node_capacity = node_group_config.node_bandwidth_capacity.tx_bps;
 
         let result = self.nodes.values().filter(|n| n.state.is_active()).fold(
             ValueAcc::default(),
             |mut acc, n| {
-                acc.count += 1;
+                let tx_bps_node_capacity = node_group_config
+                    .node_bandwidth_capacity(n.server_type.as_ref().map(|s| s.as_str()))
+                    .tx_bps;
+
+                acc.capacity += tx_bps_node_capacity;
                 acc.bandwidth += n
                     .last_stats
                     .as_ref()
@@ -679,7 +751,7 @@ impl NodeGroupScaler {
             },
         );
 
-        let max_capacity = result.count * tx_bps_node_capacity;
+        let max_capacity = result.capacity;
 
         match max_capacity {
             0 => 0,
@@ -699,6 +771,46 @@ impl NodeGroupScaler {
             .retain(|_, scaling_node| !matches!(scaling_node.state, NodeState::Deprovisioned));
     }
 
+    #[tracing::instrument(
+        name = "NodeGroupScaler::release_excess_quarantined_nodes",
+        skip(self),
+        fields(
+            group = %self.node_group.name
+        )
+    )]
+    async fn release_excess_quarantined_nodes(&mut self) {
+        let max_quarantined_nodes = match self
+            .node_group
+            .config
+            .as_ref()
+            .and_then(|c| c.max_quarantined_nodes)
+        {
+            Some(v) => v as usize,
+            None => return,
+        };
+
+        let mut quarantined_nodes = self
+            .nodes
+            .iter()
+            .filter_map(|(h, n)| n.quarantined_at.map(|qa| (qa, h, n)))
+            .collect::<Vec<(Instant, &String, &ScalingNode)>>();
+
+        if quarantined_nodes.len() <= max_quarantined_nodes {
+            return;
+        }
+
+        // release the oldest quarantined nodes first
+        quarantined_nodes.sort_by_key(|(qa, _h, _n)| *qa);
+
+        let excess_nodes = quarantined_nodes.len() - max_quarantined_nodes;
+        for (_qa, hostname, node) in quarantined_nodes.into_iter().take(excess_nodes) {
+            info!(%hostname, max_quarantined_nodes, "Release quarantined node");
+            send!(node
+                .controller
+                .deprovision_node(NodeDrainingCause::Termination));
+        }
+    }
+
     #[tracing::instrument(
         name = "NodeGroupScaler:update_node_group_config",
         skip(self),
@@ -710,6 +822,47 @@ impl NodeGroupScaler {
         self.node_group.config = node_group_config;
     }
 
+    /// Picks the location that keeps the group closest to the configured spread
+    fn pick_location(&self) -> Option<String> {
+        let locations = &self.node_group.config.as_ref()?.locations;
+
+        pick_node_location(locations, self.nodes.values())
+    }
+
+    /// Orders the nodes so that removing them from the front evens out the spread over locations,
+    /// without configured locations the order stays as it is
+    fn order_for_removal<'a>(
+        &self,
+        nodes: Vec<(&'a String, &'a ScalingNode)>,
+        location_counts: &HashMap<String, u32>,
+    ) -> Vec<(&'a String, &'a ScalingNode)> {
+        let locations = match self.node_group.config.as_ref() {
+            Some(config) if !config.locations.is_empty() => &config.locations,
+            _ => return nodes,
+        };
+
+        let candidates = nodes
+            .iter()
+            .map(|(_hostname, node)| node.location.as_ref().map(|l| l.as_str()))
+            .collect::<Vec<Option<&str>>>();
+
+        location::removal_order(locations, location_counts, &candidates)
+            .into_iter()
+            .map(|i| nodes[i])
+            .collect()
+    }
+
+    fn create_node_spec(&self) -> NodeSpec {
+        NodeSpec {
+            location: None,
+            hetzner: self
+                .node_group
+                .config
+                .as_ref()
+                .and_then(|c| c.hetzner.clone()),
+        }
+    }
+
     #[tracing::instrument(
         name = "NodeGroupScaler::create_scaling_node",
         skip(self, hostname),
@@ -734,12 +887,20 @@ impl NodeGroupScaler {
                 dns_provider: self.dns_provider.clone(),
             },
             self.node_stats_stream_factory.clone(),
+            Arc::clone(&self.lifecycle_hooks),
+            self.node_group
+                .config
+                .as_ref()
+                .and_then(|c| c.recovery.clone()),
             Arc::clone(&self.config),
         );
 
         ScalingNode {
             state: NodeState::Unready,
             last_stats: None,
+            quarantined_at: None,
+            location: None,
+            server_type: None,
             controller: spawn_actor(node_controller),
         }
     }
@@ -834,6 +995,93 @@ fn is_releasable_scale_lock(nodes: &HashMap<String, ScalingNode>, scale_lock: &S
     fulfilled_expectation
 }
 
+fn pick_node_location<'a>(
+    locations: &[NodeLocation],
+    nodes: impl Iterator<Item = &'a ScalingNode>,
+) -> Option<String> {
+    let location_counts = count_locations(nodes.filter(|n| n.is_located()));
+
+    location::pick_location(locations, &location_counts)
+}
+
+fn count_locations<'a>(nodes: impl Iterator<Item = &'a ScalingNode>) -> HashMap<String, u32> {
+    let mut counts = HashMap::new();
+
+    for location in nodes.filter_map(|node| node.location.as_ref()) {
+        *counts.entry(location.clone()).or_default() += 1;
+    }
+
+    counts
+}
+
 fn future_instant(secs: u64) -> Instant {
     Instant::now() + Duration::from_secs(secs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::QuarantineReason;
+
+    fn scaling_node(state: NodeState, location: &str) -> ScalingNode {
+        ScalingNode {
+            controller: Addr::detached(),
+            last_stats: None,
+            state,
+            quarantined_at: None,
+            location: Some(location.to_owned()),
+            server_type: None,
+        }
+    }
+
+    fn locations(names: &[&str]) -> Vec<NodeLocation> {
+        names
+            .iter()
+            .map(|name| NodeLocation {
+                name: (*name).to_owned(),
+                weight: 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pick_location_fills_the_emptiest_location() {
+        let nodes = vec![
+            scaling_node(NodeState::Active, "fsn1"),
+            scaling_node(NodeState::Ready, "fsn1"),
+            scaling_node(NodeState::Unready, "nbg1"),
+        ];
+
+        assert_eq!(
+            Some("nbg1".to_owned()),
+            pick_node_location(&locations(&["fsn1", "nbg1"]), nodes.iter())
+        );
+    }
+
+    #[test]
+    fn test_pick_location_ignores_leaving_nodes() {
+        let nodes = vec![
+            scaling_node(NodeState::Active, "fsn1"),
+            scaling_node(NodeState::Active, "nbg1"),
+            scaling_node(
+                NodeState::Quarantined(QuarantineReason::DiscoveryTimeout),
+                "nbg1",
+            ),
+            scaling_node(NodeState::Draining(NodeDrainingCause::Termination), "nbg1"),
+            scaling_node(NodeState::Deprovisioned, "nbg1"),
+            scaling_node(NodeState::Draining(NodeDrainingCause::Scaling), "fsn1"),
+        ];
+
+        assert_eq!(
+            Some("nbg1".to_owned()),
+            pick_node_location(&locations(&["fsn1", "nbg1"]), nodes.iter())
+        );
+    }
+
+    #[test]
+    fn test_pick_location_without_locations() {
+        let nodes = vec![scaling_node(NodeState::Active, "fsn1")];
+
+        assert_eq!(None, pick_node_location(&[], nodes.iter()));
+    }
+}
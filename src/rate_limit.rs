@@ -0,0 +1,263 @@
+use http::header::RETRY_AFTER;
+use http::{HeaderMap, StatusCode};
+use reqwest::{RequestBuilder, Response};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Share of the bucket that is kept for high priority requests only
+const LOW_PRIORITY_RESERVE: f64 = 0.2;
+
+/// Attempts of a high priority request that got rate limited
+const RATE_LIMITED_ATTEMPTS: usize = 3;
+
+/// Wait time after a rate limited request if the api didn't tell one
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Requests that create or delete resources are served before polling requests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    Low,
+    High,
+}
+
+/// Token bucket shared by all requests of an api client; it starts with the given limit and
+/// follows the `RateLimit-*` headers of the responses from there on
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    refilled_at: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.limit);
+        self.refilled_at = now;
+    }
+
+    /// Takes a token or returns how long to wait for one
+    fn try_take(&mut self, priority: Priority, now: Instant) -> Result<(), Duration> {
+        if let Some(blocked_until) = self.blocked_until {
+            if blocked_until > now {
+                return Err(blocked_until - now);
+            }
+
+            self.blocked_until = None;
+        }
+
+        self.refill(now);
+
+        let reserve = match priority {
+            Priority::Low => self.limit * LOW_PRIORITY_RESERVE,
+            Priority::High => 0.0,
+        };
+
+        let missing_tokens = reserve + 1.0 - self.tokens;
+        if missing_tokens <= 0.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else if self.refill_per_sec > 0.0 {
+            Err(Duration::from_secs_f64(
+                missing_tokens / self.refill_per_sec,
+            ))
+        } else {
+            Err(Duration::from_secs(1))
+        }
+    }
+
+    fn update(&mut self, limit: u64, remaining: u64, reset_in: Option<Duration>, now: Instant) {
+        self.limit = limit as f64;
+        self.tokens = remaining.min(limit) as f64;
+        self.refilled_at = now;
+
+        // the reset is the point in time the bucket is full again
+        if let Some(reset_in) = reset_in.filter(|d| *d > Duration::from_secs(0)) {
+            if remaining < limit {
+                self.refill_per_sec = (limit - remaining) as f64 / reset_in.as_secs_f64();
+            }
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Allows `limit` requests per `interval`
+    pub fn new(limit: u64, interval: Duration) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                limit: limit as f64,
+                tokens: limit as f64,
+                refill_per_sec: limit as f64 / interval.as_secs_f64(),
+                refilled_at: Instant::now(),
+                blocked_until: None,
+            })),
+        }
+    }
+
+    /// Waits for a token; low priority requests additionally wait until the bucket refilled
+    /// beyond the reserve, so they are delayed instead of failing
+    pub async fn acquire(&self, priority: Priority) {
+        loop {
+            let result = self
+                .bucket
+                .lock()
+                .unwrap()
+                .try_take(priority, Instant::now());
+
+            match result {
+                Ok(()) => return,
+                Err(wait) => tokio::time::delay_for(wait).await,
+            }
+        }
+    }
+
+    pub fn update(&self, headers: &HeaderMap) {
+        let limit = header_value(headers, "RateLimit-Limit");
+        let remaining = header_value(headers, "RateLimit-Remaining");
+        let reset_in = header_value(headers, "RateLimit-Reset").map(until_timestamp);
+
+        if let (Some(limit), Some(remaining)) = (limit, remaining) {
+            self.bucket
+                .lock()
+                .unwrap()
+                .update(limit, remaining, reset_in, Instant::now());
+        }
+    }
+
+    /// Stops all requests until the given duration passed, like after a `429 Too Many Requests`
+    pub fn block(&self, duration: Duration) {
+        let mut bucket = self.bucket.lock().unwrap();
+        let blocked_until = Instant::now() + duration;
+
+        if bucket.blocked_until.map_or(true, |b| b < blocked_until) {
+            bucket.blocked_until = Some(blocked_until);
+        }
+    }
+}
+
+/// Sends the request once the rate limiter allows it; rate limited high priority requests are
+/// retried after the time the api asks for, otherwise the error of `rate_limited` is returned
+pub async fn send<E>(
+    rate_limiter: &RateLimiter,
+    request_builder: RequestBuilder,
+    priority: Priority,
+    rate_limited: impl Fn(Duration) -> E,
+) -> Result<Response, E>
+where
+    E: From<reqwest::Error>,
+{
+    let mut request_builder = Some(request_builder);
+    let mut attempt = 1;
+
+    loop {
+        rate_limiter.acquire(priority).await;
+
+        let current_request_builder = request_builder.take().unwrap();
+        request_builder = current_request_builder.try_clone();
+
+        let response = current_request_builder.send().await?;
+        rate_limiter.update(response.headers());
+
+        if response.status() != StatusCode::TOO_MANY_REQUESTS {
+            return Ok(response);
+        }
+
+        let retry_after = retry_after(response.headers()).unwrap_or(DEFAULT_RETRY_AFTER);
+        rate_limiter.block(retry_after);
+
+        warn!(?retry_after, attempt, "Request got rate limited");
+
+        if priority == Priority::Low
+            || request_builder.is_none()
+            || attempt >= RATE_LIMITED_ATTEMPTS
+        {
+            return Err(rate_limited(retry_after));
+        }
+
+        attempt += 1;
+    }
+}
+
+/// Time to wait before retrying a rate limited request, from either the `Retry-After` or the
+/// `RateLimit-Reset` header
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    header_value(headers, RETRY_AFTER.as_str())
+        .map(Duration::from_secs)
+        .or_else(|| header_value(headers, "RateLimit-Reset").map(until_timestamp))
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+fn until_timestamp(timestamp: u64) -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    Duration::from_secs(timestamp.saturating_sub(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(limit: f64, tokens: f64) -> Bucket {
+        Bucket {
+            limit,
+            tokens,
+            refill_per_sec: 1.0,
+            refilled_at: Instant::now(),
+            blocked_until: None,
+        }
+    }
+
+    #[test]
+    fn test_low_priority_keeps_the_reserve() {
+        let mut bucket = bucket(10.0, 2.5);
+        let now = bucket.refilled_at;
+
+        assert_eq!(
+            Err(Duration::from_millis(500)),
+            bucket.try_take(Priority::Low, now)
+        );
+        assert_eq!(Ok(()), bucket.try_take(Priority::High, now));
+    }
+
+    #[test]
+    fn test_blocked_bucket_rejects_all_priorities() {
+        let mut bucket = bucket(10.0, 10.0);
+        let now = bucket.refilled_at;
+        bucket.blocked_until = Some(now + Duration::from_secs(5));
+
+        assert_eq!(
+            Err(Duration::from_secs(5)),
+            bucket.try_take(Priority::High, now)
+        );
+        assert_eq!(
+            Ok(()),
+            bucket.try_take(Priority::High, now + Duration::from_secs(5))
+        );
+    }
+
+    #[test]
+    fn test_update_derives_refill_rate_from_reset() {
+        let mut bucket = bucket(10.0, 10.0);
+        let now = bucket.refilled_at;
+
+        bucket.update(3600, 3000, Some(Duration::from_secs(1200)), now);
+
+        assert_eq!(3000.0, bucket.tokens);
+        assert_eq!(0.5, bucket.refill_per_sec);
+    }
+}
@@ -1,5 +1,8 @@
+use anyhow::Context;
 use futures::stream::{FuturesUnordered, StreamExt};
 use std::any::type_name;
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use tokio::fs::DirEntry;
 use tracing::error;
@@ -58,6 +61,22 @@ pub fn path_append(path: impl AsRef<Path>, append: &str) -> PathBuf {
     PathBuf::from(os)
 }
 
+/// Writes the file via a temporary file in the same directory that replaces the target in one
+/// rename, so readers never see a partially written file
+pub fn write_file_atomically(path: impl AsRef<Path>, contents: &[u8]) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let tmp_path = path_append(path, ".tmp");
+
+    let mut file = File::create(&tmp_path)
+        .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    Ok(())
+}
+
 pub fn type_name_val<T: ?Sized>(_: &T) -> &'static str {
     type_name::<T>()
 }